@@ -3,9 +3,69 @@
 #[ink::contract]
 /// Módulo que implementa un marketplace descentralizado usando ink!
 mod marketplace {
-    use ink::prelude::{string::String, vec::Vec};
+    use ink::env::call::{build_call, ExecutionInput, Selector};
+    use ink::prelude::{collections::{BTreeMap, BTreeSet}, string::String, vec::Vec};
     use ink::storage::Mapping;
 
+    /// Tiempo (en milisegundos) que debe pasar con una disputa abierta sin resolver
+    /// para que cualquiera pueda ejecutar el reembolso automático al comprador (30 días).
+    pub const PLAZO_RESOLUCION_DISPUTA: u64 = 30 * 24 * 60 * 60 * 1000;
+
+    /// Plazo inicial (14 días en milisegundos) tras el envío para que el owner pueda forzar
+    /// la recepción de una orden que el comprador nunca confirmó.
+    pub const TIMEOUT_RECEPCION_POR_DEFECTO: u64 = 14 * 24 * 60 * 60 * 1000;
+
+    /// Cantidad máxima de categorías que se consideran en un filtro por varias categorías.
+    pub const MAX_CATEGORIAS_FILTRO: usize = 10;
+
+    /// Cantidad máxima de productos que se pueden actualizar en un solo batch de stock.
+    pub const MAX_ACTUALIZACIONES_STOCK: usize = 50;
+
+    /// Cantidad máxima de productos que se pueden publicar en un solo batch.
+    pub const MAX_PUBLICACIONES_BATCH: usize = 50;
+
+    /// Calificaciones mínimas que necesita un vendedor para que su promedio cuente como confiable.
+    pub const MIN_CALIFICACIONES_CONFIABLE: u32 = 3;
+
+    /// Cantidad de puntos básicos que equivalen al 100%.
+    pub const BPS_BASE: u128 = 10_000;
+
+    /// Comisión máxima configurable, en puntos básicos (10000 = 100%).
+    pub const MAX_COMISION_BPS: u16 = 10_000;
+
+    /// Descuento máximo de un cupón, en puntos básicos (10000 = 100%).
+    pub const MAX_DESCUENTO_BPS: u16 = 10_000;
+
+    /// Longitud máxima (en bytes) del comentario que acompaña una calificación.
+    pub const MAX_LONGITUD_COMENTARIO: usize = 280;
+
+    /// Cantidad máxima de IDs de producto que recorre una búsqueda por nombre.
+    pub const MAX_ESCANEO_BUSQUEDA: u128 = 1_000;
+
+    /// Cantidad máxima de IDs de orden que recorre una llamada a `gmv_en_rango`.
+    pub const MAX_ESCANEO_ORDENES: u128 = 1_000;
+
+    /// Cantidad máxima de productos que un usuario puede tener en favoritos.
+    pub const MAX_FAVORITOS: usize = 100;
+
+    /// Cantidad máxima de IDs que se pueden consultar juntos con `obtener_productos`.
+    pub const MAX_PRODUCTOS_CONSULTA: usize = 100;
+
+    /// Cantidad máxima de productos destacados por el owner.
+    pub const MAX_DESTACADOS: usize = 20;
+
+    /// Duración máxima de una reserva de stock (1 hora en milisegundos).
+    pub const MAX_DURACION_RESERVA: Timestamp = 60 * 60 * 1000;
+
+    /// Cantidad máxima de IDs de reserva que recorre una llamada a `liberar_reservas_vencidas`.
+    pub const MAX_ESCANEO_RESERVAS: u128 = 50;
+
+    /// Cantidad máxima de reservas sin confirmar que puede tener un comprador a la vez.
+    pub const MAX_RESERVAS_POR_COMPRADOR: u32 = 5;
+
+    /// Clave (vendedor, categoría) de la reputación de un vendedor dentro de una categoría.
+    pub type VendedorCategoria = (AccountId, String);
+
     /// Enum que representa los roles posibles de un usuario dentro del marketplace.
     #[derive(Clone, PartialEq, Eq, Debug)]
     #[ink::scale_derive(Encode, Decode, TypeInfo)]
@@ -47,11 +107,39 @@ mod marketplace {
         }
     }
 
+    /// Errores del estándar PSP22, para decodificar las respuestas del token de pago.
+    #[derive(Clone, PartialEq, Eq, Debug)]
+    #[ink::scale_derive(Encode, Decode, TypeInfo)]
+    pub enum PSP22Error {
+        Custom(String),
+        InsufficientBalance,
+        InsufficientAllowance,
+        ZeroRecipientAddress,
+        ZeroSenderAddress,
+        SafeTransferCheckFailed(String),
+    }
+
     /// Enum que representa los estados posibles de una orden de compra.
     #[derive(Clone, PartialEq, Eq , Debug)]
     #[ink::scale_derive(Encode, Decode, TypeInfo)]
     #[cfg_attr(feature = "std", derive(ink::storage::traits::StorageLayout))]
-    pub enum EstadoOrden { Pendiente, Enviado, Recibido, Cancelada }
+    pub enum EstadoOrden { Pendiente, Enviado, Recibido, Cancelada, Disputado, ParcialmenteEnviado }
+
+    /// Motivo por el que se canceló una orden.
+    #[derive(Clone, PartialEq, Eq, Debug)]
+    #[ink::scale_derive(Encode, Decode, TypeInfo)]
+    #[cfg_attr(feature = "std", derive(ink::storage::traits::StorageLayout))]
+    pub enum MotivoCancelacion {
+        /// Comprador y vendedor aceptaron cancelar (incluye la cancelación automática
+        /// y la cancelación parcial de todas las unidades).
+        MutuoAcuerdo,
+        /// El vendedor no envió la orden dentro de `timeout_envio`.
+        TimeoutEnvio,
+        /// El vendedor rechazó la orden o canceló todas sus órdenes pendientes.
+        RechazoVendedor,
+        /// Una disputa se resolvió a favor del comprador.
+        Disputa,
+    }
 
     /// Enum que representa errores posibles en las operaciones del contrato.
     #[derive(Clone, PartialEq, Eq, Debug)]
@@ -72,6 +160,26 @@ mod marketplace {
         CalificacionInvalida,
         YaCalificado,
         OrdenNoRecibida,
+        KycRequerido,
+        DisputaNoAbierta,
+        TimeoutNoAlcanzado,
+        ProductoInactivo,
+        TransferenciaFallida,
+        ProductoConOrdenesAbiertas,
+        PagoIncorrecto,
+        ComisionInvalida,
+        Pausado,
+        UsuarioBaneado,
+        CompraPropia,
+        ReputacionInsuficiente,
+        LimitePorOrdenExcedido,
+        YaEnFavoritos,
+        CuponInvalido,
+        CategoriaNoPermitida,
+        ReservaNoExiste,
+        ReservaVencida,
+        LimiteReservasAlcanzado,
+        YaDestacado,
     }
 
     /// Estructura que almacena las calificaciones de una orden.
@@ -83,6 +191,10 @@ mod marketplace {
         pub calificacion_comprador: Option<u8>,
         /// Calificación del vendedor al comprador (1-5).
         pub calificacion_vendedor: Option<u8>,
+        /// Comentario del comprador sobre el vendedor.
+        pub comentario_comprador: Option<String>,
+        /// Comentario del vendedor sobre el comprador.
+        pub comentario_vendedor: Option<String>,
     }
 
     /// Estructura que representa la reputación acumulada de un usuario.
@@ -98,6 +210,10 @@ mod marketplace {
         pub total_calificaciones_vendedor: u32,
         /// Suma de todas las calificaciones recibidas como vendedor.
         pub suma_calificaciones_vendedor: u128,
+        /// Cantidad de calificaciones de 1 a 5 recibidas como comprador (índice = puntaje - 1).
+        pub distribucion_comprador: [u32; 5],
+        /// Cantidad de calificaciones de 1 a 5 recibidas como vendedor (índice = puntaje - 1).
+        pub distribucion_vendedor: [u32; 5],
     }
 
     impl ReputacionData {
@@ -108,6 +224,8 @@ mod marketplace {
                 suma_calificaciones_comprador: 0,
                 total_calificaciones_vendedor: 0,
                 suma_calificaciones_vendedor: 0,
+                distribucion_comprador: [0; 5],
+                distribucion_vendedor: [0; 5],
             }
         }
 
@@ -131,8 +249,41 @@ mod marketplace {
             }
         }
 
+        /// Promedio como vendedor multiplicado por 100 (4.5 -> 450), para no perder los decimales
+        /// que trunca `promedio_vendedor`.
+        pub fn promedio_vendedor_escalado(&self) -> Option<u128> {
+            if self.total_calificaciones_vendedor > 0 {
+                self.suma_calificaciones_vendedor
+                    .checked_mul(100)?
+                    .checked_div(self.total_calificaciones_vendedor as u128)
+            } else {
+                None
+            }
+        }
+
+        /// Promedio de todas las calificaciones recibidas, sumando ambos roles.
+        pub fn promedio_global(&self) -> Option<u128> {
+            let suma = self.suma_calificaciones_comprador
+                .checked_add(self.suma_calificaciones_vendedor)?;
+            let total = self.total_calificaciones_comprador
+                .checked_add(self.total_calificaciones_vendedor)?;
+            suma.checked_div(total as u128)
+        }
+
+        /// Promedio como comprador multiplicado por 100, análogo a `promedio_vendedor_escalado`.
+        pub fn promedio_comprador_escalado(&self) -> Option<u128> {
+            if self.total_calificaciones_comprador > 0 {
+                self.suma_calificaciones_comprador
+                    .checked_mul(100)?
+                    .checked_div(self.total_calificaciones_comprador as u128)
+            } else {
+                None
+            }
+        }
+
         /// Agrega una calificación como comprador.
         pub fn agregar_calificacion_comprador(&mut self, calificacion: u8) -> Result<(), ContractError> {
+            Self::sumar_a_distribucion(&mut self.distribucion_comprador, calificacion)?;
             self.total_calificaciones_comprador = self.total_calificaciones_comprador
                 .checked_add(1)
                 .ok_or(ContractError::Overflow)?;
@@ -144,6 +295,7 @@ mod marketplace {
 
         /// Agrega una calificación como vendedor.
         pub fn agregar_calificacion_vendedor(&mut self, calificacion: u8) -> Result<(), ContractError> {
+            Self::sumar_a_distribucion(&mut self.distribucion_vendedor, calificacion)?;
             self.total_calificaciones_vendedor = self.total_calificaciones_vendedor
                 .checked_add(1)
                 .ok_or(ContractError::Overflow)?;
@@ -152,6 +304,18 @@ mod marketplace {
                 .ok_or(ContractError::Overflow)?;
             Ok(())
         }
+
+        /// Incrementa el contador del puntaje (1-5) en la distribución indicada.
+        fn sumar_a_distribucion(distribucion: &mut [u32; 5], calificacion: u8) -> Result<(), ContractError> {
+            let indice = (calificacion as usize)
+                .checked_sub(1)
+                .filter(|i| *i < 5)
+                .ok_or(ContractError::CalificacionInvalida)?;
+            distribucion[indice] = distribucion[indice]
+                .checked_add(1)
+                .ok_or(ContractError::Overflow)?;
+            Ok(())
+        }
     }
 
     impl Default for ReputacionData {
@@ -160,6 +324,18 @@ mod marketplace {
         }
     }
 
+    /// Estructura que agrupa la reputación de un usuario junto con la distribución de sus puntajes.
+    #[derive(Clone, PartialEq, Eq, Debug)]
+    #[ink::scale_derive(Encode, Decode, TypeInfo)]
+    pub struct ReputacionDetallada {
+        /// Reputación acumulada del usuario.
+        pub data: ReputacionData,
+        /// Cantidad de calificaciones de 1 a 5 recibidas como vendedor.
+        pub distribucion_vendedor: [u32; 5],
+        /// Cantidad de calificaciones de 1 a 5 recibidas como comprador.
+        pub distribucion_comprador: [u32; 5],
+    }
+
     /// Estructura que representa un producto publicado por un vendedor.
     #[derive(Clone, PartialEq, Eq, Debug)]
     #[ink::scale_derive(Encode, Decode, TypeInfo)]
@@ -171,18 +347,35 @@ mod marketplace {
         pub cantidad: u32,
         pub categoria: String,
         pub vendedor: AccountId,
+        /// Indica si el producto está a la venta. Un producto inactivo conserva su registro.
+        pub activo: bool,
+        /// Promedio mínimo como comprador exigido para comprar este producto.
+        /// Los compradores sin calificaciones no quedan restringidos.
+        pub reputacion_minima_comprador: Option<u128>,
+        /// Cantidad máxima de unidades por orden. `None` significa sin límite.
+        pub limite_por_orden: Option<u32>,
     }
 
     impl Producto {
         /// Valida que los datos del producto sean correctos.
         /// Retorna un error si algún campo está vacío o tiene valor 0.
         pub fn validar(&self) -> Result<(), ContractError> {
+            self.validar_datos()?;
+            if self.cantidad == 0 {
+                return Err(ContractError::DatosInvalidos);
+            }
+            Ok(())
+        }
+
+        /// Igual que `validar` pero sin exigir stock, para productos ya publicados que
+        /// pueden haberse quedado sin unidades.
+        pub fn validar_datos(&self) -> Result<(), ContractError> {
             if self.nombre.is_empty() 
                 || self.descripcion.is_empty() 
                 || self.categoria.is_empty() {
                 return Err(ContractError::DatosInvalidos);
             }
-            if self.precio == 0 || self.cantidad == 0 {
+            if self.precio == 0 || self.limite_por_orden == Some(0) {
                 return Err(ContractError::DatosInvalidos);
             }
             Ok(())
@@ -197,6 +390,18 @@ mod marketplace {
         }
     }
 
+    /// Stock apartado por un comprador durante el checkout, todavía sin pagar.
+    #[derive(Clone, PartialEq, Eq, Debug)]
+    #[ink::scale_derive(Encode, Decode, TypeInfo)]
+    #[cfg_attr(feature = "std", derive(ink::storage::traits::StorageLayout))]
+    pub struct Reserva {
+        pub comprador: AccountId,
+        pub producto_id: u128,
+        pub cantidad: u32,
+        /// Momento a partir del cual la reserva puede liberarse.
+        pub vence_en: Timestamp,
+    }
+
     /// Estructura que representa una orden de compra realizada por un comprador.
     #[derive(Clone, PartialEq, Eq, Debug)]
     #[ink::scale_derive(Encode, Decode, TypeInfo)]
@@ -209,12 +414,35 @@ mod marketplace {
         pub estado: EstadoOrden,
         pub comprador_acepta_cancelar: bool,
         pub vendedor_acepta_cancelar: bool,
+        /// Unidades que el comprador pidió cancelar y que esperan la aceptación del vendedor.
+        pub cancelacion_parcial_solicitada: Option<u32>,
+        /// Unidades ya despachadas por el vendedor. Cuando llega a `cantidad` la orden pasa a Enviado.
+        pub cantidad_enviada: u32,
+        /// Momento en que se creó la orden.
+        pub creada_en: Timestamp,
+        /// Momento en que se completó el envío (None si aún no se envió todo).
+        pub enviada_en: Option<Timestamp>,
+        /// Momento en que el comprador confirmó la recepción (None si aún no fue recibida).
+        pub recibida_en: Option<Timestamp>,
+        /// Momento en que se abrió una disputa sobre la orden (None si nunca se abrió).
+        pub disputa_abierta_en: Option<Timestamp>,
+        /// Por qué se canceló la orden (None si no está cancelada).
+        pub motivo_cancelacion: Option<MotivoCancelacion>,
+        /// Precio del producto al crear la orden, sin descuentos. No cambia si luego se edita el precio.
+        pub precio_unitario: u128,
+        /// Importe total de la orden (precio * cantidad) al momento de crearla.
+        /// Es el monto que el contrato retiene en escrow hasta la recepción; al reembolsarse
+        /// por cancelación queda en cero.
+        pub monto: Balance,
     }
 
     impl Orden {
         /// Verifica si la orden puede ser cancelada.
         pub fn puede_cancelarse(&self) -> bool {
-            matches!(self.estado, EstadoOrden::Pendiente | EstadoOrden::Enviado)
+            matches!(
+                self.estado,
+                EstadoOrden::Pendiente | EstadoOrden::ParcialmenteEnviado | EstadoOrden::Enviado
+            )
         }
 
         /// Marca la orden como cancelada si ambos participantes están de acuerdo.
@@ -228,6 +456,81 @@ mod marketplace {
         }
     }
 
+    /// Evento emitido cuando se crea una orden de compra.
+    #[ink(event)]
+    pub struct OrdenCreada {
+        orden_id: u128,
+        #[ink(topic)]
+        comprador: AccountId,
+        #[ink(topic)]
+        vendedor: AccountId,
+        producto_id: u128,
+    }
+
+    /// Evento emitido cuando el vendedor marca una orden como enviada.
+    #[ink(event)]
+    pub struct OrdenEnviada {
+        orden_id: u128,
+        #[ink(topic)]
+        comprador: AccountId,
+        #[ink(topic)]
+        vendedor: AccountId,
+    }
+
+    /// Evento emitido cuando el comprador confirma la recepción de una orden.
+    #[ink(event)]
+    pub struct OrdenRecibida {
+        orden_id: u128,
+        #[ink(topic)]
+        comprador: AccountId,
+        #[ink(topic)]
+        vendedor: AccountId,
+    }
+
+    /// Evento emitido cuando una orden queda cancelada.
+    #[ink(event)]
+    pub struct OrdenCancelada {
+        orden_id: u128,
+        #[ink(topic)]
+        comprador: AccountId,
+        #[ink(topic)]
+        vendedor: AccountId,
+        motivo: MotivoCancelacion,
+    }
+
+    /// Evento emitido cuando un vendedor publica un nuevo producto.
+    #[ink(event)]
+    pub struct ProductoPublicado {
+        producto_id: u128,
+        vendedor: AccountId,
+        #[ink(topic)]
+        categoria: String,
+        precio: u128,
+    }
+
+    /// Evento emitido cuando cambia el stock de un producto.
+    #[ink(event)]
+    pub struct StockActualizado {
+        producto_id: u128,
+        cantidad_nueva: u32,
+    }
+
+    /// Evento emitido cuando se registra una calificación sobre una orden.
+    #[ink(event)]
+    pub struct CalificacionRegistrada {
+        orden_id: u128,
+        /// Usuario que recibe la calificación.
+        #[ink(topic)]
+        calificado: AccountId,
+        /// Usuario que emite la calificación.
+        calificador: AccountId,
+        puntaje: u8,
+        /// True si el calificado actúa como vendedor en la orden.
+        es_vendedor: bool,
+        /// Nuevo promedio del calificado en el rol correspondiente.
+        promedio: Option<u128>,
+    }
+
     /// Contrato Marketplace donde los usuarios pueden registrarse, publicar productos y crear órdenes.
     #[ink(storage)]
     pub struct Marketplace {
@@ -240,58 +543,220 @@ mod marketplace {
         /// Mapea un usuario con los IDs de productos que publicó.
         productos_por_usuario: Mapping<AccountId, Vec<u128>>,
 
+        /// Mapea una categoría con los IDs de productos publicados en ella.
+        productos_por_categoria: Mapping<String, Vec<u128>>,
+
         /// ID del próximo producto a registrar.
         siguiente_producto_id: u128,
 
+        /// Cantidad de productos publicados que no fueron eliminados.
+        productos_vivos: u128,
+
         /// Mapea un ID de orden a su estructura.
         ordenes: Mapping<u128, Orden>,
 
         /// Mapea un usuario con las órdenes que creó.
         ordenes_por_usuario: Mapping<AccountId, Vec<u128>>,
 
+        /// Mapea un vendedor con las órdenes recibidas sobre sus productos.
+        ordenes_por_vendedor: Mapping<AccountId, Vec<u128>>,
+
         /// ID de la próxima orden a registrar.
         siguiente_orden_id: u128,
 
+        /// Cantidad de órdenes creadas, independiente de los huecos en los IDs.
+        total_ordenes: u128,
+
+        /// Reservas de stock pendientes de confirmar, por ID.
+        reservas: Mapping<u128, Reserva>,
+
+        /// Reservas sin confirmar de cada comprador.
+        reservas_por_comprador: Mapping<AccountId, u32>,
+
+        /// Menor ID de reserva que puede seguir pendiente. Las anteriores ya se confirmaron
+        /// o liberaron, así que `liberar_reservas_vencidas` empieza a recorrer desde acá.
+        primera_reserva_pendiente: u128,
+
+        /// ID de la próxima reserva a registrar.
+        siguiente_reserva_id: u128,
+
         /// Mapea un ID de orden a sus calificaciones.
         calificaciones_por_orden: Mapping<u128, CalificacionesOrden>,
 
         /// Mapea un usuario a su reputación acumulada.
         reputaciones: Mapping<AccountId, ReputacionData>,
 
+        /// Calificaciones recibidas por cada producto: (suma, cantidad).
+        calificaciones_producto: Mapping<u128, (u128, u32)>,
+
+        /// Calificaciones que cada usuario otorgó a otros: (suma, cantidad).
+        calificaciones_dadas: Mapping<AccountId, (u128, u32)>,
+
         /// Mapea un producto a la cantidad de veces que ha sido vendido.
         ventas_por_producto: Mapping<u128, u32>,
 
+        /// Mapea un producto a la cantidad total de unidades vendidas (órdenes recibidas).
+        unidades_vendidas_por_producto: Mapping<u128, u32>,
+
         /// Mapea una categoría a estadísticas de ventas y calificaciones.
         /// La clave es la categoría como String.
         /// El valor es (total_ventas, suma_calificaciones, cantidad_calificaciones).
         estadisticas_por_categoria: Mapping<String, (u32, u128, u32)>,
 
+        /// Reputación de cada vendedor dentro de una categoría.
+        /// El valor es (suma_calificaciones, cantidad_calificaciones).
+        reputacion_vendedor_por_categoria: Mapping<VendedorCategoria, (u128, u32)>,
+
         /// Lista de todos los usuarios registrados (para reportes).
         /// Usamos un Mapping como lista indexada: (índice) -> AccountId.
         usuarios_registrados: Mapping<u32, AccountId>,
         /// Contador de usuarios registrados (para saber cuántos hay).
         contador_usuarios: u32,
+
+        /// Vendedores que completaron al menos una venta (orden recibida).
+        vendedores_con_ventas: Mapping<AccountId, bool>,
+        /// Cantidad de vendedores distintos con al menos una venta.
+        contador_vendedores_activos: u32,
+        /// Compradores que recibieron al menos una orden.
+        compradores_con_compras: Mapping<AccountId, bool>,
+        /// Cantidad de compradores distintos con al menos una orden recibida.
+        contador_compradores_activos: u32,
+
+        /// Cuenta administradora del marketplace (quien desplegó el contrato).
+        owner: AccountId,
+
+        /// Vendedores que aceptan de antemano la cancelación de órdenes pendientes.
+        cancelacion_automatica: Mapping<AccountId, bool>,
+
+        /// Cantidad de órdenes canceladas en las que participó cada usuario (como comprador o vendedor).
+        cancelaciones_por_usuario: Mapping<AccountId, u32>,
+
+        /// IDs de los productos que cada usuario marcó como favoritos.
+        favoritos: Mapping<AccountId, Vec<u128>>,
+
+        /// Productos destacados por el owner, en el orden en que se agregaron.
+        destacados: Vec<u128>,
+
+        /// Cuentas baneadas por el owner: no pueden registrarse, publicar ni comprar.
+        baneados: Mapping<AccountId, bool>,
+
+        /// Usuarios verificados por el owner (KYC).
+        verificados: Mapping<AccountId, bool>,
+
+        /// Si está activo, solo los compradores verificados pueden crear órdenes.
+        modo_alto_riesgo: bool,
+
+        /// Cupones de descuento vigentes: código -> descuento en puntos básicos.
+        cupones: Mapping<String, u16>,
+
+        /// Pausa de emergencia: bloquea las operaciones de compraventa, no las consultas.
+        pausado: bool,
+
+        /// Si es false, la reputación de un usuario solo la pueden leer él mismo y el owner.
+        reputacion_publica: bool,
+
+        /// Suma de los montos de órdenes que todavía pueden terminar en reembolso
+        /// (Pendiente, Enviado o Disputado).
+        liabilidad_reembolsos: Balance,
+
+        /// Comisiones acumuladas a favor del owner, pendientes de retiro.
+        fondos_acumulados: Balance,
+
+        /// Comisión que cobra el marketplace sobre cada venta, en puntos básicos.
+        comision_bps: u16,
+
+        /// Decimales con que se expresan `precio` y los montos. Es solo informativo para los frontends.
+        decimales_token: u8,
+
+        /// Tiempo (en milisegundos) tras el cual el comprador puede cancelar por su cuenta
+        /// una orden que sigue Pendiente.
+        timeout_envio: u64,
+
+        /// Tiempo (en milisegundos) desde el envío tras el cual el owner puede forzar la
+        /// recepción de una orden Enviada.
+        timeout_recepcion: u64,
+
+        /// Si está activo, solo se puede publicar en las categorías de `categorias_permitidas`.
+        categorias_restringidas: bool,
+
+        /// Categorías habilitadas por el owner cuando las categorías están restringidas.
+        categorias_permitidas: Mapping<String, bool>,
+
+        /// Contrato PSP22 con el que se paga. Si es None se usa el balance nativo.
+        token: Option<AccountId>,
     }
 
     impl Marketplace {
         /// Crea una nueva instancia del contrato con estructuras vacías.
+        /// `comision_bps` es la comisión sobre cada venta en puntos básicos (máximo 10000).
+        /// `timeout_envio` es el plazo en milisegundos que tiene el vendedor para enviar una
+        /// orden antes de que el comprador pueda cancelarla sin su consentimiento.
+        /// Con `categorias_restringidas` solo se aceptan las categorías que habilite el owner.
+        /// Si se indica `token`, el escrow, los pagos y los reembolsos se hacen con ese
+        /// contrato PSP22 en lugar del balance nativo.
+        /// `decimales` indica cuántos decimales usan los precios; solo sirve para mostrarlos.
         #[ink(constructor)]
-        pub fn new() -> Self {
-            Self {
+        pub fn new(
+            comision_bps: u16,
+            timeout_envio: u64,
+            categorias_restringidas: bool,
+            token: Option<AccountId>,
+            decimales: u8,
+        ) -> Result<Self, ContractError> {
+            if comision_bps > MAX_COMISION_BPS {
+                return Err(ContractError::ComisionInvalida);
+            }
+            Ok(Self {
                 roles: Mapping::default(),
                 productos: Mapping::default(),
                 productos_por_usuario: Mapping::default(),
+                productos_por_categoria: Mapping::default(),
                 siguiente_producto_id: 1,
+                productos_vivos: 0,
                 ordenes: Mapping::default(),
                 ordenes_por_usuario: Mapping::default(),
+                ordenes_por_vendedor: Mapping::default(),
                 siguiente_orden_id: 1,
+                total_ordenes: 0,
+                reservas: Mapping::default(),
+                reservas_por_comprador: Mapping::default(),
+                primera_reserva_pendiente: 1,
+                siguiente_reserva_id: 1,
                 calificaciones_por_orden: Mapping::default(),
                 reputaciones: Mapping::default(),
+                calificaciones_producto: Mapping::default(),
+                calificaciones_dadas: Mapping::default(),
                 ventas_por_producto: Mapping::default(),
+                unidades_vendidas_por_producto: Mapping::default(),
                 estadisticas_por_categoria: Mapping::default(),
+                reputacion_vendedor_por_categoria: Mapping::default(),
                 usuarios_registrados: Mapping::default(),
                 contador_usuarios: 0,
-            }
+                vendedores_con_ventas: Mapping::default(),
+                contador_vendedores_activos: 0,
+                compradores_con_compras: Mapping::default(),
+                contador_compradores_activos: 0,
+                owner: Self::env().caller(),
+                cancelacion_automatica: Mapping::default(),
+                cancelaciones_por_usuario: Mapping::default(),
+                favoritos: Mapping::default(),
+                destacados: Vec::new(),
+                baneados: Mapping::default(),
+                verificados: Mapping::default(),
+                modo_alto_riesgo: false,
+                cupones: Mapping::default(),
+                pausado: false,
+                reputacion_publica: true,
+                liabilidad_reembolsos: 0,
+                fondos_acumulados: 0,
+                comision_bps,
+                decimales_token: decimales,
+                timeout_envio,
+                timeout_recepcion: TIMEOUT_RECEPCION_POR_DEFECTO,
+                categorias_restringidas,
+                categorias_permitidas: Mapping::default(),
+                token,
+            })
         }
 
         /// Registra un nuevo usuario con un rol determinado.
@@ -308,19 +773,64 @@ mod marketplace {
             self._modificar_rol(caller, nuevo_rol)
         }
 
+        /// Registra al usuario con `rol` si es nuevo o, si ya estaba registrado, le suma el rol.
+        #[ink(message)]
+        pub fn registrar_o_actualizar_rol(&mut self, rol: Roles) -> Result<(), ContractError> {
+            let caller = self.env().caller();
+            self._registrar_o_actualizar_rol(caller, rol)
+        }
+
         /// Devuelve el rol de un usuario específico.
         #[ink(message)]
         pub fn obtener_rol(&self, usuario: AccountId) -> Option<Roles> {
             self._obtener_rol(usuario)
         }
 
+        /// Devuelve `(es_comprador, es_vendedor)` para un usuario, o None si no está registrado.
+        #[ink(message)]
+        pub fn permisos(&self, usuario: AccountId) -> Option<(bool, bool)> {
+            self._obtener_rol(usuario).map(|rol| (rol.es_comprador(), rol.es_vendedor()))
+        }
+
         /// Devuelve el estado de una orden específica.
         #[ink(message)]
         pub fn obtener_estado_orden(&self, orden_id: u128) -> Option<EstadoOrden> {
             self._obtener_estado_orden(orden_id)
         }
 
+        /// Devuelve la orden completa (comprador, vendedor, cantidad, estado, etc.).
+        #[ink(message)]
+        pub fn obtener_orden(&self, orden_id: u128) -> Option<Orden> {
+            self.ordenes.get(orden_id)
+        }
+
+        /// Devuelve `precio * cantidad` de una orden usando el precio actual del producto.
+        /// A diferencia de `monto`, no refleja descuentos ni cambios de precio posteriores.
+        /// Retorna None si la orden o el producto no existen o si el cálculo desborda.
+        #[ink(message)]
+        pub fn precio_total_orden(&self, orden_id: u128) -> Option<u128> {
+            let orden = self.ordenes.get(orden_id)?;
+            let producto = self.productos.get(orden.producto_id)?;
+            producto.precio.checked_mul(orden.cantidad as u128)
+        }
+
+        /// Devuelve el estado de negociación de la cancelación de una orden:
+        /// (estado actual, comprador_acepta, vendedor_acepta, puede_cancelarse).
+        #[ink(message)]
+        pub fn detalle_cancelacion(&self, orden_id: u128) -> Option<(EstadoOrden, bool, bool, bool)> {
+            self.ordenes.get(orden_id).map(|orden| {
+                let puede_cancelarse = orden.puede_cancelarse();
+                (
+                    orden.estado,
+                    orden.comprador_acepta_cancelar,
+                    orden.vendedor_acepta_cancelar,
+                    puede_cancelarse,
+                )
+            })
+        }
+
         /// Publica un nuevo producto para el usuario que llama.
+        /// `limite_por_orden` acota las unidades que se pueden comprar en una sola orden.
         #[ink(message)]
         pub fn publicar_producto(
             &mut self,
@@ -329,1412 +839,6612 @@ mod marketplace {
             precio: u128,
             cantidad: u32,
             categoria: String,
+            limite_por_orden: Option<u32>,
         ) -> Result<u128, ContractError> {
+            self._no_pausado()?;
             let caller = self.env().caller();
-            self._publicar_producto(caller, nombre, descripcion, precio, cantidad, categoria)
+            self._publicar_producto(caller, nombre, descripcion, precio, cantidad, categoria, limite_por_orden)
         }
 
-        /// Devuelve los productos publicados por el usuario que llama.
+        /// Publica varios productos en una sola llamada y devuelve sus IDs en el mismo orden.
+        /// Cada entrada es (nombre, descripcion, precio, cantidad, categoria). Si alguna es
+        /// inválida no se publica ninguna. Máximo `MAX_PUBLICACIONES_BATCH` por llamada.
         #[ink(message)]
-        pub fn ver_mis_productos(&self) -> Vec<(u128, Producto)> {
+        pub fn publicar_productos_batch(
+            &mut self,
+            productos: Vec<(String, String, u128, u32, String)>,
+        ) -> Result<Vec<u128>, ContractError> {
+            self._no_pausado()?;
             let caller = self.env().caller();
-            self._ver_mis_productos(caller)
+            self._publicar_productos_batch(caller, productos)
         }
 
-        /// Devuelve todos los productos publicados en el marketplace.
+        /// Edita la descripción y la categoría de un producto propio. El nombre no se puede
+        /// cambiar. Si cambia la categoría, las estadísticas ya acumuladas en
+        /// `estadisticas_por_categoria` quedan bajo la categoría anterior.
         #[ink(message)]
-        pub fn ver_todos_los_productos(&self) -> Vec<(u128, Producto)> {
-            self._ver_todos_los_productos()
+        pub fn editar_producto(
+            &mut self,
+            producto_id: u128,
+            descripcion: String,
+            categoria: String,
+        ) -> Result<(), ContractError> {
+            let caller = self.env().caller();
+            self._editar_producto(caller, producto_id, descripcion, categoria)
         }
 
-        /// Crea una nueva orden de compra para el producto indicado.
+        /// Actualiza el precio de un producto propio manteniendo su ID e historial de ventas.
         #[ink(message)]
-        pub fn crear_orden_de_compra(&mut self, producto_id: u128, cantidad: u32) -> Result<u128, ContractError> {
+        pub fn actualizar_precio_producto(
+            &mut self,
+            producto_id: u128,
+            nuevo_precio: u128,
+        ) -> Result<(), ContractError> {
             let caller = self.env().caller();
-            self._crear_orden(caller, producto_id, cantidad)
+            self._actualizar_precio_producto(caller, producto_id, nuevo_precio)
         }
 
-        /// Marca una orden como enviada (solo el vendedor puede hacerlo).
+        /// Fija (o quita con `None`) el promedio mínimo como comprador exigido para comprar
+        /// un producto propio.
         #[ink(message)]
-        pub fn marcar_orden_enviada(&mut self, orden_id: u128) -> Result<(), ContractError> {
+        pub fn establecer_reputacion_minima_comprador(
+            &mut self,
+            producto_id: u128,
+            minima: Option<u128>,
+        ) -> Result<(), ContractError> {
             let caller = self.env().caller();
-            self._marcar_enviada(caller, orden_id)
+            self._establecer_reputacion_minima_comprador(caller, producto_id, minima)
         }
 
-        /// Marca una orden como recibida (solo el comprador puede hacerlo).
+        /// Activa o desactiva la cancelación automática para el vendedor que llama: si está
+        /// activa, las órdenes pendientes se cancelan apenas el comprador lo solicita.
         #[ink(message)]
-        pub fn marcar_orden_recibida(&mut self, orden_id: u128) -> Result<(), ContractError> {
+        pub fn establecer_cancelacion_automatica(&mut self, activa: bool) -> Result<(), ContractError> {
             let caller = self.env().caller();
-            self._marcar_recibida(caller, orden_id)
+            self._establecer_cancelacion_automatica(caller, activa)
         }
 
-        /// El comprador solicita la cancelación de una orden.
+        /// Indica si un vendedor tiene activa la cancelación automática.
         #[ink(message)]
-        pub fn comprador_solicita_cancelacion(&mut self, orden_id: u128) -> Result<(), ContractError> {
+        pub fn tiene_cancelacion_automatica(&self, vendedor: AccountId) -> bool {
+            self.cancelacion_automatica.get(vendedor).unwrap_or(false)
+        }
+
+        /// Repone stock de un producto propio sin crear una nueva publicación.
+        #[ink(message)]
+        pub fn reponer_stock(&mut self, producto_id: u128, cantidad: u32) -> Result<(), ContractError> {
             let caller = self.env().caller();
-            self._solicitar_cancel_comprador(caller, orden_id)
+            self._reponer_stock(caller, producto_id, cantidad)
         }
 
-        /// El vendedor acepta la cancelación de una orden.
+        /// Fija el stock de varios productos propios en una sola llamada.
+        /// Recibe pares (producto_id, cantidad) y falla completo si algún producto no es del caller.
         #[ink(message)]
-        pub fn vendedor_acepta_cancelacion(&mut self, orden_id: u128) -> Result<(), ContractError> {
+        pub fn actualizar_stocks_batch(&mut self, actualizaciones: Vec<(u128, u32)>) -> Result<(), ContractError> {
             let caller = self.env().caller();
-            self._aceptar_cancel_vendedor(caller, orden_id)
+            self._actualizar_stocks_batch(caller, actualizaciones)
         }
 
-        /// El comprador califica al vendedor después de recibir la orden.
-        /// Solo se puede calificar si la orden está en estado Recibido.
+        /// Desactiva un producto propio: deja de estar a la venta pero conserva su registro.
         #[ink(message)]
-        pub fn comprador_califica_vendedor(
-            &mut self,
-            orden_id: u128,
-            calificacion: u8,
-        ) -> Result<(), ContractError> {
+        pub fn desactivar_producto(&mut self, producto_id: u128) -> Result<(), ContractError> {
             let caller = self.env().caller();
-            self._calificar_vendedor(caller, orden_id, calificacion)
+            self._cambiar_estado_producto(caller, producto_id, false)
         }
 
-        /// El vendedor califica al comprador después de recibir la orden.
-        /// Solo se puede calificar si la orden está en estado Recibido.
+        /// Vuelve a poner a la venta un producto propio desactivado (requiere stock disponible).
         #[ink(message)]
-        pub fn vendedor_califica_comprador(
-            &mut self,
-            orden_id: u128,
-            calificacion: u8,
-        ) -> Result<(), ContractError> {
+        pub fn reactivar_producto(&mut self, producto_id: u128) -> Result<(), ContractError> {
             let caller = self.env().caller();
-            self._calificar_comprador(caller, orden_id, calificacion)
+            self._cambiar_estado_producto(caller, producto_id, true)
         }
 
-        /// Obtiene la reputación de un usuario.
+        /// Elimina definitivamente un producto propio. No se permite si tiene órdenes abiertas.
         #[ink(message)]
-        pub fn obtener_reputacion(&self, usuario: AccountId) -> Option<ReputacionData> {
-            self._obtener_reputacion(usuario)
+        pub fn eliminar_producto(&mut self, producto_id: u128) -> Result<(), ContractError> {
+            let caller = self.env().caller();
+            self._eliminar_producto(caller, producto_id)
         }
 
-        /// Obtiene la reputación promedio como comprador de un usuario.
+        /// Devuelve (IDs de producto emitidos, productos vivos). La diferencia son los eliminados.
         #[ink(message)]
-        pub fn reputacion_como_comprador(&self, usuario: AccountId) -> Option<u128> {
-            self._obtener_reputacion(usuario)
-                .and_then(|r| r.promedio_comprador())
+        pub fn conteo_productos(&self) -> (u128, u128) {
+            (self.siguiente_producto_id.saturating_sub(1), self.productos_vivos)
         }
 
-        /// Obtiene la reputación promedio como vendedor de un usuario.
+        /// Cantidad de productos publicados que no fueron eliminados.
         #[ink(message)]
-        pub fn reputacion_como_vendedor(&self, usuario: AccountId) -> Option<u128> {
-            self._obtener_reputacion(usuario)
-                .and_then(|r| r.promedio_vendedor())
+        pub fn total_productos(&self) -> u128 {
+            self.productos_vivos
         }
 
-        /// Obtiene las calificaciones de una orden.
+        /// Cantidad de órdenes creadas en el marketplace.
         #[ink(message)]
-        pub fn obtener_calificaciones_orden(&self, orden_id: u128) -> Option<CalificacionesOrden> {
-            self.calificaciones_por_orden.get(orden_id)
+        pub fn total_ordenes(&self) -> u128 {
+            self.total_ordenes
         }
 
-        /// Obtiene la cantidad de ventas de un producto.
+        /// Devuelve los productos publicados por el usuario que llama.
         #[ink(message)]
-        pub fn obtener_ventas_producto(&self, producto_id: u128) -> u32 {
-            self.ventas_por_producto.get(producto_id).unwrap_or(0)
+        pub fn ver_mis_productos(&self) -> Vec<(u128, Producto)> {
+            let caller = self.env().caller();
+            self._ver_mis_productos(caller)
         }
 
-        /// Obtiene las estadísticas de una categoría.
+        /// Cantidad de productos activos que tiene publicados un vendedor.
         #[ink(message)]
-        pub fn obtener_estadisticas_categoria(&self, categoria: String) -> Option<(u32, u128, u32)> {
-            self.estadisticas_por_categoria.get(&categoria)
+        pub fn cantidad_productos_vendedor(&self, vendedor: AccountId) -> u32 {
+            self.productos_por_usuario
+                .get(vendedor)
+                .unwrap_or_default()
+                .into_iter()
+                .filter(|id| self.productos.get(id).is_some_and(|p| p.activo))
+                .count() as u32
         }
 
-        /// Obtiene la cantidad de órdenes de un usuario.
+        /// Agrega un producto existente a los favoritos del usuario que llama.
         #[ink(message)]
-        pub fn cantidad_ordenes_usuario(&self, usuario: AccountId) -> u32 {
-            self.ordenes_por_usuario
-                .get(&usuario)
-                .map(|v| v.len() as u32)
-                .unwrap_or(0)
+        pub fn agregar_favorito(&mut self, producto_id: u128) -> Result<(), ContractError> {
+            let caller = self.env().caller();
+            self._agregar_favorito(caller, producto_id)
         }
 
-        /// Obtiene todos los usuarios con reputación (para reportes).
-        /// Retorna un vector de tuplas (usuario, reputacion_data).
+        /// Quita un producto de los favoritos del usuario que llama.
         #[ink(message)]
-        pub fn obtener_usuarios_con_reputacion(&self) -> Vec<(AccountId, ReputacionData)> {
-            let mut resultado = Vec::new();
-            for i in 0..self.contador_usuarios {
-                if let Some(usuario) = self.usuarios_registrados.get(i) {
-                    if let Some(reputacion) = self.reputaciones.get(usuario) {
-                        resultado.push((usuario, reputacion));
-                    }
-                }
-            }
-            resultado
+        pub fn quitar_favorito(&mut self, producto_id: u128) -> Result<(), ContractError> {
+            let caller = self.env().caller();
+            self._quitar_favorito(caller, producto_id)
+        }
+
+        /// Devuelve los favoritos del usuario que llama. Los productos eliminados se omiten.
+        #[ink(message)]
+        pub fn ver_favoritos(&self) -> Vec<(u128, Producto)> {
+            let caller = self.env().caller();
+            self._ver_favoritos(caller)
         }
 
+        /// Agrega un producto existente a la lista de destacados (solo el owner).
+        #[ink(message)]
+        pub fn destacar_producto(&mut self, producto_id: u128) -> Result<(), ContractError> {
+            let caller = self.env().caller();
+            self._destacar_producto(caller, producto_id)
+        }
 
-        // ===== Funciones privadas =====
+        /// Quita un producto de la lista de destacados (solo el owner).
+        #[ink(message)]
+        pub fn quitar_destacado(&mut self, producto_id: u128) -> Result<(), ContractError> {
+            let caller = self.env().caller();
+            self._quitar_destacado(caller, producto_id)
+        }
 
-        /// Registra un nuevo usuario con el rol especificado.
-        fn _registrar_usuario(
-            &mut self, 
-            caller: AccountId, 
-            rol: Roles
-        ) -> Result<(), ContractError> {
-            if self.roles.contains(caller) {
-                return Err(ContractError::YaRegistrado);
+        /// Devuelve los productos destacados. Los productos eliminados se omiten.
+        #[ink(message)]
+        pub fn ver_destacados(&self) -> Vec<(u128, Producto)> {
+            self.destacados
+                .iter()
+                .filter_map(|&id| self.productos.get(id).map(|p| (id, p)))
+                .collect()
+        }
+
+        /// Devuelve cada ID pedido junto a su producto, o None si no existe o fue eliminado.
+        /// Acepta como máximo `MAX_PRODUCTOS_CONSULTA` IDs.
+        #[ink(message)]
+        pub fn obtener_productos(&self, ids: Vec<u128>) -> Result<Vec<(u128, Option<Producto>)>, ContractError> {
+            if ids.len() > MAX_PRODUCTOS_CONSULTA {
+                return Err(ContractError::DatosInvalidos);
             }
-            self.roles.insert(caller, &rol);
-            // Agregar a la lista de usuarios registrados
-            let index = self.contador_usuarios;
-            self.usuarios_registrados.insert(index, &caller);
-            self.contador_usuarios = index.checked_add(1).ok_or(ContractError::Overflow)?;
-            Ok(())
+            Ok(ids.into_iter().map(|id| (id, self.productos.get(id))).collect())
         }
 
-        /// Modifica el rol de un usuario, permitiendo solo agregar roles, no quitarlos.
-        fn _modificar_rol(
-            &mut self, 
-            caller: AccountId, 
-            nuevo_rol: Roles
+        /// Devuelve todos los productos activos publicados en el marketplace.
+        #[ink(message)]
+        pub fn ver_todos_los_productos(&self) -> Vec<(u128, Producto)> {
+            self._ver_todos_los_productos()
+        }
+
+        /// Devuelve hasta `limite` productos activos a partir del ID `inicio`, ordenados
+        /// por ID ascendente. Los IDs inexistentes o inactivos se saltean, por lo que
+        /// para pedir la página siguiente se usa el último ID recibido + 1.
+        #[ink(message)]
+        pub fn ver_productos_paginado(&self, inicio: u128, limite: u32) -> Vec<(u128, Producto)> {
+            self._ver_productos_paginado(inicio, limite)
+        }
+
+        /// Busca productos activos cuyo nombre contenga `termino`, sin distinguir mayúsculas.
+        /// Solo se ignoran mayúsculas ASCII: "Ñ" y "ñ" o letras acentuadas no se igualan.
+        /// Recorre como máximo los primeros `MAX_ESCANEO_BUSQUEDA` IDs; un término vacío no
+        /// devuelve resultados.
+        #[ink(message)]
+        pub fn buscar_productos(&self, termino: String) -> Vec<(u128, Producto)> {
+            self._buscar_productos(termino)
+        }
+
+        /// Devuelve cuántos productos activos hay en cada categoría, ordenado por nombre de
+        /// categoría. Las categorías sin productos activos no aparecen.
+        #[ink(message)]
+        pub fn conteo_por_categoria(&self) -> Vec<(String, u32)> {
+            let mut conteo: BTreeMap<String, u32> = BTreeMap::new();
+            for (_, producto) in self._ver_todos_los_productos() {
+                let total = conteo.entry(producto.categoria).or_insert(0);
+                *total = total.saturating_add(1);
+            }
+            conteo.into_iter().collect()
+        }
+
+        /// Devuelve los productos activos de una categoría (coincidencia exacta).
+        #[ink(message)]
+        pub fn ver_productos_por_categoria(&self, categoria: String) -> Vec<(u128, Producto)> {
+            self._ver_productos_por_categoria(categoria)
+        }
+
+        /// Devuelve hasta `limite` productos activos cuya categoría está en `categorias`,
+        /// recorriendo los IDs en orden ascendente a partir de `inicio`.
+        /// Solo se consideran las primeras `MAX_CATEGORIAS_FILTRO` categorías recibidas.
+        #[ink(message)]
+        pub fn productos_por_categorias(
+            &self,
+            categorias: Vec<String>,
+            inicio: u128,
+            limite: u32,
+        ) -> Vec<(u128, Producto)> {
+            self._productos_por_categorias(categorias, inicio, limite)
+        }
+
+        /// Crea una nueva orden de compra para el producto indicado.
+        /// Se debe transferir exactamente `precio * cantidad`, menos el descuento del cupón
+        /// `codigo` si se indica uno; el contrato retiene los fondos hasta que el comprador
+        /// marca la orden como recibida. Si el marketplace usa un token PSP22, el monto se
+        /// cobra con `transfer_from` (el comprador debe haberlo aprobado antes) y no se
+        /// acepta valor nativo.
+        #[ink(message, payable)]
+        pub fn crear_orden_de_compra(
+            &mut self,
+            producto_id: u128,
+            cantidad: u32,
+            codigo: Option<String>,
+        ) -> Result<u128, ContractError> {
+            self._no_pausado()?;
+            let caller = self.env().caller();
+            let pago = self.env().transferred_value();
+            // Primero se valida quién compra, así un pago incorrecto no oculta un error de permisos
+            self._validar_comprador(caller)?;
+            let descuento_bps = self._descuento_cupon(codigo)?;
+            self._validar_pago(producto_id, cantidad, descuento_bps, pago)?;
+            let orden_id = self._crear_orden_con_descuento(caller, producto_id, cantidad, descuento_bps)?;
+            // Con token PSP22 el escrow se cobra después de crear la orden; si falla se revierte todo
+            let monto = self.ordenes.get(orden_id).map(|orden| orden.monto).unwrap_or(0);
+            self._cobrar_token(caller, monto)?;
+            Ok(orden_id)
+        }
+
+        /// Marca una orden como enviada (solo el vendedor puede hacerlo).
+        /// Con `cantidad_enviada` se registra un envío parcial; `None` despacha todo lo que falta.
+        #[ink(message)]
+        pub fn marcar_orden_enviada(&mut self, orden_id: u128, cantidad_enviada: Option<u32>) -> Result<(), ContractError> {
+            self._no_pausado()?;
+            let caller = self.env().caller();
+            self._marcar_enviada(caller, orden_id, cantidad_enviada)
+        }
+
+        /// Marca una orden como recibida (solo el comprador puede hacerlo).
+        #[ink(message)]
+        pub fn marcar_orden_recibida(&mut self, orden_id: u128) -> Result<(), ContractError> {
+            self._no_pausado()?;
+            let caller = self.env().caller();
+            self._marcar_recibida(caller, orden_id)
+        }
+
+        /// Marca una orden como recibida y califica al vendedor en la misma transacción.
+        /// Si la calificación es inválida no se marca la recepción.
+        #[ink(message)]
+        pub fn marcar_recibida_y_calificar(&mut self, orden_id: u128, calificacion: u8) -> Result<(), ContractError> {
+            self._no_pausado()?;
+            let caller = self.env().caller();
+            self._marcar_recibida_y_calificar(caller, orden_id, calificacion)
+        }
+
+        /// El comprador solicita la cancelación de una orden.
+        #[ink(message)]
+        pub fn comprador_solicita_cancelacion(&mut self, orden_id: u128) -> Result<(), ContractError> {
+            let caller = self.env().caller();
+            self._solicitar_cancel_comprador(caller, orden_id)
+        }
+
+        /// El vendedor acepta la cancelación de una orden.
+        #[ink(message)]
+        pub fn vendedor_acepta_cancelacion(&mut self, orden_id: u128) -> Result<(), ContractError> {
+            let caller = self.env().caller();
+            self._aceptar_cancel_vendedor(caller, orden_id)
+        }
+
+        /// El comprador pide cancelar solo `cantidad` unidades de una orden. Se aplica cuando
+        /// el vendedor acepta (o de inmediato si tiene la cancelación automática activa y la
+        /// orden está Pendiente).
+        #[ink(message)]
+        pub fn solicitar_cancelacion_parcial(&mut self, orden_id: u128, cantidad: u32) -> Result<(), ContractError> {
+            let caller = self.env().caller();
+            self._solicitar_cancelacion_parcial(caller, orden_id, cantidad)
+        }
+
+        /// El vendedor acepta la cancelación parcial pedida por el comprador: se devuelven las
+        /// unidades al stock y se reembolsa la parte proporcional del escrow.
+        #[ink(message)]
+        pub fn aceptar_cancelacion_parcial(&mut self, orden_id: u128) -> Result<(), ContractError> {
+            let caller = self.env().caller();
+            self._aceptar_cancelacion_parcial(caller, orden_id)
+        }
+
+        /// El comprador cambia la cantidad de una orden Pendiente. Si aumenta debe transferir
+        /// la diferencia al precio unitario pagado; si disminuye se le reembolsa.
+        #[ink(message, payable)]
+        pub fn modificar_cantidad_orden(&mut self, orden_id: u128, nueva_cantidad: u32) -> Result<(), ContractError> {
+            let caller = self.env().caller();
+            let pago = self.env().transferred_value();
+            self._modificar_cantidad_orden(caller, orden_id, nueva_cantidad, pago)
+        }
+
+        /// Aparta `cantidad` unidades de un producto durante `duracion` sin crear la orden.
+        /// Retorna el ID de la reserva.
+        #[ink(message)]
+        pub fn reservar_stock(&mut self, producto_id: u128, cantidad: u32, duracion: Timestamp) -> Result<u128, ContractError> {
+            self._no_pausado()?;
+            let caller = self.env().caller();
+            self._reservar_stock(caller, producto_id, cantidad, duracion)
+        }
+
+        /// Convierte una reserva vigente en una orden de compra, pagando al precio actual.
+        #[ink(message, payable)]
+        pub fn confirmar_reserva(&mut self, reserva_id: u128, codigo: Option<String>) -> Result<u128, ContractError> {
+            self._no_pausado()?;
+            let caller = self.env().caller();
+            let pago = self.env().transferred_value();
+            let descuento_bps = self._descuento_cupon(codigo)?;
+            let orden_id = self._confirmar_reserva(caller, reserva_id, descuento_bps, pago)?;
+            let monto = self.ordenes.get(orden_id).map(|orden| orden.monto).unwrap_or(0);
+            self._cobrar_token(caller, monto)?;
+            Ok(orden_id)
+        }
+
+        /// Devuelve al stock las reservas vencidas. Puede ser llamado por cualquier cuenta.
+        /// Recorre como máximo `MAX_ESCANEO_RESERVAS` IDs por llamada, así que puede hacer falta
+        /// llamarlo varias veces. Retorna la cantidad de reservas liberadas.
+        #[ink(message)]
+        pub fn liberar_reservas_vencidas(&mut self) -> u32 {
+            self._liberar_reservas_vencidas()
+        }
+
+        /// Obtiene una reserva por su ID.
+        #[ink(message)]
+        pub fn obtener_reserva(&self, reserva_id: u128) -> Option<Reserva> {
+            self.reservas.get(reserva_id)
+        }
+
+        /// El vendedor cancela todas sus órdenes pendientes (por ejemplo, al dejar el marketplace).
+        /// Las órdenes ya enviadas no se tocan. Retorna la cantidad de órdenes canceladas.
+        #[ink(message)]
+        pub fn vendedor_cancela_todo_pendiente(&mut self) -> Result<u32, ContractError> {
+            let caller = self.env().caller();
+            self._cancelar_pendientes_vendedor(caller)
+        }
+
+        /// Abre una disputa sobre una orden Pendiente o Enviada (comprador o vendedor).
+        #[ink(message)]
+        pub fn abrir_disputa(&mut self, orden_id: u128) -> Result<(), ContractError> {
+            let caller = self.env().caller();
+            self._abrir_disputa(caller, orden_id)
+        }
+
+        /// Resuelve a favor del comprador una disputa que lleva abierta más de
+        /// `PLAZO_RESOLUCION_DISPUTA`: cancela la orden y devuelve el stock.
+        /// Puede ser llamado por cualquier cuenta.
+        #[ink(message)]
+        pub fn resolver_disputa_timelock(&mut self, orden_id: u128) -> Result<(), ContractError> {
+            self._resolver_disputa_timelock(orden_id)
+        }
+
+        /// El owner resuelve una disputa como árbitro. A favor del comprador se cancela la
+        /// orden (devolviendo stock y escrow); a favor del vendedor se marca como Recibida
+        /// y se liberan los fondos.
+        #[ink(message)]
+        pub fn resolver_disputa(&mut self, orden_id: u128, favor_comprador: bool) -> Result<(), ContractError> {
+            let caller = self.env().caller();
+            self._resolver_disputa(caller, orden_id, favor_comprador)
+        }
+
+        /// Permite al comprador cancelar una orden Pendiente cuando el vendedor no la envió
+        /// dentro de `timeout_envio`. Devuelve el stock y reembolsa el escrow.
+        #[ink(message)]
+        pub fn cancelar_por_inactividad(&mut self, orden_id: u128) -> Result<(), ContractError> {
+            let caller = self.env().caller();
+            self._cancelar_por_inactividad(caller, orden_id)
+        }
+
+        /// El vendedor rechaza una orden Pendiente que no puede cumplir, sin necesitar el
+        /// acuerdo del comprador. Devuelve el stock y reembolsa el escrow.
+        #[ink(message)]
+        pub fn vendedor_rechaza_orden(&mut self, orden_id: u128) -> Result<(), ContractError> {
+            let caller = self.env().caller();
+            self._vendedor_rechaza_orden(caller, orden_id)
+        }
+
+        /// Devuelve el plazo de envío en milisegundos configurado al desplegar el contrato.
+        #[ink(message)]
+        pub fn timeout_envio(&self) -> u64 {
+            self.timeout_envio
+        }
+
+        /// El owner marca como Recibida una orden Enviada cuyo comprador no confirmó la
+        /// recepción dentro de `timeout_recepcion`, liberando el escrow al vendedor.
+        #[ink(message)]
+        pub fn forzar_recepcion(&mut self, orden_id: u128) -> Result<(), ContractError> {
+            let caller = self.env().caller();
+            self._forzar_recepcion(caller, orden_id)
+        }
+
+        /// Devuelve el plazo en milisegundos tras el envío para poder forzar la recepción.
+        #[ink(message)]
+        pub fn timeout_recepcion(&self) -> u64 {
+            self.timeout_recepcion
+        }
+
+        /// Cambia el plazo para forzar la recepción de órdenes enviadas (solo el owner).
+        #[ink(message)]
+        pub fn establecer_timeout_recepcion(&mut self, timeout: u64) -> Result<(), ContractError> {
+            let caller = self.env().caller();
+            self._establecer_timeout_recepcion(caller, timeout)
+        }
+
+        /// El comprador califica al vendedor después de recibir la orden, con un comentario
+        /// opcional de hasta `MAX_LONGITUD_COMENTARIO` bytes.
+        /// Solo se puede calificar si la orden está en estado Recibido.
+        #[ink(message)]
+        pub fn comprador_califica_vendedor(
+            &mut self,
+            orden_id: u128,
+            calificacion: u8,
+            comentario: Option<String>,
         ) -> Result<(), ContractError> {
-            let rol_actual = self.roles.get(caller)
-                .ok_or(ContractError::UsuarioNoRegistrado)?;
-            
-            let rol_actualizado = rol_actual.agregar_rol(nuevo_rol)?;
-            self.roles.insert(caller, &rol_actualizado);
-            Ok(())
+            self._no_pausado()?;
+            let caller = self.env().caller();
+            self._calificar_vendedor(caller, orden_id, calificacion, comentario)
+        }
+
+        /// El vendedor califica al comprador después de recibir la orden, con un comentario
+        /// opcional de hasta `MAX_LONGITUD_COMENTARIO` bytes.
+        /// Solo se puede calificar si la orden está en estado Recibido.
+        #[ink(message)]
+        pub fn vendedor_califica_comprador(
+            &mut self,
+            orden_id: u128,
+            calificacion: u8,
+            comentario: Option<String>,
+        ) -> Result<(), ContractError> {
+            self._no_pausado()?;
+            let caller = self.env().caller();
+            self._calificar_comprador(caller, orden_id, calificacion, comentario)
+        }
+
+        /// Obtiene la reputación de un usuario.
+        /// Si la reputación no es pública, solo la ven el propio usuario y el owner.
+        #[ink(message)]
+        pub fn obtener_reputacion(&self, usuario: AccountId) -> Option<ReputacionData> {
+            let caller = self.env().caller();
+            self._reputacion_visible(caller, usuario)
+        }
+
+        /// Obtiene la reputación promedio como comprador de un usuario.
+        #[ink(message)]
+        pub fn reputacion_como_comprador(&self, usuario: AccountId) -> Option<u128> {
+            let caller = self.env().caller();
+            self._reputacion_visible(caller, usuario)
+                .and_then(|r| r.promedio_comprador())
+        }
+
+        /// Obtiene la reputación promedio como vendedor de un usuario.
+        #[ink(message)]
+        pub fn reputacion_como_vendedor(&self, usuario: AccountId) -> Option<u128> {
+            let caller = self.env().caller();
+            self._reputacion_visible(caller, usuario)
+                .and_then(|r| r.promedio_vendedor())
+        }
+
+        /// Obtiene un único puntaje de confianza combinando las calificaciones recibidas como
+        /// comprador y como vendedor. None si el usuario no tiene calificaciones.
+        #[ink(message)]
+        pub fn reputacion_global(&self, usuario: AccountId) -> Option<u128> {
+            let caller = self.env().caller();
+            self._reputacion_visible(caller, usuario)
+                .and_then(|r| r.promedio_global())
+        }
+
+        /// Devuelve cuántas calificaciones de 1 a 5 recibió un usuario, como
+        /// (distribución como comprador, distribución como vendedor).
+        #[ink(message)]
+        pub fn histograma_reputacion(&self, usuario: AccountId) -> Option<([u32; 5], [u32; 5])> {
+            let caller = self.env().caller();
+            self._reputacion_visible(caller, usuario)
+                .map(|r| (r.distribucion_comprador, r.distribucion_vendedor))
+        }
+
+        /// Obtiene la reputación de un usuario junto con la distribución de sus puntajes
+        /// como vendedor y como comprador en una sola consulta.
+        #[ink(message)]
+        pub fn reputacion_detallada(&self, usuario: AccountId) -> Option<ReputacionDetallada> {
+            let caller = self.env().caller();
+            self._reputacion_visible(caller, usuario).map(|data| ReputacionDetallada {
+                distribucion_vendedor: data.distribucion_vendedor,
+                distribucion_comprador: data.distribucion_comprador,
+                data,
+            })
+        }
+
+        /// Devuelve (promedio de las calificaciones que el usuario otorga, promedio de las que
+        /// recibe), sumando sus roles de comprador y vendedor. Sirve para detectar usuarios
+        /// que califican bajo en represalia. Retorna None si le falta alguno de los dos datos
+        /// o si la reputación no es pública y el caller no puede verla.
+        #[ink(message)]
+        pub fn correlacion_calificaciones(&self, usuario: AccountId) -> Option<(u128, u128)> {
+            let rep = self._reputacion_visible(self.env().caller(), usuario)?;
+            let (suma_dada, cantidad_dada) = self.calificaciones_dadas.get(usuario)?;
+            let dada = suma_dada.checked_div(cantidad_dada as u128)?;
+
+            let suma_recibida = rep.suma_calificaciones_comprador
+                .checked_add(rep.suma_calificaciones_vendedor)?;
+            let cantidad_recibida = (rep.total_calificaciones_comprador as u128)
+                .checked_add(rep.total_calificaciones_vendedor as u128)?;
+            let recibida = suma_recibida.checked_div(cantidad_recibida)?;
+
+            Some((dada, recibida))
+        }
+
+        /// Devuelve, en orden ascendente, las órdenes recibidas que el usuario todavía no
+        /// calificó (como comprador o como vendedor) y que fueron recibidas hace más de
+        /// `antiguedad_min` milisegundos.
+        #[ink(message)]
+        pub fn ordenes_calificacion_pendiente_antiguas(&self, usuario: AccountId, antiguedad_min: u64) -> Vec<u128> {
+            self._calificaciones_pendientes_antiguas(usuario, antiguedad_min)
+        }
+
+        /// Obtiene las calificaciones de una orden.
+        #[ink(message)]
+        pub fn obtener_calificaciones_orden(&self, orden_id: u128) -> Option<CalificacionesOrden> {
+            self.calificaciones_por_orden.get(orden_id)
+        }
+
+        /// Indica si cada parte ya calificó la orden: (comprador_ya_califico, vendedor_ya_califico).
+        /// Devuelve `None` si la orden todavía no fue recibida.
+        #[ink(message)]
+        pub fn estado_calificacion_orden(&self, orden_id: u128) -> Option<(bool, bool)> {
+            self.calificaciones_por_orden.get(orden_id).map(|c| {
+                (c.calificacion_comprador.is_some(), c.calificacion_vendedor.is_some())
+            })
+        }
+
+        /// Devuelve las unidades que se pueden comprar de un producto: su stock, o 0 si está
+        /// inactivo. `None` si el producto no existe.
+        #[ink(message)]
+        pub fn stock_disponible(&self, producto_id: u128) -> Option<u32> {
+            self.productos
+                .get(producto_id)
+                .map(|p| if p.activo { p.cantidad } else { 0 })
+        }
+
+        /// Cantidad de órdenes canceladas en las que participó un usuario, como comprador o
+        /// vendedor. Las cancelaciones parciales no cuentan hasta que la orden se cancela entera.
+        #[ink(message)]
+        pub fn cancelaciones_usuario(&self, usuario: AccountId) -> u32 {
+            self.cancelaciones_por_usuario.get(usuario).unwrap_or(0)
+        }
+
+        /// Obtiene la cantidad de ventas de un producto.
+        #[ink(message)]
+        pub fn obtener_ventas_producto(&self, producto_id: u128) -> u32 {
+            self.ventas_por_producto.get(producto_id).unwrap_or(0)
+        }
+
+        /// Devuelve (vendedores distintos con al menos una venta, compradores distintos con
+        /// al menos una orden recibida).
+        #[ink(message)]
+        pub fn participantes_activos(&self) -> (u32, u32) {
+            (self.contador_vendedores_activos, self.contador_compradores_activos)
+        }
+
+        /// Devuelve el promedio de las calificaciones que recibieron las ventas de un producto.
+        #[ink(message)]
+        pub fn calificacion_promedio_producto(&self, producto_id: u128) -> Option<u128> {
+            let (suma, cantidad) = self.calificaciones_producto.get(producto_id)?;
+            suma.checked_div(cantidad as u128)
+        }
+
+        /// Devuelve en una sola llamada el producto, su cantidad de ventas y su calificación
+        /// promedio. `None` si el producto no existe.
+        #[ink(message)]
+        pub fn resumen_producto(&self, producto_id: u128) -> Option<(Producto, u32, Option<u128>)> {
+            let producto = self.productos.get(producto_id)?;
+            Some((
+                producto,
+                self.obtener_ventas_producto(producto_id),
+                self.calificacion_promedio_producto(producto_id),
+            ))
+        }
+
+        /// Indica si un producto tiene al menos una venta completada.
+        #[ink(message)]
+        pub fn producto_tiene_ventas(&self, producto_id: u128) -> bool {
+            self.obtener_ventas_producto(producto_id) > 0
+        }
+
+        /// Calcula la rotación de inventario de un producto: unidades vendidas dividido
+        /// stock actual, multiplicado por 100 (ej: 150 = 1,5 veces el stock disponible).
+        /// Retorna None si el producto no existe o si no le queda stock (rotación indefinida).
+        #[ink(message)]
+        pub fn rotacion_producto(&self, producto_id: u128) -> Option<u128> {
+            let producto = self.productos.get(producto_id)?;
+            if producto.cantidad == 0 {
+                return None;
+            }
+            let vendidas = self.unidades_vendidas_por_producto.get(producto_id).unwrap_or(0) as u128;
+            vendidas
+                .checked_mul(100)?
+                .checked_div(producto.cantidad as u128)
+        }
+
+        /// Obtiene las estadísticas de una categoría.
+        #[ink(message)]
+        pub fn obtener_estadisticas_categoria(&self, categoria: String) -> Option<(u32, u128, u32)> {
+            self.estadisticas_por_categoria.get(&categoria)
+        }
+
+        /// Devuelve el promedio de calificaciones de un vendedor dentro de una categoría.
+        /// Si la reputación no es pública, solo la ven el propio vendedor y el owner.
+        #[ink(message)]
+        pub fn reputacion_vendedor_en_categoria(&self, vendedor: AccountId, categoria: String) -> Option<u128> {
+            let caller = self.env().caller();
+            self._reputacion_vendedor_en_categoria(caller, vendedor, categoria)
+        }
+
+        /// Devuelve la reputación del vendedor de un producto en la categoría de ese producto.
+        /// Retorna None si el producto no existe o el vendedor no tiene calificaciones ahí.
+        #[ink(message)]
+        pub fn reputacion_vendedor_en_categoria_producto(&self, producto_id: u128) -> Option<u128> {
+            let caller = self.env().caller();
+            let producto = self.productos.get(producto_id)?;
+            self._reputacion_vendedor_en_categoria(caller, producto.vendedor, producto.categoria)
+        }
+
+        /// Obtiene la cantidad de órdenes de un usuario.
+        #[ink(message)]
+        pub fn cantidad_ordenes_usuario(&self, usuario: AccountId) -> u32 {
+            self.ordenes_por_usuario
+                .get(&usuario)
+                .map(|v| v.len() as u32)
+                .unwrap_or(0)
+        }
+
+        /// Devuelve la última orden creada por el usuario que llama (la de mayor ID).
+        #[ink(message)]
+        pub fn mi_ultima_orden(&self) -> Option<(u128, Orden)> {
+            let caller = self.env().caller();
+            self._ultima_orden_comprador(caller)
+        }
+
+        /// Devuelve todas las órdenes creadas por el usuario que llama (como comprador).
+        #[ink(message)]
+        pub fn ver_mis_ordenes(&self) -> Vec<(u128, Orden)> {
+            let caller = self.env().caller();
+            self._ver_mis_ordenes(caller)
+        }
+
+        /// Devuelve las órdenes del caller en el estado indicado, ordenadas por ID. Incluye
+        /// tanto las órdenes donde es comprador como aquellas donde es vendedor; por ejemplo,
+        /// un vendedor obtiene con `Pendiente` las órdenes que tiene que enviar.
+        #[ink(message)]
+        pub fn ordenes_por_estado(&self, estado: EstadoOrden) -> Vec<(u128, Orden)> {
+            let caller = self.env().caller();
+            self._ordenes_por_estado(caller, estado)
+        }
+
+        /// Devuelve hasta `limite` órdenes de todo el marketplace en el estado indicado,
+        /// recorriendo los IDs desde `inicio` (solo el owner). Para la página siguiente se usa
+        /// el último ID recibido + 1.
+        #[ink(message)]
+        pub fn ordenes_globales_por_estado(
+            &self,
+            estado: EstadoOrden,
+            inicio: u128,
+            limite: u32,
+        ) -> Result<Vec<(u128, Orden)>, ContractError> {
+            let caller = self.env().caller();
+            self._ordenes_globales_por_estado(caller, estado, inicio, limite)
+        }
+
+        /// Tiempo promedio (en milisegundos) que tarda un vendedor entre la creación de una
+        /// orden y su envío completo. `None` si todavía no envió ninguna orden.
+        #[ink(message)]
+        pub fn tiempo_envio_promedio(&self, vendedor: AccountId) -> Option<u64> {
+            let (suma, cantidad) = self._ordenes_de_vendedor(vendedor)
+                .into_iter()
+                .filter_map(|(_, orden)| orden.enviada_en.map(|enviada| enviada.saturating_sub(orden.creada_en)))
+                .fold((0u64, 0u64), |(suma, cantidad), demora| {
+                    (suma.saturating_add(demora), cantidad.saturating_add(1))
+                });
+            suma.checked_div(cantidad)
+        }
+
+        /// Suma `precio_unitario * cantidad` de las órdenes Recibidas de un vendedor, con el
+        /// precio vigente al momento de cada compra (no descuenta cupones ni comisiones).
+        #[ink(message)]
+        pub fn ingresos_reales_vendedor(&self, vendedor: AccountId) -> u128 {
+            self._ordenes_de_vendedor(vendedor)
+                .into_iter()
+                .filter(|(_, orden)| orden.estado == EstadoOrden::Recibido)
+                .fold(0u128, |total, (_, orden)| {
+                    total.saturating_add(orden.precio_unitario.saturating_mul(orden.cantidad as u128))
+                })
+        }
+
+        /// Devuelve los compradores que calificaron a un vendedor junto a la nota que le
+        /// dieron, una entrada por orden. Recorre todas las órdenes del vendedor, por lo que
+        /// puede ser costoso: con historiales largos conviene `calificadores_vendedor_paginado`.
+        /// Si la reputación no es pública, solo lo ven el propio vendedor y el owner.
+        #[ink(message)]
+        pub fn calificadores_vendedor(&self, vendedor: AccountId) -> Vec<(AccountId, u8)> {
+            let caller = self.env().caller();
+            self._calificadores_vendedor(caller, vendedor, 0, u32::MAX)
+        }
+
+        /// Versión paginada de `calificadores_vendedor`: recorre solo las órdenes del vendedor
+        /// con índice en `[inicio, inicio + limite)`. Una página puede venir vacía.
+        #[ink(message)]
+        pub fn calificadores_vendedor_paginado(
+            &self,
+            vendedor: AccountId,
+            inicio: u32,
+            limite: u32,
+        ) -> Vec<(AccountId, u8)> {
+            let caller = self.env().caller();
+            self._calificadores_vendedor(caller, vendedor, inicio, limite)
+        }
+
+        /// Devuelve el historial de órdenes de un comprador con un vendedor determinado,
+        /// en orden de creación (el mismo en que se guardan en `ordenes_por_usuario`).
+        #[ink(message)]
+        pub fn ordenes_entre(&self, comprador: AccountId, vendedor: AccountId) -> Vec<(u128, Orden)> {
+            self._ver_mis_ordenes(comprador)
+                .into_iter()
+                .filter(|(_, orden)| orden.vendedor == vendedor)
+                .collect()
+        }
+
+        /// Devuelve las unidades vendidas por un vendedor en cada categoría (solo órdenes
+        /// recibidas), ordenadas alfabéticamente por categoría.
+        #[ink(message)]
+        pub fn mix_categorias_vendedor(&self, vendedor: AccountId) -> Vec<(String, u32)> {
+            self._mix_categorias_vendedor(vendedor)
+        }
+
+        /// Devuelve las órdenes recibidas sobre los productos del vendedor que llama.
+        #[ink(message)]
+        pub fn ver_ordenes_como_vendedor(&self) -> Vec<(u128, Orden)> {
+            let caller = self.env().caller();
+            self._ordenes_de_vendedor(caller)
+        }
+
+        /// Devuelve las órdenes del usuario que llama (como comprador) que están en disputa.
+        #[ink(message)]
+        pub fn mis_disputas(&self) -> Vec<(u128, Orden)> {
+            let caller = self.env().caller();
+            self._disputas_comprador(caller)
+        }
+
+        /// Obtiene todos los usuarios con reputación (para reportes).
+        /// Retorna un vector de tuplas (usuario, reputacion_data).
+        /// Si la reputación no es pública, solo se incluye la que el caller puede ver.
+        #[ink(message)]
+        pub fn obtener_usuarios_con_reputacion(&self) -> Vec<(AccountId, ReputacionData)> {
+            let caller = self.env().caller();
+            self._usuarios_con_reputacion(caller, 0, self.contador_usuarios)
+        }
+
+        /// Versión paginada de `obtener_usuarios_con_reputacion`: recorre los usuarios con
+        /// índice de registro en `[inicio, inicio + limite)` y devuelve los que tienen reputación.
+        /// Una página puede venir vacía; el recorrido termina al llegar a `total_usuarios`.
+        /// Si la reputación no es pública, solo se incluye la que el caller puede ver.
+        #[ink(message)]
+        pub fn usuarios_con_reputacion_paginado(&self, inicio: u32, limite: u32) -> Vec<(AccountId, ReputacionData)> {
+            let caller = self.env().caller();
+            self._usuarios_con_reputacion(caller, inicio, limite)
+        }
+
+        /// Cantidad de usuarios registrados. Sirve para dimensionar los recorridos paginados
+        /// del directorio.
+        #[ink(message)]
+        pub fn total_usuarios(&self) -> u32 {
+            self.contador_usuarios
+        }
+
+        /// Devuelve hasta `limite` usuarios registrados a partir del índice `inicio`, en orden
+        /// de registro, con su rol y su reputación (vacía si todavía no fue calificado).
+        /// Pensado para que un indexador reconstruya el directorio completo página a página.
+        /// Si la reputación no es pública, la de los usuarios que el caller no puede ver viene
+        /// como `None`, para no confundirla con una reputación vacía.
+        #[ink(message)]
+        pub fn snapshot_directorio(&self, inicio: u32, limite: u32) -> Vec<(AccountId, Roles, Option<ReputacionData>)> {
+            let caller = self.env().caller();
+            let fin = inicio.saturating_add(limite).min(self.contador_usuarios);
+            let mut resultado = Vec::new();
+            for i in inicio..fin {
+                if let Some(usuario) = self.usuarios_registrados.get(i) {
+                    if let Some(rol) = self.roles.get(usuario) {
+                        let reputacion = self._puede_ver_reputacion(caller, usuario)
+                            .then(|| self._obtener_reputacion(usuario).unwrap_or_default());
+                        resultado.push((usuario, rol, reputacion));
+                    }
+                }
+            }
+            resultado
+        }
+
+        /// Devuelve los últimos `n` usuarios registrados, del más reciente al más antiguo.
+        /// Como el índice de usuarios solo crece, se recorre hacia atrás desde el final.
+        #[ink(message)]
+        pub fn usuarios_recientes(&self, n: u32) -> Vec<(AccountId, Roles)> {
+            let mut resultado = Vec::new();
+            let mut i = self.contador_usuarios;
+            while i > 0 && (resultado.len() as u32) < n {
+                i -= 1;
+                if let Some(usuario) = self.usuarios_registrados.get(i) {
+                    if let Some(rol) = self.roles.get(usuario) {
+                        resultado.push((usuario, rol));
+                    }
+                }
+            }
+            resultado
+        }
+
+        /// Devuelve la cuenta administradora del marketplace.
+        #[ink(message)]
+        pub fn obtener_owner(&self) -> AccountId {
+            self.owner
+        }
+
+        /// Transfiere el rol de owner a otra cuenta (solo el owner actual).
+        #[ink(message)]
+        pub fn transferir_owner(&mut self, nuevo: AccountId) -> Result<(), ContractError> {
+            let caller = self.env().caller();
+            self._transferir_owner(caller, nuevo)
+        }
+
+        /// Banea una cuenta (solo el owner). Las órdenes en curso se pueden seguir completando.
+        #[ink(message)]
+        pub fn banear_usuario(&mut self, usuario: AccountId) -> Result<(), ContractError> {
+            let caller = self.env().caller();
+            self._cambiar_baneo(caller, usuario, true)
+        }
+
+        /// Quita el baneo de una cuenta (solo el owner).
+        #[ink(message)]
+        pub fn desbanear_usuario(&mut self, usuario: AccountId) -> Result<(), ContractError> {
+            let caller = self.env().caller();
+            self._cambiar_baneo(caller, usuario, false)
+        }
+
+        /// Indica si una cuenta está baneada.
+        #[ink(message)]
+        pub fn esta_baneado(&self, usuario: AccountId) -> bool {
+            self.baneados.get(usuario).unwrap_or(false)
+        }
+
+        /// Marca o desmarca a un usuario como verificado (solo el owner).
+        #[ink(message)]
+        pub fn verificar_usuario(&mut self, usuario: AccountId, verificado: bool) -> Result<(), ContractError> {
+            let caller = self.env().caller();
+            self._verificar_usuario(caller, usuario, verificado)
+        }
+
+        /// Indica si un usuario fue verificado por el owner.
+        #[ink(message)]
+        pub fn esta_verificado(&self, usuario: AccountId) -> bool {
+            self.verificados.get(usuario).unwrap_or(false)
+        }
+
+        /// Indica si un vendedor es confiable: está verificado por el owner, o tiene un
+        /// promedio como vendedor >= `reputacion_minima` con al menos
+        /// `MIN_CALIFICACIONES_CONFIABLE` calificaciones. Si la reputación no es pública, a los
+        /// demás solo les cuenta la verificación.
+        #[ink(message)]
+        pub fn es_vendedor_confiable(&self, vendedor: AccountId, reputacion_minima: u128) -> bool {
+            if self.esta_verificado(vendedor) {
+                return true;
+            }
+            let caller = self.env().caller();
+            self._reputacion_visible(caller, vendedor).is_some_and(|rep| {
+                rep.total_calificaciones_vendedor >= MIN_CALIFICACIONES_CONFIABLE
+                    && rep.promedio_vendedor().is_some_and(|p| p >= reputacion_minima)
+            })
+        }
+
+        /// Activa o desactiva el modo de alto riesgo (solo el owner).
+        /// Mientras está activo, solo los compradores verificados pueden crear órdenes.
+        #[ink(message)]
+        pub fn establecer_modo_alto_riesgo(&mut self, activo: bool) -> Result<(), ContractError> {
+            let caller = self.env().caller();
+            self._establecer_modo_alto_riesgo(caller, activo)
+        }
+
+        /// Pausa o reanuda la operatoria del marketplace (solo el owner).
+        /// Mientras está pausado no se pueden publicar productos, crear órdenes,
+        /// marcarlas como enviadas o recibidas ni calificar; las consultas siguen funcionando.
+        #[ink(message)]
+        pub fn establecer_pausa(&mut self, pausado: bool) -> Result<(), ContractError> {
+            let caller = self.env().caller();
+            self._establecer_pausa(caller, pausado)
+        }
+
+        /// Indica si el marketplace está pausado.
+        #[ink(message)]
+        pub fn esta_pausado(&self) -> bool {
+            self.pausado
+        }
+
+        /// Crea o reemplaza un cupón de descuento (solo el owner). El descuento se expresa
+        /// en puntos básicos y no puede superar el 100%.
+        #[ink(message)]
+        pub fn crear_cupon(&mut self, codigo: String, descuento_bps: u16) -> Result<(), ContractError> {
+            let caller = self.env().caller();
+            self._crear_cupon(caller, codigo, descuento_bps)
+        }
+
+        /// Elimina un cupón de descuento (solo el owner).
+        #[ink(message)]
+        pub fn eliminar_cupon(&mut self, codigo: String) -> Result<(), ContractError> {
+            let caller = self.env().caller();
+            self._eliminar_cupon(caller, codigo)
+        }
+
+        /// Devuelve el descuento en puntos básicos de un cupón, si existe.
+        #[ink(message)]
+        pub fn obtener_cupon(&self, codigo: String) -> Option<u16> {
+            self.cupones.get(codigo)
+        }
+
+        /// Habilita una categoría para publicar productos (solo el owner).
+        #[ink(message)]
+        pub fn agregar_categoria(&mut self, categoria: String) -> Result<(), ContractError> {
+            let caller = self.env().caller();
+            self._agregar_categoria(caller, categoria)
+        }
+
+        /// Deshabilita una categoría (solo el owner). Los productos ya publicados en ella se conservan.
+        /// Falla con `CategoriaNoPermitida` si la categoría no estaba habilitada.
+        #[ink(message)]
+        pub fn quitar_categoria(&mut self, categoria: String) -> Result<(), ContractError> {
+            let caller = self.env().caller();
+            self._quitar_categoria(caller, categoria)
+        }
+
+        /// Indica si se puede publicar en una categoría.
+        #[ink(message)]
+        pub fn categoria_permitida(&self, categoria: String) -> bool {
+            self._validar_categoria(&categoria).is_ok()
+        }
+
+        /// Devuelve el contrato PSP22 con el que se paga, o None si se usa el balance nativo.
+        #[ink(message)]
+        pub fn token(&self) -> Option<AccountId> {
+            self.token
+        }
+
+        /// Indica si el modo de alto riesgo está activo.
+        #[ink(message)]
+        pub fn modo_alto_riesgo(&self) -> bool {
+            self.modo_alto_riesgo
+        }
+
+        /// Define si la reputación de los usuarios puede ser leída por cualquiera (solo el owner).
+        #[ink(message)]
+        pub fn establecer_reputacion_publica(&mut self, publica: bool) -> Result<(), ContractError> {
+            let caller = self.env().caller();
+            self._establecer_reputacion_publica(caller, publica)
+        }
+
+        /// Indica si la reputación de los usuarios es pública.
+        #[ink(message)]
+        pub fn reputacion_publica(&self) -> bool {
+            self.reputacion_publica
+        }
+
+        /// Devuelve la suma de los montos de órdenes que todavía pueden terminar en
+        /// reembolso al comprador (Pendiente, Enviado o Disputado).
+        /// Se mantiene como contador para no recorrer todas las órdenes.
+        #[ink(message)]
+        pub fn liabilidad_reembolsos(&self) -> u128 {
+            self.liabilidad_reembolsos
+        }
+
+        /// Transfiere al owner las comisiones acumuladas y deja el saldo en cero.
+        /// Retorna el monto retirado. Separado de los cobros de los vendedores.
+        #[ink(message)]
+        pub fn retirar_comisiones(&mut self) -> Result<u128, ContractError> {
+            let caller = self.env().caller();
+            self._retirar_comisiones(caller)
+        }
+
+        /// Devuelve las comisiones acumuladas pendientes de retiro.
+        #[ink(message)]
+        pub fn fondos_acumulados(&self) -> u128 {
+            self.fondos_acumulados
+        }
+
+        /// Devuelve los decimales con que se expresan precios y montos en escrow, tal como
+        /// se indicaron al desplegar (por ejemplo 6 para mostrar 1000000 como 1.000000).
+        /// No afecta la lógica del contrato.
+        #[ink(message)]
+        pub fn decimales_token(&self) -> u8 {
+            self.decimales_token
+        }
+
+        /// Actualiza los decimales del token informados a los frontends (solo el owner).
+        #[ink(message)]
+        pub fn establecer_decimales_token(&mut self, decimales: u8) -> Result<(), ContractError> {
+            let caller = self.env().caller();
+            self._establecer_decimales_token(caller, decimales)
+        }
+
+        /// Devuelve la comisión del marketplace en puntos básicos.
+        #[ink(message)]
+        pub fn comision_bps(&self) -> u16 {
+            self.comision_bps
+        }
+
+        /// Calcula el volumen bruto de mercadería (GMV) de las órdenes recibidas
+        /// entre `desde` y `hasta` (ambos inclusive), sumando `precio_unitario * cantidad`
+        /// con el precio que tenía cada orden. Recorre solo los IDs de orden en
+        /// `[inicio, inicio + limite)`, con `limite` acotado a `MAX_ESCANEO_ORDENES`: para el
+        /// total se suman las páginas hasta cubrir `total_ordenes`.
+        #[ink(message)]
+        pub fn gmv_en_rango(&self, desde: u64, hasta: u64, inicio: u128, limite: u32) -> u128 {
+            self._gmv_en_rango(desde, hasta, inicio, limite)
+        }
+
+
+        // ===== Funciones privadas =====
+
+        /// Registra un nuevo usuario con el rol especificado.
+        fn _registrar_usuario(
+            &mut self, 
+            caller: AccountId, 
+            rol: Roles
+        ) -> Result<(), ContractError> {
+            self._no_baneado(caller)?;
+            if self.roles.contains(caller) {
+                return Err(ContractError::YaRegistrado);
+            }
+            self.roles.insert(caller, &rol);
+            // Agregar a la lista de usuarios registrados
+            let index = self.contador_usuarios;
+            self.usuarios_registrados.insert(index, &caller);
+            self.contador_usuarios = index.checked_add(1).ok_or(ContractError::Overflow)?;
+            Ok(())
+        }
+
+        /// Modifica el rol de un usuario, permitiendo solo agregar roles, no quitarlos.
+        fn _modificar_rol(
+            &mut self, 
+            caller: AccountId, 
+            nuevo_rol: Roles
+        ) -> Result<(), ContractError> {
+            let rol_actual = self.roles.get(caller)
+                .ok_or(ContractError::UsuarioNoRegistrado)?;
+            
+            let rol_actualizado = rol_actual.agregar_rol(nuevo_rol)?;
+            self.roles.insert(caller, &rol_actualizado);
+            Ok(())
+        }
+
+        /// Registra al usuario si es nuevo; si ya existe, le suma el rol pedido.
+        fn _registrar_o_actualizar_rol(&mut self, caller: AccountId, rol: Roles) -> Result<(), ContractError> {
+            if self.roles.contains(caller) {
+                self._modificar_rol(caller, rol)
+            } else {
+                self._registrar_usuario(caller, rol)
+            }
+        }
+
+        /// Obtiene el rol de un usuario.
+        fn _obtener_rol(&self, usuario: AccountId) -> Option<Roles> {
+            self.roles.get(usuario)
+        }
+
+        /// Obtiene el estado de una orden.
+        fn _obtener_estado_orden(&self, orden_id: u128) -> Option<EstadoOrden> {
+            self.ordenes.get(orden_id).map(|orden| orden.estado.clone())
+        }
+        /// Publica un nuevo producto validando que todos los campos sean válidos.
+        #[allow(clippy::too_many_arguments)]
+        fn _publicar_producto(
+            &mut self,
+            caller: AccountId,
+            nombre: String,
+            descripcion: String,
+            precio: u128,
+            cantidad: u32,
+            categoria: String,
+            limite_por_orden: Option<u32>,
+        ) -> Result<u128, ContractError> {
+            self._validar_vendedor(caller)?;
+
+            let producto = Producto {
+                nombre,
+                descripcion,
+                precio,
+                cantidad,
+                categoria,
+                vendedor: caller,
+                activo: true,
+                reputacion_minima_comprador: None,
+                limite_por_orden,
+            };
+
+            // Validar que los datos del producto sean correctos
+            producto.validar()?;
+            self._validar_categoria(&producto.categoria)?;
+            self._insertar_producto(producto)
+        }
+
+        /// Verifica que la cuenta pueda publicar: no baneada y con rol de vendedor.
+        fn _validar_vendedor(&self, caller: AccountId) -> Result<(), ContractError> {
+            self._no_baneado(caller)?;
+            let rol = self.roles.get(&caller);
+            if !rol.map_or(false, |r| r.es_vendedor()) {
+                return Err(ContractError::NoVendedor);
+            }
+            Ok(())
+        }
+
+        /// Guarda un producto ya validado, lo indexa por vendedor y categoría y emite el evento.
+        fn _insertar_producto(&mut self, producto: Producto) -> Result<u128, ContractError> {
+            let vendedor = producto.vendedor;
+            let pid = self.siguiente_producto_id;
+            self.productos.insert(pid, &producto);
+            
+            let mut lista = self.productos_por_usuario
+                .get(&vendedor)
+                .unwrap_or_default();
+            lista.push(pid);
+            self.productos_por_usuario.insert(&vendedor, &lista);
+
+            let mut por_categoria = self.productos_por_categoria
+                .get(&producto.categoria)
+                .unwrap_or_default();
+            por_categoria.push(pid);
+            self.productos_por_categoria.insert(&producto.categoria, &por_categoria);
+            
+            self.siguiente_producto_id = pid
+                .checked_add(1)
+                .ok_or(ContractError::Overflow)?;
+            self.productos_vivos = self.productos_vivos
+                .checked_add(1)
+                .ok_or(ContractError::Overflow)?;
+
+            self.env().emit_event(ProductoPublicado {
+                producto_id: pid,
+                vendedor,
+                categoria: producto.categoria,
+                precio: producto.precio,
+            });
+            Ok(pid)
+        }
+
+        /// Valida todas las entradas antes de publicar, para no dejar el batch a medias.
+        fn _publicar_productos_batch(
+            &mut self,
+            caller: AccountId,
+            productos: Vec<(String, String, u128, u32, String)>,
+        ) -> Result<Vec<u128>, ContractError> {
+            if productos.len() > MAX_PUBLICACIONES_BATCH {
+                return Err(ContractError::DatosInvalidos);
+            }
+            self._validar_vendedor(caller)?;
+
+            // Se valida todo el lote antes de insertar, así una entrada inválida no deja
+            // publicadas las anteriores
+            let mut nuevos = Vec::with_capacity(productos.len());
+            for (nombre, descripcion, precio, cantidad, categoria) in productos {
+                let producto = Producto {
+                    nombre,
+                    descripcion,
+                    precio,
+                    cantidad,
+                    categoria,
+                    vendedor: caller,
+                    activo: true,
+                    reputacion_minima_comprador: None,
+                    limite_por_orden: None,
+                };
+                producto.validar()?;
+                self._validar_categoria(&producto.categoria)?;
+                nuevos.push(producto);
+            }
+
+            let mut ids = Vec::with_capacity(nuevos.len());
+            for producto in nuevos {
+                ids.push(self._insertar_producto(producto)?);
+            }
+            Ok(ids)
+        }
+
+        /// Actualiza el precio de un producto (solo su vendedor puede hacerlo).
+        fn _actualizar_precio_producto(
+            &mut self,
+            caller: AccountId,
+            producto_id: u128,
+            nuevo_precio: u128,
+        ) -> Result<(), ContractError> {
+            let mut producto = self.productos
+                .get(producto_id)
+                .ok_or(ContractError::ProductoNoEncontrado)?;
+
+            if producto.vendedor != caller {
+                return Err(ContractError::NoAutorizado);
+            }
+            if nuevo_precio == 0 {
+                return Err(ContractError::DatosInvalidos);
+            }
+
+            producto.precio = nuevo_precio;
+            self.productos.insert(producto_id, &producto);
+            Ok(())
+        }
+
+        /// Fija el mínimo de reputación de comprador de un producto (solo su vendedor).
+        /// El mínimo debe estar en el rango de las calificaciones (1-5).
+        fn _establecer_reputacion_minima_comprador(
+            &mut self,
+            caller: AccountId,
+            producto_id: u128,
+            minima: Option<u128>,
+        ) -> Result<(), ContractError> {
+            let mut producto = self.productos
+                .get(producto_id)
+                .ok_or(ContractError::ProductoNoEncontrado)?;
+
+            if producto.vendedor != caller {
+                return Err(ContractError::NoAutorizado);
+            }
+            if minima.is_some_and(|m| !(1..=5).contains(&m)) {
+                return Err(ContractError::DatosInvalidos);
+            }
+
+            producto.reputacion_minima_comprador = minima;
+            self.productos.insert(producto_id, &producto);
+            Ok(())
+        }
+
+        /// Actualiza descripción y categoría, moviendo el producto en el índice por categoría.
+        fn _editar_producto(
+            &mut self,
+            caller: AccountId,
+            producto_id: u128,
+            descripcion: String,
+            categoria: String,
+        ) -> Result<(), ContractError> {
+            let producto = self.productos
+                .get(producto_id)
+                .ok_or(ContractError::ProductoNoEncontrado)?;
+
+            if producto.vendedor != caller {
+                return Err(ContractError::NoAutorizado);
+            }
+            // Se valida el producto ya editado con las mismas reglas que al publicar,
+            // salvo el stock: un producto agotado también se puede editar
+            let editado = Producto {
+                descripcion,
+                categoria,
+                ..producto.clone()
+            };
+            editado.validar_datos()?;
+
+            if producto.categoria != editado.categoria {
+                self._validar_categoria(&editado.categoria)?;
+                let mut anterior = self.productos_por_categoria
+                    .get(&producto.categoria)
+                    .unwrap_or_default();
+                anterior.retain(|id| *id != producto_id);
+                self.productos_por_categoria.insert(&producto.categoria, &anterior);
+
+                let mut nueva = self.productos_por_categoria.get(&editado.categoria).unwrap_or_default();
+                nueva.push(producto_id);
+                self.productos_por_categoria.insert(&editado.categoria, &nueva);
+            }
+
+            self.productos.insert(producto_id, &editado);
+            Ok(())
+        }
+
+        /// Guarda la preferencia de cancelación automática de un vendedor.
+        fn _establecer_cancelacion_automatica(
+            &mut self,
+            caller: AccountId,
+            activa: bool,
+        ) -> Result<(), ContractError> {
+            let rol = self.roles.get(caller);
+            if !rol.is_some_and(|r| r.es_vendedor()) {
+                return Err(ContractError::NoVendedor);
+            }
+            self.cancelacion_automatica.insert(caller, &activa);
+            Ok(())
+        }
+
+        /// Aumenta el stock de un producto (solo su vendedor puede hacerlo).
+        fn _reponer_stock(
+            &mut self,
+            caller: AccountId,
+            producto_id: u128,
+            cantidad: u32,
+        ) -> Result<(), ContractError> {
+            let mut producto = self.productos
+                .get(producto_id)
+                .ok_or(ContractError::ProductoNoEncontrado)?;
+
+            if producto.vendedor != caller {
+                return Err(ContractError::NoAutorizado);
+            }
+            if cantidad == 0 {
+                return Err(ContractError::DatosInvalidos);
+            }
+
+            producto.aumentar_stock(cantidad)?;
+            self.productos.insert(producto_id, &producto);
+            self.env().emit_event(StockActualizado {
+                producto_id,
+                cantidad_nueva: producto.cantidad,
+            });
+            Ok(())
+        }
+
+        /// Valida todo el batch antes de escribir, para no dejar actualizaciones a medias.
+        fn _actualizar_stocks_batch(
+            &mut self,
+            caller: AccountId,
+            actualizaciones: Vec<(u128, u32)>,
+        ) -> Result<(), ContractError> {
+            if actualizaciones.len() > MAX_ACTUALIZACIONES_STOCK {
+                return Err(ContractError::DatosInvalidos);
+            }
+
+            let mut productos = Vec::with_capacity(actualizaciones.len());
+            for (producto_id, cantidad) in actualizaciones {
+                let mut producto = self.productos
+                    .get(producto_id)
+                    .ok_or(ContractError::ProductoNoEncontrado)?;
+                if producto.vendedor != caller {
+                    return Err(ContractError::NoAutorizado);
+                }
+                producto.cantidad = cantidad;
+                productos.push((producto_id, producto));
+            }
+
+            for (producto_id, producto) in productos {
+                self.productos.insert(producto_id, &producto);
+                self.env().emit_event(StockActualizado {
+                    producto_id,
+                    cantidad_nueva: producto.cantidad,
+                });
+            }
+            Ok(())
+        }
+
+        /// Activa o desactiva un producto (solo su vendedor puede hacerlo).
+        /// Para reactivarlo el producto debe tener stock.
+        fn _cambiar_estado_producto(
+            &mut self,
+            caller: AccountId,
+            producto_id: u128,
+            activo: bool,
+        ) -> Result<(), ContractError> {
+            let mut producto = self.productos
+                .get(producto_id)
+                .ok_or(ContractError::ProductoNoEncontrado)?;
+
+            if producto.vendedor != caller {
+                return Err(ContractError::NoAutorizado);
+            }
+            if activo && producto.cantidad == 0 {
+                return Err(ContractError::StockInsuficiente);
+            }
+
+            producto.activo = activo;
+            self.productos.insert(producto_id, &producto);
+            Ok(())
+        }
+
+        /// Borra un producto y lo quita de los índices por vendedor y por categoría.
+        fn _eliminar_producto(&mut self, caller: AccountId, producto_id: u128) -> Result<(), ContractError> {
+            let producto = self.productos
+                .get(producto_id)
+                .ok_or(ContractError::ProductoNoEncontrado)?;
+
+            if producto.vendedor != caller {
+                return Err(ContractError::NoAutorizado);
+            }
+            let tiene_abiertas = self._ordenes_de_vendedor(caller).iter().any(|(_, orden)| {
+                orden.producto_id == producto_id
+                    && matches!(
+                        orden.estado,
+                        EstadoOrden::Pendiente
+                            | EstadoOrden::ParcialmenteEnviado
+                            | EstadoOrden::Enviado
+                            | EstadoOrden::Disputado
+                    )
+            });
+            if tiene_abiertas {
+                return Err(ContractError::ProductoConOrdenesAbiertas);
+            }
+
+            self.productos.remove(producto_id);
+
+            let mut lista = self.productos_por_usuario.get(caller).unwrap_or_default();
+            lista.retain(|id| *id != producto_id);
+            self.productos_por_usuario.insert(caller, &lista);
+
+            let mut por_categoria = self.productos_por_categoria
+                .get(&producto.categoria)
+                .unwrap_or_default();
+            por_categoria.retain(|id| *id != producto_id);
+            self.productos_por_categoria.insert(&producto.categoria, &por_categoria);
+
+            self.productos_vivos = self.productos_vivos.saturating_sub(1);
+            Ok(())
+        }
+
+        /// Agrega un favorito validando que el producto exista y no esté repetido.
+        fn _agregar_favorito(&mut self, caller: AccountId, producto_id: u128) -> Result<(), ContractError> {
+            if !self.productos.contains(producto_id) {
+                return Err(ContractError::ProductoNoEncontrado);
+            }
+            let mut favoritos = self.favoritos.get(caller).unwrap_or_default();
+            if favoritos.contains(&producto_id) {
+                return Err(ContractError::YaEnFavoritos);
+            }
+            if favoritos.len() >= MAX_FAVORITOS {
+                return Err(ContractError::DatosInvalidos);
+            }
+            favoritos.push(producto_id);
+            self.favoritos.insert(caller, &favoritos);
+            Ok(())
+        }
+
+        /// Quita un favorito; falla si el producto no estaba en la lista.
+        fn _quitar_favorito(&mut self, caller: AccountId, producto_id: u128) -> Result<(), ContractError> {
+            let mut favoritos = self.favoritos.get(caller).unwrap_or_default();
+            let posicion = favoritos
+                .iter()
+                .position(|id| *id == producto_id)
+                .ok_or(ContractError::ProductoNoEncontrado)?;
+            favoritos.remove(posicion);
+            self.favoritos.insert(caller, &favoritos);
+            Ok(())
+        }
+
+        /// Resuelve los favoritos de un usuario, salteando los productos eliminados.
+        fn _ver_favoritos(&self, caller: AccountId) -> Vec<(u128, Producto)> {
+            self.favoritos
+                .get(caller)
+                .unwrap_or_default()
+                .into_iter()
+                .filter_map(|id| {
+                    self.productos.get(id).map(|p| (id, p))
+                })
+                .collect()
+        }
+
+        /// Agrega un destacado; falla si ya estaba o si la lista está llena.
+        fn _destacar_producto(&mut self, caller: AccountId, producto_id: u128) -> Result<(), ContractError> {
+            self._solo_owner(caller)?;
+            if !self.productos.contains(producto_id) {
+                return Err(ContractError::ProductoNoEncontrado);
+            }
+            if self.destacados.contains(&producto_id) {
+                return Err(ContractError::YaDestacado);
+            }
+            if self.destacados.len() >= MAX_DESTACADOS {
+                return Err(ContractError::DatosInvalidos);
+            }
+            self.destacados.push(producto_id);
+            Ok(())
+        }
+
+        /// Quita un destacado; falla si el producto no estaba en la lista.
+        fn _quitar_destacado(&mut self, caller: AccountId, producto_id: u128) -> Result<(), ContractError> {
+            self._solo_owner(caller)?;
+            let posicion = self.destacados
+                .iter()
+                .position(|id| *id == producto_id)
+                .ok_or(ContractError::ProductoNoEncontrado)?;
+            self.destacados.remove(posicion);
+            Ok(())
+        }
+
+        /// Obtiene todos los productos publicados por un usuario (incluye los inactivos).
+        fn _ver_mis_productos(&self, caller: AccountId) -> Vec<(u128, Producto)> {
+            self.productos_por_usuario
+                .get(&caller)
+                .unwrap_or_default()
+                .into_iter()
+                .filter_map(|id| {
+                    self.productos.get(id).map(|p| (id, p))
+                })
+                .collect()
+        }
+
+        /// Obtiene todos los productos activos publicados en el marketplace.
+        fn _ver_todos_los_productos(&self) -> Vec<(u128, Producto)> {
+            let mut acc = Vec::new();
+            for id in 1..self.siguiente_producto_id {
+                if let Some(p) = self.productos.get(id) {
+                    if p.activo {
+                        acc.push((id, p));
+                    }
+                }
+            }
+            acc
+        }
+
+        /// Obtiene los productos activos de una categoría usando el índice por categoría.
+        fn _ver_productos_por_categoria(&self, categoria: String) -> Vec<(u128, Producto)> {
+            self.productos_por_categoria
+                .get(categoria)
+                .unwrap_or_default()
+                .into_iter()
+                .filter_map(|id| self.productos.get(id).map(|p| (id, p)))
+                .filter(|(_, p)| p.activo)
+                .collect()
+        }
+
+        /// Recorre los productos activos desde `inicio` hasta juntar `limite` resultados.
+        fn _ver_productos_paginado(&self, inicio: u128, limite: u32) -> Vec<(u128, Producto)> {
+            let mut acc = Vec::new();
+            for id in inicio.max(1)..self.siguiente_producto_id {
+                if acc.len() as u32 >= limite {
+                    break;
+                }
+                if let Some(p) = self.productos.get(id) {
+                    if p.activo {
+                        acc.push((id, p));
+                    }
+                }
+            }
+            acc
+        }
+
+        /// Compara nombres y término en minúsculas ASCII.
+        fn _buscar_productos(&self, termino: String) -> Vec<(u128, Producto)> {
+            if termino.is_empty() {
+                return Vec::new();
+            }
+            let termino = termino.to_ascii_lowercase();
+            let fin = self.siguiente_producto_id.min(MAX_ESCANEO_BUSQUEDA.saturating_add(1));
+            (1..fin)
+                .filter_map(|id| self.productos.get(id).map(|p| (id, p)))
+                .filter(|(_, p)| p.activo && p.nombre.to_ascii_lowercase().contains(&termino))
+                .collect()
+        }
+
+        /// Filtra productos por un conjunto de categorías, de forma paginada.
+        fn _productos_por_categorias(
+            &self,
+            categorias: Vec<String>,
+            inicio: u128,
+            limite: u32,
+        ) -> Vec<(u128, Producto)> {
+            let filtro: BTreeSet<String> = categorias
+                .into_iter()
+                .take(MAX_CATEGORIAS_FILTRO)
+                .collect();
+            let mut acc = Vec::new();
+            for id in inicio.max(1)..self.siguiente_producto_id {
+                if acc.len() as u32 >= limite {
+                    break;
+                }
+                if let Some(p) = self.productos.get(id) {
+                    if p.activo && filtro.contains(&p.categoria) {
+                        acc.push((id, p));
+                    }
+                }
+            }
+            acc
+        }
+
+        /// Verifica que el pago recibido coincida con el precio total de la compra.
+        fn _validar_pago(
+            &self,
+            producto_id: u128,
+            cantidad: u32,
+            descuento_bps: u16,
+            pago: Balance,
+        ) -> Result<(), ContractError> {
+            let producto = self.productos
+                .get(producto_id)
+                .ok_or(ContractError::ProductoNoEncontrado)?;
+            let monto = producto.precio
+                .checked_mul(cantidad as u128)
+                .ok_or(ContractError::Overflow)?;
+            self._verificar_pago_nativo(Self::_aplicar_descuento(monto, descuento_bps)?, pago)
+        }
+
+        /// Verifica el valor nativo recibido: sin token debe ser exactamente `monto`; con token
+        /// no se acepta valor nativo porque el cobro se hace con `transfer_from`.
+        fn _verificar_pago_nativo(&self, monto: Balance, pago: Balance) -> Result<(), ContractError> {
+            let esperado = if self.token.is_some() { 0 } else { monto };
+            if pago != esperado {
+                return Err(ContractError::PagoIncorrecto);
+            }
+            Ok(())
+        }
+
+        /// Si el marketplace usa un token PSP22, cobra `monto` a `origen` con `transfer_from`.
+        /// Con balance nativo no hace nada: el pago ya llegó como valor transferido.
+        fn _cobrar_token(&self, origen: AccountId, monto: Balance) -> Result<(), ContractError> {
+            let Some(token) = self.token else {
+                return Ok(());
+            };
+            if monto == 0 {
+                return Ok(());
+            }
+            build_call::<ink::env::DefaultEnvironment>()
+                .call(token)
+                .exec_input(
+                    ExecutionInput::new(Selector::new(ink::selector_bytes!("PSP22::transfer_from")))
+                        .push_arg(origen)
+                        .push_arg(self.env().account_id())
+                        .push_arg(monto)
+                        .push_arg(Vec::<u8>::new()),
+                )
+                .returns::<Result<(), PSP22Error>>()
+                .try_invoke()
+                .map_err(|_| ContractError::TransferenciaFallida)?
+                .map_err(|_| ContractError::TransferenciaFallida)?
+                .map_err(|_| ContractError::TransferenciaFallida)
+        }
+
+        /// Paga `monto` a `destino` con el token PSP22 configurado o, si no hay, con balance nativo.
+        fn _pagar(&self, destino: AccountId, monto: Balance) -> Result<(), ContractError> {
+            let Some(token) = self.token else {
+                return self.env()
+                    .transfer(destino, monto)
+                    .map_err(|_| ContractError::TransferenciaFallida);
+            };
+            build_call::<ink::env::DefaultEnvironment>()
+                .call(token)
+                .exec_input(
+                    ExecutionInput::new(Selector::new(ink::selector_bytes!("PSP22::transfer")))
+                        .push_arg(destino)
+                        .push_arg(monto)
+                        .push_arg(Vec::<u8>::new()),
+                )
+                .returns::<Result<(), PSP22Error>>()
+                .try_invoke()
+                .map_err(|_| ContractError::TransferenciaFallida)?
+                .map_err(|_| ContractError::TransferenciaFallida)?
+                .map_err(|_| ContractError::TransferenciaFallida)
+        }
+
+        /// Devuelve el descuento del cupón indicado, o 0 si no se usa ninguno.
+        fn _descuento_cupon(&self, codigo: Option<String>) -> Result<u16, ContractError> {
+            match codigo {
+                Some(codigo) => self.cupones.get(codigo).ok_or(ContractError::CuponInvalido),
+                None => Ok(0),
+            }
+        }
+
+        /// Descuenta `descuento_bps` puntos básicos de un monto.
+        fn _aplicar_descuento(monto: Balance, descuento_bps: u16) -> Result<Balance, ContractError> {
+            let descuento = monto
+                .checked_mul(descuento_bps as u128)
+                .ok_or(ContractError::Overflow)?
+                .checked_div(BPS_BASE)
+                .ok_or(ContractError::Overflow)?;
+            monto.checked_sub(descuento).ok_or(ContractError::Overflow)
+        }
+
+        /// Crea una nueva orden de compra sin descuento.
+        fn _crear_orden(
+            &mut self,
+            comprador: AccountId,
+            producto_id: u128,
+            cantidad: u32
+        ) -> Result<u128, ContractError> {
+            self._crear_orden_con_descuento(comprador, producto_id, cantidad, 0)
+        }
+
+        /// Verifica que la cuenta pueda comprar: no baneada, registrada como compradora y,
+        /// en modo de alto riesgo, verificada.
+        fn _validar_comprador(&self, comprador: AccountId) -> Result<(), ContractError> {
+            self._no_baneado(comprador)?;
+
+            // Validar que el usuario esté registrado y tenga permisos de comprador
+            let rol = self.roles
+                .get(&comprador)
+                .ok_or(ContractError::UsuarioNoRegistrado)?;
+            if !rol.es_comprador() {
+                return Err(ContractError::NoAutorizado);
+            }
+
+            // En modo de alto riesgo solo pueden comprar usuarios verificados
+            if self.modo_alto_riesgo && !self.verificados.get(comprador).unwrap_or(false) {
+                return Err(ContractError::KycRequerido);
+            }
+            Ok(())
+        }
+
+        /// Verifica que `comprador` pueda comprar `cantidad` unidades del producto y lo retorna.
+        fn _validar_compra(&self, comprador: AccountId, producto_id: u128, cantidad: u32) -> Result<Producto, ContractError> {
+            self._validar_comprador(comprador)?;
+
+            // Validar que la cantidad sea mayor que 0
+            if cantidad == 0 {
+                return Err(ContractError::StockInsuficiente);
+            }
+
+            // Obtener y validar el producto
+            let producto = self.productos
+                .get(producto_id)
+                .ok_or(ContractError::ProductoNoEncontrado)?;
+
+            if !producto.activo {
+                return Err(ContractError::ProductoInactivo);
+            }
+
+            // Un vendedor no puede comprar sus propios productos
+            if producto.vendedor == comprador {
+                return Err(ContractError::CompraPropia);
+            }
+            
+            // Si el vendedor fijó un mínimo, el comprador debe alcanzarlo. Sin calificaciones
+            // previas todavía no hay historial que juzgar, así que se le permite comprar.
+            if let Some(minima) = producto.reputacion_minima_comprador {
+                let promedio = self.reputaciones.get(comprador).unwrap_or_default().promedio_comprador();
+                if promedio.is_some_and(|p| p < minima) {
+                    return Err(ContractError::ReputacionInsuficiente);
+                }
+            }
+
+            if producto.limite_por_orden.is_some_and(|limite| cantidad > limite) {
+                return Err(ContractError::LimitePorOrdenExcedido);
+            }
+
+            if producto.cantidad < cantidad {
+                return Err(ContractError::StockInsuficiente);
+            }
+
+            Ok(producto)
+        }
+
+        /// Crea una nueva orden de compra validando stock y permisos. El escrow de la orden
+        /// es el precio total menos `descuento_bps`.
+        fn _crear_orden_con_descuento(
+            &mut self, 
+            comprador: AccountId, 
+            producto_id: u128, 
+            cantidad: u32,
+            descuento_bps: u16,
+        ) -> Result<u128, ContractError> {
+            let mut producto = self._validar_compra(comprador, producto_id, cantidad)?;
+
+            let monto = producto.precio
+                .checked_mul(cantidad as u128)
+                .ok_or(ContractError::Overflow)?;
+            let monto = Self::_aplicar_descuento(monto, descuento_bps)?;
+
+            // Reducir el stock del producto
+            producto.cantidad = producto.cantidad
+                .checked_sub(cantidad)
+                .ok_or(ContractError::Overflow)?;
+            self.productos.insert(producto_id, &producto);
+            self.env().emit_event(StockActualizado {
+                producto_id,
+                cantidad_nueva: producto.cantidad,
+            });
+
+            // Crear la orden
+            let oid = self.siguiente_orden_id;
+            let orden = Orden {
+                comprador,
+                vendedor: producto.vendedor,
+                producto_id,
+                cantidad,
+                estado: EstadoOrden::Pendiente,
+                comprador_acepta_cancelar: false,
+                vendedor_acepta_cancelar: false,
+                cancelacion_parcial_solicitada: None,
+                cantidad_enviada: 0,
+                creada_en: self.env().block_timestamp(),
+                enviada_en: None,
+                recibida_en: None,
+                disputa_abierta_en: None,
+                motivo_cancelacion: None,
+                precio_unitario: producto.precio,
+                monto,
+            };
+            self.ordenes.insert(oid, &orden);
+            self.liabilidad_reembolsos = self.liabilidad_reembolsos
+                .checked_add(monto)
+                .ok_or(ContractError::Overflow)?;
+
+            // Agregar la orden a la lista del comprador
+            let mut ordenes_usuario = self.ordenes_por_usuario
+                .get(&comprador)
+                .unwrap_or_default();
+            ordenes_usuario.push(oid);
+            self.ordenes_por_usuario.insert(&comprador, &ordenes_usuario);
+
+            // Agregar la orden a la lista del vendedor
+            let mut ordenes_vendedor = self.ordenes_por_vendedor
+                .get(producto.vendedor)
+                .unwrap_or_default();
+            ordenes_vendedor.push(oid);
+            self.ordenes_por_vendedor.insert(producto.vendedor, &ordenes_vendedor);
+
+            self.siguiente_orden_id = oid
+                .checked_add(1)
+                .ok_or(ContractError::Overflow)?;
+            self.total_ordenes = self.total_ordenes.saturating_add(1);
+
+            self.env().emit_event(OrdenCreada {
+                orden_id: oid,
+                comprador,
+                vendedor: producto.vendedor,
+                producto_id,
+            });
+            Ok(oid)
+        }
+
+        /// Marca una orden como enviada (solo puede ser Pendiente).
+        fn _marcar_enviada(
+            &mut self, 
+            caller: AccountId, 
+            orden_id: u128,
+            cantidad_enviada: Option<u32>,
+        ) -> Result<(), ContractError> {
+            let mut orden = self.ordenes
+                .get(orden_id)
+                .ok_or(ContractError::OrdenNoExiste)?;
+            
+            if orden.vendedor != caller {
+                return Err(ContractError::NoAutorizado);
+            }
+
+            // No se puede pasar directamente a Recibido
+            // Solo se puede enviar desde Pendiente o completar un envío parcial
+            if !matches!(orden.estado, EstadoOrden::Pendiente | EstadoOrden::ParcialmenteEnviado) {
+                return Err(ContractError::EstadoInvalido);
+            }
+
+            let restante = orden.cantidad.saturating_sub(orden.cantidad_enviada);
+            let cantidad = cantidad_enviada.unwrap_or(restante);
+            if cantidad == 0 {
+                return Err(ContractError::DatosInvalidos);
+            }
+            if cantidad > restante {
+                return Err(ContractError::EstadoInvalido);
+            }
+            orden.cantidad_enviada += cantidad;
+
+            if orden.cantidad_enviada < orden.cantidad {
+                orden.estado = EstadoOrden::ParcialmenteEnviado;
+                self.ordenes.insert(orden_id, &orden);
+                return Ok(());
+            }
+
+            self._completar_envio(orden_id, &mut orden);
+            Ok(())
+        }
+
+        /// Pasa a Enviado una orden cuyas unidades ya fueron todas despachadas.
+        fn _completar_envio(&mut self, orden_id: u128, orden: &mut Orden) {
+            orden.estado = EstadoOrden::Enviado;
+            orden.enviada_en = Some(self.env().block_timestamp());
+            self.ordenes.insert(orden_id, orden);
+
+            self.env().emit_event(OrdenEnviada {
+                orden_id,
+                comprador: orden.comprador,
+                vendedor: orden.vendedor,
+            });
+        }
+
+        /// Marca una orden como recibida (solo puede ser desde Enviado, no retrocede).
+        fn _marcar_recibida(
+            &mut self, 
+            caller: AccountId, 
+            orden_id: u128
+        ) -> Result<(), ContractError> {
+            let orden = self.ordenes
+                .get(orden_id)
+                .ok_or(ContractError::OrdenNoExiste)?;
+            
+            if orden.comprador != caller {
+                return Err(ContractError::NoAutorizado);
+            }
+
+            // Solo se puede recibir desde Enviado
+            // No se puede pasar directamente de Pendiente a Recibido
+            if orden.estado != EstadoOrden::Enviado {
+                return Err(ContractError::EstadoInvalido);
+            }
+
+            self._completar_recepcion(orden_id, orden)
+        }
+
+        /// Pasa una orden a Recibido y aplica sus efectos: registro de ventas, calificaciones
+        /// pendientes, comisión y liberación del escrow al vendedor.
+        fn _completar_recepcion(&mut self, orden_id: u128, mut orden: Orden) -> Result<(), ContractError> {
+            // Una vez recibido, no se puede retroceder
+            orden.estado = EstadoOrden::Recibido;
+            orden.recibida_en = Some(self.env().block_timestamp());
+            self.ordenes.insert(orden_id, &orden);
+            // La orden ya no puede reembolsarse
+            self.liabilidad_reembolsos = self.liabilidad_reembolsos.saturating_sub(orden.monto);
+
+            // Registrar venta del producto
+            let ventas_actuales = self.ventas_por_producto.get(orden.producto_id).unwrap_or(0);
+            self.ventas_por_producto.insert(
+                orden.producto_id,
+                &ventas_actuales.checked_add(1).ok_or(ContractError::Overflow)?,
+            );
+            let unidades_actuales = self.unidades_vendidas_por_producto
+                .get(orden.producto_id)
+                .unwrap_or(0);
+            self.unidades_vendidas_por_producto.insert(
+                orden.producto_id,
+                &unidades_actuales.checked_add(orden.cantidad).ok_or(ContractError::Overflow)?,
+            );
+
+            // Contar a los participantes la primera vez que completan una orden
+            if !self.vendedores_con_ventas.get(orden.vendedor).unwrap_or(false) {
+                self.vendedores_con_ventas.insert(orden.vendedor, &true);
+                self.contador_vendedores_activos = self.contador_vendedores_activos
+                    .checked_add(1)
+                    .ok_or(ContractError::Overflow)?;
+            }
+            if !self.compradores_con_compras.get(orden.comprador).unwrap_or(false) {
+                self.compradores_con_compras.insert(orden.comprador, &true);
+                self.contador_compradores_activos = self.contador_compradores_activos
+                    .checked_add(1)
+                    .ok_or(ContractError::Overflow)?;
+            }
+
+            // Inicializar calificaciones vacías para esta orden
+            let calificaciones = CalificacionesOrden {
+                calificacion_comprador: None,
+                calificacion_vendedor: None,
+                comentario_comprador: None,
+                comentario_vendedor: None,
+            };
+            self.calificaciones_por_orden.insert(orden_id, &calificaciones);
+
+            // Separar la comisión del marketplace y liberar el resto al vendedor
+            let comision = orden.monto
+                .checked_mul(self.comision_bps as u128)
+                .ok_or(ContractError::Overflow)?
+                / BPS_BASE;
+            self.fondos_acumulados = self.fondos_acumulados
+                .checked_add(comision)
+                .ok_or(ContractError::Overflow)?;
+            let pago_vendedor = orden.monto
+                .checked_sub(comision)
+                .ok_or(ContractError::Overflow)?;
+            self._pagar(orden.vendedor, pago_vendedor)?;
+
+            self.env().emit_event(OrdenRecibida {
+                orden_id,
+                comprador: orden.comprador,
+                vendedor: orden.vendedor,
+            });
+            Ok(())
+        }
+
+        /// El comprador solicita la cancelación de una orden.
+        /// Si ambos aceptan, se cancela y se devuelve el stock.
+        fn _solicitar_cancel_comprador(
+            &mut self, 
+            caller: AccountId, 
+            orden_id: u128
+        ) -> Result<(), ContractError> {
+            let mut orden = self.ordenes
+                .get(orden_id)
+                .ok_or(ContractError::OrdenNoExiste)?;
+            
+            if orden.comprador != caller {
+                return Err(ContractError::NoAutorizado);
+            }
+
+            // Una orden ya cancelada no debe volver a devolver stock ni reembolsar
+            if orden.estado == EstadoOrden::Cancelada {
+                return Err(ContractError::EstadoInvalido);
+            }
+
+            // Solo se puede cancelar si está Pendiente, ParcialmenteEnviado o Enviado
+            if !orden.puede_cancelarse() {
+                return Err(ContractError::EstadoInvalido);
+            }
+
+            orden.comprador_acepta_cancelar = true;
+            // Si el vendedor pre-aprobó las cancelaciones, una orden pendiente se cancela al instante
+            if orden.estado == EstadoOrden::Pendiente && self.tiene_cancelacion_automatica(orden.vendedor) {
+                orden.vendedor_acepta_cancelar = true;
+            }
+            
+            // Si ambos aceptan, cancelar y devolver stock
+            if orden.marcar_cancelada_si_ambos_aceptan() {
+                self._finalizar_cancelacion(orden_id, &mut orden, MotivoCancelacion::MutuoAcuerdo)?;
+            }
+            
+            self.ordenes.insert(orden_id, &orden);
+            Ok(())
+        }
+
+        /// El vendedor acepta la cancelación de una orden.
+        /// Si ambos aceptan, se cancela y se devuelve el stock.
+        fn _aceptar_cancel_vendedor(
+            &mut self, 
+            caller: AccountId, 
+            orden_id: u128
+        ) -> Result<(), ContractError> {
+            let mut orden = self.ordenes
+                .get(orden_id)
+                .ok_or(ContractError::OrdenNoExiste)?;
+            
+            if orden.vendedor != caller {
+                return Err(ContractError::NoAutorizado);
+            }
+
+            // Una orden ya cancelada no debe volver a devolver stock ni reembolsar
+            if orden.estado == EstadoOrden::Cancelada {
+                return Err(ContractError::EstadoInvalido);
+            }
+
+            // Solo se puede cancelar si está Pendiente, ParcialmenteEnviado o Enviado
+            if !orden.puede_cancelarse() {
+                return Err(ContractError::EstadoInvalido);
+            }
+
+            orden.vendedor_acepta_cancelar = true;
+            
+            // Si ambos aceptan, cancelar y devolver stock
+            if orden.marcar_cancelada_si_ambos_aceptan() {
+                self._finalizar_cancelacion(orden_id, &mut orden, MotivoCancelacion::MutuoAcuerdo)?;
+            }
+            
+            self.ordenes.insert(orden_id, &orden);
+            Ok(())
+        }
+
+        /// Registra el pedido de cancelación parcial del comprador.
+        fn _solicitar_cancelacion_parcial(
+            &mut self,
+            caller: AccountId,
+            orden_id: u128,
+            cantidad: u32,
+        ) -> Result<(), ContractError> {
+            let mut orden = self.ordenes
+                .get(orden_id)
+                .ok_or(ContractError::OrdenNoExiste)?;
+
+            if orden.comprador != caller {
+                return Err(ContractError::NoAutorizado);
+            }
+            if !orden.puede_cancelarse() {
+                return Err(ContractError::EstadoInvalido);
+            }
+            // Solo se pueden cancelar las unidades que todavía no se despacharon
+            if cantidad == 0 || cantidad > orden.cantidad.saturating_sub(orden.cantidad_enviada) {
+                return Err(ContractError::DatosInvalidos);
+            }
+
+            orden.cancelacion_parcial_solicitada = Some(cantidad);
+            if orden.estado == EstadoOrden::Pendiente && self.tiene_cancelacion_automatica(orden.vendedor) {
+                return self._aplicar_cancelacion_parcial(orden_id, orden);
+            }
+            self.ordenes.insert(orden_id, &orden);
+            Ok(())
+        }
+
+        /// El vendedor acepta una cancelación parcial pendiente.
+        fn _aceptar_cancelacion_parcial(&mut self, caller: AccountId, orden_id: u128) -> Result<(), ContractError> {
+            let orden = self.ordenes
+                .get(orden_id)
+                .ok_or(ContractError::OrdenNoExiste)?;
+
+            if orden.vendedor != caller {
+                return Err(ContractError::NoAutorizado);
+            }
+            if !orden.puede_cancelarse() || orden.cancelacion_parcial_solicitada.is_none() {
+                return Err(ContractError::EstadoInvalido);
+            }
+            self._aplicar_cancelacion_parcial(orden_id, orden)
+        }
+
+        /// Ajusta stock, monto y liabilidad por la diferencia de unidades. La diferencia se
+        /// calcula en proporción al monto pagado (con su descuento), aunque el vendedor haya
+        /// cambiado el precio después; al aumentar se redondea hacia arriba y al reembolsar
+        /// hacia abajo, como en la cancelación parcial.
+        fn _modificar_cantidad_orden(
+            &mut self,
+            caller: AccountId,
+            orden_id: u128,
+            nueva_cantidad: u32,
+            pago: Balance,
+        ) -> Result<(), ContractError> {
+            let mut orden = self.ordenes
+                .get(orden_id)
+                .ok_or(ContractError::OrdenNoExiste)?;
+
+            if orden.comprador != caller {
+                return Err(ContractError::NoAutorizado);
+            }
+            if orden.estado != EstadoOrden::Pendiente || orden.cancelacion_parcial_solicitada.is_some() {
+                return Err(ContractError::EstadoInvalido);
+            }
+            if nueva_cantidad == 0 {
+                return Err(ContractError::DatosInvalidos);
+            }
+
+            if nueva_cantidad > orden.cantidad {
+                // Sumar unidades es una compra más: se aplican las mismas validaciones
+                self._no_pausado()?;
+                let extra = nueva_cantidad - orden.cantidad;
+                let mut producto = self._validar_compra(caller, orden.producto_id, extra)?;
+                if producto.limite_por_orden.is_some_and(|limite| nueva_cantidad > limite) {
+                    return Err(ContractError::LimitePorOrdenExcedido);
+                }
+                let costo = orden.monto
+                    .checked_mul(extra as u128)
+                    .ok_or(ContractError::Overflow)?
+                    .div_ceil(orden.cantidad as u128);
+                self._verificar_pago_nativo(costo, pago)?;
+
+                producto.cantidad -= extra;
+                orden.monto = orden.monto.checked_add(costo).ok_or(ContractError::Overflow)?;
+                self.liabilidad_reembolsos = self.liabilidad_reembolsos
+                    .checked_add(costo)
+                    .ok_or(ContractError::Overflow)?;
+                orden.cantidad = nueva_cantidad;
+                self.productos.insert(orden.producto_id, &producto);
+                self.ordenes.insert(orden_id, &orden);
+                self.env().emit_event(StockActualizado {
+                    producto_id: orden.producto_id,
+                    cantidad_nueva: producto.cantidad,
+                });
+                return self._cobrar_token(caller, costo);
+            }
+
+            if pago != 0 {
+                return Err(ContractError::PagoIncorrecto);
+            }
+            if nueva_cantidad == orden.cantidad {
+                return Ok(());
+            }
+
+            let devueltas = orden.cantidad - nueva_cantidad;
+            let reembolso = orden.monto
+                .checked_mul(devueltas as u128)
+                .ok_or(ContractError::Overflow)?
+                / orden.cantidad as u128;
+            orden.cantidad = nueva_cantidad;
+            orden.monto = orden.monto.checked_sub(reembolso).ok_or(ContractError::Overflow)?;
+            self.liabilidad_reembolsos = self.liabilidad_reembolsos.saturating_sub(reembolso);
+            self._devolver_stock(orden.producto_id, devueltas)?;
+            self.ordenes.insert(orden_id, &orden);
+
+            if reembolso > 0 {
+                self._pagar(orden.comprador, reembolso)?;
+            }
+            Ok(())
+        }
+
+        /// Aparta stock para un comprador sin crear la orden. Falla si ya tiene
+        /// `MAX_RESERVAS_POR_COMPRADOR` reservas sin confirmar.
+        fn _reservar_stock(
+            &mut self,
+            comprador: AccountId,
+            producto_id: u128,
+            cantidad: u32,
+            duracion: Timestamp,
+        ) -> Result<u128, ContractError> {
+            if duracion == 0 || duracion > MAX_DURACION_RESERVA {
+                return Err(ContractError::DatosInvalidos);
+            }
+            let reservas_comprador = self.reservas_por_comprador.get(comprador).unwrap_or(0);
+            if reservas_comprador >= MAX_RESERVAS_POR_COMPRADOR {
+                return Err(ContractError::LimiteReservasAlcanzado);
+            }
+            let mut producto = self._validar_compra(comprador, producto_id, cantidad)?;
+            producto.cantidad = producto.cantidad
+                .checked_sub(cantidad)
+                .ok_or(ContractError::Overflow)?;
+            self.productos.insert(producto_id, &producto);
+            self.env().emit_event(StockActualizado {
+                producto_id,
+                cantidad_nueva: producto.cantidad,
+            });
+
+            let rid = self.siguiente_reserva_id;
+            let vence_en = self.env().block_timestamp()
+                .checked_add(duracion)
+                .ok_or(ContractError::Overflow)?;
+            self.reservas.insert(rid, &Reserva { comprador, producto_id, cantidad, vence_en });
+            self.reservas_por_comprador.insert(comprador, &reservas_comprador.saturating_add(1));
+            self.siguiente_reserva_id = rid.checked_add(1).ok_or(ContractError::Overflow)?;
+            Ok(rid)
+        }
+
+        /// Devuelve las unidades de la reserva al stock y la elimina.
+        fn _devolver_reserva(&mut self, reserva_id: u128, reserva: &Reserva) -> Result<(), ContractError> {
+            if let Some(mut producto) = self.productos.get(reserva.producto_id) {
+                producto.aumentar_stock(reserva.cantidad)?;
+                self.productos.insert(reserva.producto_id, &producto);
+                self.env().emit_event(StockActualizado {
+                    producto_id: reserva.producto_id,
+                    cantidad_nueva: producto.cantidad,
+                });
+            }
+            self.reservas.remove(reserva_id);
+            let reservas_comprador = self.reservas_por_comprador.get(reserva.comprador).unwrap_or(0);
+            self.reservas_por_comprador.insert(reserva.comprador, &reservas_comprador.saturating_sub(1));
+            Ok(())
+        }
+
+        /// Convierte una reserva vigente del caller en una orden al precio actual.
+        fn _confirmar_reserva(
+            &mut self,
+            caller: AccountId,
+            reserva_id: u128,
+            descuento_bps: u16,
+            pago: Balance,
+        ) -> Result<u128, ContractError> {
+            let reserva = self.reservas.get(reserva_id).ok_or(ContractError::ReservaNoExiste)?;
+            if reserva.comprador != caller {
+                return Err(ContractError::NoAutorizado);
+            }
+            if self.env().block_timestamp() >= reserva.vence_en {
+                return Err(ContractError::ReservaVencida);
+            }
+            self._validar_pago(reserva.producto_id, reserva.cantidad, descuento_bps, pago)?;
+            // Las unidades vuelven al stock y la orden las descuenta de nuevo con las validaciones habituales
+            self._devolver_reserva(reserva_id, &reserva)?;
+            self._crear_orden_con_descuento(caller, reserva.producto_id, reserva.cantidad, descuento_bps)
+        }
+
+        /// Libera las reservas vencidas entre `primera_reserva_pendiente` y los siguientes
+        /// `MAX_ESCANEO_RESERVAS` IDs, y adelanta el cursor hasta la primera que sigue pendiente.
+        fn _liberar_reservas_vencidas(&mut self) -> u32 {
+            let ahora = self.env().block_timestamp();
+            let inicio = self.primera_reserva_pendiente;
+            let fin = inicio.saturating_add(MAX_ESCANEO_RESERVAS).min(self.siguiente_reserva_id);
+            let mut primera_pendiente: Option<u128> = None;
+            let mut liberadas: u32 = 0;
+            for id in inicio..fin {
+                let Some(reserva) = self.reservas.get(id) else {
+                    continue;
+                };
+                if ahora >= reserva.vence_en && self._devolver_reserva(id, &reserva).is_ok() {
+                    liberadas = liberadas.saturating_add(1);
+                } else {
+                    primera_pendiente.get_or_insert(id);
+                }
+            }
+            self.primera_reserva_pendiente = primera_pendiente.unwrap_or(fin);
+            liberadas
+        }
+
+        /// Descuenta de la orden las unidades solicitadas, devuelve ese stock y reembolsa la
+        /// parte proporcional del escrow. Si no quedan unidades, la orden se cancela completa.
+        fn _aplicar_cancelacion_parcial(&mut self, orden_id: u128, mut orden: Orden) -> Result<(), ContractError> {
+            let cantidad = orden.cancelacion_parcial_solicitada
+                .take()
+                .ok_or(ContractError::EstadoInvalido)?;
+
+            if cantidad == orden.cantidad {
+                orden.estado = EstadoOrden::Cancelada;
+                self._finalizar_cancelacion(orden_id, &mut orden, MotivoCancelacion::MutuoAcuerdo)?;
+                self.ordenes.insert(orden_id, &orden);
+                return Ok(());
+            }
+
+            let reembolso = orden.monto
+                .checked_mul(cantidad as u128)
+                .ok_or(ContractError::Overflow)?
+                / orden.cantidad as u128;
+            orden.cantidad = orden.cantidad
+                .checked_sub(cantidad)
+                .ok_or(ContractError::DatosInvalidos)?;
+            orden.monto = orden.monto
+                .checked_sub(reembolso)
+                .ok_or(ContractError::Overflow)?;
+            self.liabilidad_reembolsos = self.liabilidad_reembolsos.saturating_sub(reembolso);
+            self._devolver_stock(orden.producto_id, cantidad)?;
+            if orden.estado == EstadoOrden::ParcialmenteEnviado && orden.cantidad_enviada >= orden.cantidad {
+                // Lo que queda de la orden ya fue despachado
+                self._completar_envio(orden_id, &mut orden);
+            } else {
+                self.ordenes.insert(orden_id, &orden);
+            }
+
+            if reembolso > 0 {
+                self._pagar(orden.comprador, reembolso)?;
+            }
+            Ok(())
+        }
+
+        /// Obtiene la última orden creada por un comprador.
+        /// Las órdenes se agregan en orden creciente de ID, así que es el último elemento.
+        fn _ultima_orden_comprador(&self, comprador: AccountId) -> Option<(u128, Orden)> {
+            let oid = *self.ordenes_por_usuario.get(comprador)?.last()?;
+            self.ordenes.get(oid).map(|orden| (oid, orden))
+        }
+
+        /// Obtiene todas las órdenes creadas por un comprador.
+        fn _ver_mis_ordenes(&self, comprador: AccountId) -> Vec<(u128, Orden)> {
+            self.ordenes_por_usuario
+                .get(comprador)
+                .unwrap_or_default()
+                .into_iter()
+                .filter_map(|oid| self.ordenes.get(oid).map(|orden| (oid, orden)))
+                .collect()
+        }
+
+        /// Resuelve el índice de órdenes de un vendedor a sus órdenes completas.
+        fn _ordenes_de_vendedor(&self, vendedor: AccountId) -> Vec<(u128, Orden)> {
+            self.ordenes_por_vendedor
+                .get(vendedor)
+                .unwrap_or_default()
+                .into_iter()
+                .filter_map(|oid| self.ordenes.get(oid).map(|orden| (oid, orden)))
+                .collect()
+        }
+
+        /// Compradores que calificaron al vendedor en las órdenes Recibidas del rango pedido.
+        /// Vacío si el caller no puede ver la reputación del vendedor.
+        fn _calificadores_vendedor(
+            &self,
+            caller: AccountId,
+            vendedor: AccountId,
+            inicio: u32,
+            limite: u32,
+        ) -> Vec<(AccountId, u8)> {
+            if !self._puede_ver_reputacion(caller, vendedor) {
+                return Vec::new();
+            }
+            self.ordenes_por_vendedor
+                .get(vendedor)
+                .unwrap_or_default()
+                .into_iter()
+                .skip(inicio as usize)
+                .take(limite as usize)
+                .filter_map(|oid| {
+                    let orden = self.ordenes.get(oid)?;
+                    if orden.estado != EstadoOrden::Recibido {
+                        return None;
+                    }
+                    let calificacion = self.calificaciones_por_orden.get(oid)?.calificacion_comprador?;
+                    Some((orden.comprador, calificacion))
+                })
+                .collect()
+        }
+
+        /// Junta las órdenes de un usuario en ambos roles y filtra por estado, sin repetir.
+        fn _ordenes_por_estado(&self, usuario: AccountId, estado: EstadoOrden) -> Vec<(u128, Orden)> {
+            let mut ordenes: BTreeMap<u128, Orden> = BTreeMap::new();
+            for (oid, orden) in self._ver_mis_ordenes(usuario)
+                .into_iter()
+                .chain(self._ordenes_de_vendedor(usuario))
+            {
+                if orden.estado == estado {
+                    ordenes.insert(oid, orden);
+                }
+            }
+            ordenes.into_iter().collect()
+        }
+
+        /// Suma el GMV de las órdenes recibidas en la ventana dentro del rango de IDs pedido.
+        fn _gmv_en_rango(&self, desde: u64, hasta: u64, inicio: u128, limite: u32) -> u128 {
+            let inicio = inicio.max(1);
+            let fin = inicio
+                .saturating_add((limite as u128).min(MAX_ESCANEO_ORDENES))
+                .min(self.siguiente_orden_id);
+            let mut total: u128 = 0;
+            for id in inicio..fin {
+                if let Some(orden) = self.ordenes.get(id) {
+                    let en_rango = orden.recibida_en
+                        .is_some_and(|t| (desde..=hasta).contains(&t));
+                    if en_rango {
+                        let monto = orden.precio_unitario.saturating_mul(orden.cantidad as u128);
+                        total = total.saturating_add(monto);
+                    }
+                }
+            }
+            total
+        }
+
+        /// Recorre las órdenes desde `inicio` hasta juntar `limite` en el estado pedido.
+        fn _ordenes_globales_por_estado(
+            &self,
+            caller: AccountId,
+            estado: EstadoOrden,
+            inicio: u128,
+            limite: u32,
+        ) -> Result<Vec<(u128, Orden)>, ContractError> {
+            self._solo_owner(caller)?;
+            let mut acc = Vec::new();
+            for id in inicio.max(1)..self.siguiente_orden_id {
+                if acc.len() as u32 >= limite {
+                    break;
+                }
+                if let Some(orden) = self.ordenes.get(id) {
+                    if orden.estado == estado {
+                        acc.push((id, orden));
+                    }
+                }
+            }
+            Ok(acc)
+        }
+
+        /// Acumula por categoría las unidades de las órdenes recibidas de un vendedor.
+        /// Las órdenes de productos ya eliminados no se cuentan.
+        fn _mix_categorias_vendedor(&self, vendedor: AccountId) -> Vec<(String, u32)> {
+            let mut mix: BTreeMap<String, u32> = BTreeMap::new();
+            for (_, orden) in self._ordenes_de_vendedor(vendedor) {
+                if orden.estado != EstadoOrden::Recibido {
+                    continue;
+                }
+                if let Some(producto) = self.productos.get(orden.producto_id) {
+                    let unidades = mix.entry(producto.categoria).or_insert(0);
+                    *unidades = unidades.saturating_add(orden.cantidad);
+                }
+            }
+            mix.into_iter().collect()
+        }
+
+        /// Recorre el índice de órdenes del comprador y devuelve las que están en disputa.
+        fn _disputas_comprador(&self, comprador: AccountId) -> Vec<(u128, Orden)> {
+            self._ver_mis_ordenes(comprador)
+                .into_iter()
+                .filter(|(_, orden)| orden.estado == EstadoOrden::Disputado)
+                .collect()
+        }
+
+        /// Abre una disputa sobre una orden y registra el momento de apertura.
+        fn _abrir_disputa(&mut self, caller: AccountId, orden_id: u128) -> Result<(), ContractError> {
+            let mut orden = self.ordenes
+                .get(orden_id)
+                .ok_or(ContractError::OrdenNoExiste)?;
+
+            if orden.comprador != caller && orden.vendedor != caller {
+                return Err(ContractError::NoAutorizado);
+            }
+            if !orden.puede_cancelarse() {
+                return Err(ContractError::EstadoInvalido);
+            }
+
+            orden.estado = EstadoOrden::Disputado;
+            orden.disputa_abierta_en = Some(self.env().block_timestamp());
+            self.ordenes.insert(orden_id, &orden);
+            Ok(())
+        }
+
+        /// Cancela una orden en disputa si ya venció el plazo de resolución.
+        fn _resolver_disputa_timelock(&mut self, orden_id: u128) -> Result<(), ContractError> {
+            let mut orden = self.ordenes
+                .get(orden_id)
+                .ok_or(ContractError::OrdenNoExiste)?;
+
+            if orden.estado != EstadoOrden::Disputado {
+                return Err(ContractError::DisputaNoAbierta);
+            }
+            let abierta_en = orden.disputa_abierta_en.ok_or(ContractError::DisputaNoAbierta)?;
+            let transcurrido = self.env().block_timestamp().saturating_sub(abierta_en);
+            if transcurrido <= PLAZO_RESOLUCION_DISPUTA {
+                return Err(ContractError::TimeoutNoAlcanzado);
+            }
+
+            orden.estado = EstadoOrden::Cancelada;
+            self._finalizar_cancelacion(orden_id, &mut orden, MotivoCancelacion::Disputa)?;
+            self.ordenes.insert(orden_id, &orden);
+            Ok(())
+        }
+
+        /// Resuelve una disputa abierta según la decisión del owner.
+        fn _resolver_disputa(
+            &mut self,
+            caller: AccountId,
+            orden_id: u128,
+            favor_comprador: bool,
+        ) -> Result<(), ContractError> {
+            self._solo_owner(caller)?;
+            let mut orden = self.ordenes
+                .get(orden_id)
+                .ok_or(ContractError::OrdenNoExiste)?;
+
+            if orden.estado != EstadoOrden::Disputado {
+                return Err(ContractError::DisputaNoAbierta);
+            }
+
+            if favor_comprador {
+                orden.estado = EstadoOrden::Cancelada;
+                self._finalizar_cancelacion(orden_id, &mut orden, MotivoCancelacion::Disputa)?;
+                self.ordenes.insert(orden_id, &orden);
+                Ok(())
+            } else {
+                self._completar_recepcion(orden_id, orden)
+            }
+        }
+
+        /// Cancela una orden pendiente cuyo plazo de envío ya venció.
+        fn _cancelar_por_inactividad(&mut self, caller: AccountId, orden_id: u128) -> Result<(), ContractError> {
+            let mut orden = self.ordenes
+                .get(orden_id)
+                .ok_or(ContractError::OrdenNoExiste)?;
+
+            if orden.comprador != caller {
+                return Err(ContractError::NoAutorizado);
+            }
+            if orden.estado != EstadoOrden::Pendiente {
+                return Err(ContractError::EstadoInvalido);
+            }
+            let transcurrido = self.env().block_timestamp().saturating_sub(orden.creada_en);
+            if transcurrido <= self.timeout_envio {
+                return Err(ContractError::TimeoutNoAlcanzado);
+            }
+
+            orden.estado = EstadoOrden::Cancelada;
+            self._finalizar_cancelacion(orden_id, &mut orden, MotivoCancelacion::TimeoutEnvio)?;
+            self.ordenes.insert(orden_id, &orden);
+            Ok(())
+        }
+
+        /// Completa la recepción de una orden Enviada cuyo plazo de recepción ya venció.
+        fn _forzar_recepcion(&mut self, caller: AccountId, orden_id: u128) -> Result<(), ContractError> {
+            self._solo_owner(caller)?;
+            let orden = self.ordenes
+                .get(orden_id)
+                .ok_or(ContractError::OrdenNoExiste)?;
+
+            if orden.estado != EstadoOrden::Enviado {
+                return Err(ContractError::EstadoInvalido);
+            }
+            let enviada_en = orden.enviada_en.ok_or(ContractError::EstadoInvalido)?;
+            let transcurrido = self.env().block_timestamp().saturating_sub(enviada_en);
+            if transcurrido <= self.timeout_recepcion {
+                return Err(ContractError::TimeoutNoAlcanzado);
+            }
+
+            self._completar_recepcion(orden_id, orden)
+        }
+
+        /// Cancela una orden Pendiente a pedido de su vendedor.
+        fn _vendedor_rechaza_orden(&mut self, caller: AccountId, orden_id: u128) -> Result<(), ContractError> {
+            let mut orden = self.ordenes
+                .get(orden_id)
+                .ok_or(ContractError::OrdenNoExiste)?;
+
+            if orden.vendedor != caller {
+                return Err(ContractError::NoAutorizado);
+            }
+            if orden.estado != EstadoOrden::Pendiente {
+                return Err(ContractError::EstadoInvalido);
+            }
+
+            orden.estado = EstadoOrden::Cancelada;
+            self._finalizar_cancelacion(orden_id, &mut orden, MotivoCancelacion::RechazoVendedor)?;
+            self.ordenes.insert(orden_id, &orden);
+            Ok(())
+        }
+
+        /// Cancela todas las órdenes en estado Pendiente de un vendedor.
+        fn _cancelar_pendientes_vendedor(&mut self, vendedor: AccountId) -> Result<u32, ContractError> {
+            let mut canceladas: u32 = 0;
+            for oid in self.ordenes_por_vendedor.get(vendedor).unwrap_or_default() {
+                let Some(mut orden) = self.ordenes.get(oid) else {
+                    continue;
+                };
+                if orden.estado != EstadoOrden::Pendiente {
+                    continue;
+                }
+                orden.estado = EstadoOrden::Cancelada;
+                self._finalizar_cancelacion(oid, &mut orden, MotivoCancelacion::RechazoVendedor)?;
+                self.ordenes.insert(oid, &orden);
+                canceladas = canceladas.checked_add(1).ok_or(ContractError::Overflow)?;
+            }
+            Ok(canceladas)
+        }
+
+        /// Aplica los efectos de una orden que acaba de pasar a Cancelada
+        /// (devolución de stock, reembolso del escrow al comprador y emisión del evento).
+        /// El monto de la orden queda en cero para que no pueda reembolsarse dos veces.
+        fn _finalizar_cancelacion(
+            &mut self,
+            orden_id: u128,
+            orden: &mut Orden,
+            motivo: MotivoCancelacion,
+        ) -> Result<(), ContractError> {
+            orden.motivo_cancelacion = Some(motivo.clone());
+            self._devolver_stock(orden.producto_id, orden.cantidad)?;
+            self.liabilidad_reembolsos = self.liabilidad_reembolsos.saturating_sub(orden.monto);
+
+            let reembolso = orden.monto;
+            orden.monto = 0;
+            if reembolso > 0 {
+                // Si la transferencia falla, el Err revierte la cancelación y el comprador puede reintentar
+                self._pagar(orden.comprador, reembolso)?;
+            }
+
+            for usuario in [orden.comprador, orden.vendedor] {
+                let cancelaciones = self.cancelaciones_por_usuario.get(usuario).unwrap_or(0);
+                self.cancelaciones_por_usuario.insert(usuario, &cancelaciones.saturating_add(1));
+            }
+
+            self.env().emit_event(OrdenCancelada {
+                orden_id,
+                comprador: orden.comprador,
+                vendedor: orden.vendedor,
+                motivo,
+            });
+            Ok(())
+        }
+
+        /// Devuelve stock a un producto cuando se cancela una orden.
+        fn _devolver_stock(
+            &mut self, 
+            producto_id: u128, 
+            cantidad: u32
+        ) -> Result<(), ContractError> {
+            let mut producto = self.productos
+                .get(producto_id)
+                .ok_or(ContractError::ProductoNoEncontrado)?;
+            
+            producto.aumentar_stock(cantidad)?;
+            self.productos.insert(producto_id, &producto);
+            self.env().emit_event(StockActualizado {
+                producto_id,
+                cantidad_nueva: producto.cantidad,
+            });
+            Ok(())
+        }
+
+        /// Valida que el comentario de una calificación no supere `MAX_LONGITUD_COMENTARIO` bytes.
+        fn _validar_comentario(comentario: &Option<String>) -> Result<(), ContractError> {
+            if comentario.as_ref().is_some_and(|c| c.len() > MAX_LONGITUD_COMENTARIO) {
+                return Err(ContractError::DatosInvalidos);
+            }
+            Ok(())
+        }
+
+        /// Valida la calificación antes de marcar la recepción, para no dejar la orden
+        /// Recibida sin calificar.
+        fn _marcar_recibida_y_calificar(
+            &mut self,
+            caller: AccountId,
+            orden_id: u128,
+            calificacion: u8,
+        ) -> Result<(), ContractError> {
+            Self::_validar_calificacion(calificacion)?;
+            self._marcar_recibida(caller, orden_id)?;
+            self._calificar_vendedor(caller, orden_id, calificacion, None)
+        }
+
+        /// Valida que una calificación esté en el rango válido (1-5).
+        fn _validar_calificacion(calificacion: u8) -> Result<(), ContractError> {
+            if calificacion < 1 || calificacion > 5 {
+                return Err(ContractError::CalificacionInvalida);
+            }
+            Ok(())
+        }
+
+        /// El comprador califica al vendedor.
+        fn _calificar_vendedor(
+            &mut self,
+            comprador: AccountId,
+            orden_id: u128,
+            calificacion: u8,
+            comentario: Option<String>,
+        ) -> Result<(), ContractError> {
+            // Validar rango de calificación y largo del comentario
+            Self::_validar_calificacion(calificacion)?;
+            Self::_validar_comentario(&comentario)?;
+
+            // Obtener y validar la orden
+            let orden = self.ordenes
+                .get(orden_id)
+                .ok_or(ContractError::OrdenNoExiste)?;
+
+            // Verificar que el caller es el comprador
+            if orden.comprador != comprador {
+                return Err(ContractError::NoAutorizado);
+            }
+
+            // Solo se puede calificar si la orden está recibida
+            if orden.estado != EstadoOrden::Recibido {
+                return Err(ContractError::OrdenNoRecibida);
+            }
+
+            // Obtener calificaciones existentes (solo existen para órdenes recibidas)
+            let mut calificaciones = self.calificaciones_por_orden
+                .get(orden_id)
+                .ok_or(ContractError::OrdenNoRecibida)?;
+
+            // Verificar que no haya calificado antes
+            if calificaciones.calificacion_comprador.is_some() {
+                return Err(ContractError::YaCalificado);
+            }
+
+            // Guardar la calificación
+            calificaciones.calificacion_comprador = Some(calificacion);
+            calificaciones.comentario_comprador = comentario;
+            self.calificaciones_por_orden.insert(orden_id, &calificaciones);
+
+            // Actualizar reputación del vendedor
+            let mut reputacion = self.reputaciones
+                .get(orden.vendedor)
+                .unwrap_or_else(ReputacionData::new);
+            reputacion.agregar_calificacion_vendedor(calificacion)?;
+            self.reputaciones.insert(orden.vendedor, &reputacion);
+            self._registrar_calificacion_dada(comprador, calificacion)?;
+
+            let (suma, cantidad) = self.calificaciones_producto
+                .get(orden.producto_id)
+                .unwrap_or((0, 0));
+            let suma = suma.checked_add(calificacion as u128).ok_or(ContractError::Overflow)?;
+            let cantidad = cantidad.checked_add(1).ok_or(ContractError::Overflow)?;
+            self.calificaciones_producto.insert(orden.producto_id, &(suma, cantidad));
+
+            self.env().emit_event(CalificacionRegistrada {
+                orden_id,
+                calificado: orden.vendedor,
+                calificador: comprador,
+                puntaje: calificacion,
+                es_vendedor: true,
+                promedio: reputacion.promedio_vendedor(),
+            });
+
+            // Actualizar estadísticas de categoría
+            if let Some(producto) = self.productos.get(orden.producto_id) {
+                let mut stats = self.estadisticas_por_categoria
+                    .get(&producto.categoria)
+                    .unwrap_or((0, 0, 0));
+                
+                stats.0 = stats.0.checked_add(1).ok_or(ContractError::Overflow)?;
+                stats.1 = stats.1.checked_add(calificacion as u128).ok_or(ContractError::Overflow)?;
+                stats.2 = stats.2.checked_add(1).ok_or(ContractError::Overflow)?;
+                
+                self.estadisticas_por_categoria.insert(&producto.categoria, &stats);
+
+                let clave = (orden.vendedor, producto.categoria);
+                let (suma, cantidad) = self.reputacion_vendedor_por_categoria
+                    .get(&clave)
+                    .unwrap_or((0, 0));
+                let suma = suma.checked_add(calificacion as u128).ok_or(ContractError::Overflow)?;
+                let cantidad = cantidad.checked_add(1).ok_or(ContractError::Overflow)?;
+                self.reputacion_vendedor_por_categoria.insert(&clave, &(suma, cantidad));
+            }
+
+            Ok(())
+        }
+
+        /// Recorre las órdenes del usuario en ambos roles buscando calificaciones pendientes viejas.
+        fn _calificaciones_pendientes_antiguas(&self, usuario: AccountId, antiguedad_min: u64) -> Vec<u128> {
+            let ahora = self.env().block_timestamp();
+            let como_comprador = self._ver_mis_ordenes(usuario).into_iter().map(|o| (o, true));
+            let como_vendedor = self._ordenes_de_vendedor(usuario).into_iter().map(|o| (o, false));
+
+            let mut pendientes = BTreeSet::new();
+            for ((oid, orden), es_comprador) in como_comprador.chain(como_vendedor) {
+                let Some(recibida_en) = orden.recibida_en else {
+                    continue;
+                };
+                if ahora.saturating_sub(recibida_en) <= antiguedad_min {
+                    continue;
+                }
+                let Some(calificaciones) = self.calificaciones_por_orden.get(oid) else {
+                    continue;
+                };
+                let pendiente = if es_comprador {
+                    calificaciones.calificacion_comprador.is_none()
+                } else {
+                    calificaciones.calificacion_vendedor.is_none()
+                };
+                if pendiente {
+                    pendientes.insert(oid);
+                }
+            }
+            pendientes.into_iter().collect()
+        }
+
+        /// Acumula una calificación en el historial de calificaciones otorgadas por un usuario.
+        fn _registrar_calificacion_dada(&mut self, calificador: AccountId, calificacion: u8) -> Result<(), ContractError> {
+            let (suma, cantidad) = self.calificaciones_dadas.get(calificador).unwrap_or((0, 0));
+            let suma = suma.checked_add(calificacion as u128).ok_or(ContractError::Overflow)?;
+            let cantidad = cantidad.checked_add(1).ok_or(ContractError::Overflow)?;
+            self.calificaciones_dadas.insert(calificador, &(suma, cantidad));
+            Ok(())
+        }
+
+        /// El vendedor califica al comprador.
+        fn _calificar_comprador(
+            &mut self,
+            vendedor: AccountId,
+            orden_id: u128,
+            calificacion: u8,
+            comentario: Option<String>,
+        ) -> Result<(), ContractError> {
+            // Validar rango de calificación y largo del comentario
+            Self::_validar_calificacion(calificacion)?;
+            Self::_validar_comentario(&comentario)?;
+
+            // Obtener y validar la orden
+            let orden = self.ordenes
+                .get(orden_id)
+                .ok_or(ContractError::OrdenNoExiste)?;
+
+            // Verificar que el caller es el vendedor
+            if orden.vendedor != vendedor {
+                return Err(ContractError::NoAutorizado);
+            }
+
+            // Solo se puede calificar si la orden está recibida
+            if orden.estado != EstadoOrden::Recibido {
+                return Err(ContractError::OrdenNoRecibida);
+            }
+
+            // Obtener calificaciones existentes (solo existen para órdenes recibidas)
+            let mut calificaciones = self.calificaciones_por_orden
+                .get(orden_id)
+                .ok_or(ContractError::OrdenNoRecibida)?;
+
+            // Verificar que no haya calificado antes
+            if calificaciones.calificacion_vendedor.is_some() {
+                return Err(ContractError::YaCalificado);
+            }
+
+            // Guardar la calificación
+            calificaciones.calificacion_vendedor = Some(calificacion);
+            calificaciones.comentario_vendedor = comentario;
+            self.calificaciones_por_orden.insert(orden_id, &calificaciones);
+
+            // Actualizar reputación del comprador
+            let mut reputacion = self.reputaciones
+                .get(orden.comprador)
+                .unwrap_or_else(ReputacionData::new);
+            reputacion.agregar_calificacion_comprador(calificacion)?;
+            self.reputaciones.insert(orden.comprador, &reputacion);
+            self._registrar_calificacion_dada(vendedor, calificacion)?;
+
+            self.env().emit_event(CalificacionRegistrada {
+                orden_id,
+                calificado: orden.comprador,
+                calificador: vendedor,
+                puntaje: calificacion,
+                es_vendedor: false,
+                promedio: reputacion.promedio_comprador(),
+            });
+            Ok(())
+        }
+
+        /// Obtiene la reputación de un usuario.
+        fn _obtener_reputacion(&self, usuario: AccountId) -> Option<ReputacionData> {
+            self.reputaciones.get(usuario)
+        }
+
+        /// Indica si el caller puede ver la reputación de `usuario` según la configuración de privacidad.
+        fn _puede_ver_reputacion(&self, caller: AccountId, usuario: AccountId) -> bool {
+            self.reputacion_publica || caller == usuario || caller == self.owner
+        }
+
+        /// Obtiene la reputación de un usuario respetando la configuración de privacidad.
+        fn _reputacion_visible(&self, caller: AccountId, usuario: AccountId) -> Option<ReputacionData> {
+            if !self._puede_ver_reputacion(caller, usuario) {
+                return None;
+            }
+            self._obtener_reputacion(usuario)
+        }
+
+        /// Promedio de un vendedor en una categoría, respetando la configuración de privacidad.
+        fn _reputacion_vendedor_en_categoria(
+            &self,
+            caller: AccountId,
+            vendedor: AccountId,
+            categoria: String,
+        ) -> Option<u128> {
+            if !self._puede_ver_reputacion(caller, vendedor) {
+                return None;
+            }
+            let (suma, cantidad) = self.reputacion_vendedor_por_categoria.get((vendedor, categoria))?;
+            suma.checked_div(cantidad as u128)
+        }
+
+        /// Usuarios con índice de registro en `[inicio, inicio + limite)` cuya reputación
+        /// existe y es visible para el caller.
+        fn _usuarios_con_reputacion(&self, caller: AccountId, inicio: u32, limite: u32) -> Vec<(AccountId, ReputacionData)> {
+            let fin = inicio.saturating_add(limite).min(self.contador_usuarios);
+            let mut resultado = Vec::new();
+            for i in inicio..fin {
+                if let Some(usuario) = self.usuarios_registrados.get(i) {
+                    if let Some(reputacion) = self._reputacion_visible(caller, usuario) {
+                        resultado.push((usuario, reputacion));
+                    }
+                }
+            }
+            resultado
+        }
+
+        /// Verifica que el caller sea el owner del contrato.
+        fn _solo_owner(&self, caller: AccountId) -> Result<(), ContractError> {
+            if caller != self.owner {
+                return Err(ContractError::NoAutorizado);
+            }
+            Ok(())
+        }
+
+        /// Falla con `Pausado` si el marketplace está en pausa de emergencia.
+        fn _no_pausado(&self) -> Result<(), ContractError> {
+            if self.pausado {
+                return Err(ContractError::Pausado);
+            }
+            Ok(())
+        }
+
+        /// Activa o desactiva la pausa de emergencia.
+        fn _establecer_pausa(&mut self, caller: AccountId, pausado: bool) -> Result<(), ContractError> {
+            self._solo_owner(caller)?;
+            self.pausado = pausado;
+            Ok(())
+        }
+
+        /// Guarda un cupón; falla si el código está vacío o el descuento supera `MAX_DESCUENTO_BPS`.
+        fn _crear_cupon(&mut self, caller: AccountId, codigo: String, descuento_bps: u16) -> Result<(), ContractError> {
+            self._solo_owner(caller)?;
+            if codigo.is_empty() || descuento_bps > MAX_DESCUENTO_BPS {
+                return Err(ContractError::CuponInvalido);
+            }
+            self.cupones.insert(codigo, &descuento_bps);
+            Ok(())
+        }
+
+        /// Borra un cupón; falla con `CuponInvalido` si no existía.
+        fn _eliminar_cupon(&mut self, caller: AccountId, codigo: String) -> Result<(), ContractError> {
+            self._solo_owner(caller)?;
+            if !self.cupones.contains(&codigo) {
+                return Err(ContractError::CuponInvalido);
+            }
+            self.cupones.remove(codigo);
+            Ok(())
+        }
+
+        /// Habilita una categoría; falla con `DatosInvalidos` si el nombre está vacío.
+        fn _agregar_categoria(&mut self, caller: AccountId, categoria: String) -> Result<(), ContractError> {
+            self._solo_owner(caller)?;
+            if categoria.is_empty() {
+                return Err(ContractError::DatosInvalidos);
+            }
+            self.categorias_permitidas.insert(categoria, &true);
+            Ok(())
+        }
+
+        /// Deshabilita una categoría; falla con `CategoriaNoPermitida` si no estaba habilitada.
+        fn _quitar_categoria(&mut self, caller: AccountId, categoria: String) -> Result<(), ContractError> {
+            self._solo_owner(caller)?;
+            if !self.categorias_permitidas.contains(&categoria) {
+                return Err(ContractError::CategoriaNoPermitida);
+            }
+            self.categorias_permitidas.remove(categoria);
+            Ok(())
+        }
+
+        /// Falla con `CategoriaNoPermitida` si las categorías están restringidas y la
+        /// indicada no fue habilitada por el owner.
+        fn _validar_categoria(&self, categoria: &str) -> Result<(), ContractError> {
+            if self.categorias_restringidas && !self.categorias_permitidas.get(categoria).unwrap_or(false) {
+                return Err(ContractError::CategoriaNoPermitida);
+            }
+            Ok(())
+        }
+
+        /// Falla con `UsuarioBaneado` si la cuenta está baneada.
+        fn _no_baneado(&self, usuario: AccountId) -> Result<(), ContractError> {
+            if self.esta_baneado(usuario) {
+                return Err(ContractError::UsuarioBaneado);
+            }
+            Ok(())
+        }
+
+        /// Banea o desbanea una cuenta.
+        fn _cambiar_baneo(&mut self, caller: AccountId, usuario: AccountId, baneado: bool) -> Result<(), ContractError> {
+            self._solo_owner(caller)?;
+            if baneado {
+                self.baneados.insert(usuario, &true);
+            } else {
+                self.baneados.remove(usuario);
+            }
+            Ok(())
+        }
+
+        /// Cambia los decimales informados del token.
+        fn _establecer_decimales_token(&mut self, caller: AccountId, decimales: u8) -> Result<(), ContractError> {
+            self._solo_owner(caller)?;
+            self.decimales_token = decimales;
+            Ok(())
+        }
+
+        /// Cambia el plazo para forzar la recepción.
+        fn _establecer_timeout_recepcion(&mut self, caller: AccountId, timeout: u64) -> Result<(), ContractError> {
+            self._solo_owner(caller)?;
+            self.timeout_recepcion = timeout;
+            Ok(())
+        }
+
+        /// Cambia el owner del contrato.
+        fn _transferir_owner(&mut self, caller: AccountId, nuevo: AccountId) -> Result<(), ContractError> {
+            self._solo_owner(caller)?;
+            self.owner = nuevo;
+            Ok(())
+        }
+
+        /// Marca o desmarca a un usuario como verificado.
+        fn _verificar_usuario(
+            &mut self,
+            caller: AccountId,
+            usuario: AccountId,
+            verificado: bool,
+        ) -> Result<(), ContractError> {
+            self._solo_owner(caller)?;
+            self.verificados.insert(usuario, &verificado);
+            Ok(())
+        }
+
+        /// Retira las comisiones acumuladas hacia el owner (patrón pull-payment).
+        fn _retirar_comisiones(&mut self, caller: AccountId) -> Result<u128, ContractError> {
+            self._solo_owner(caller)?;
+            let monto = self.fondos_acumulados;
+            if monto == 0 {
+                return Ok(0);
+            }
+            // Se pone en cero antes de transferir; si la transferencia falla el Err revierte todo
+            self.fondos_acumulados = 0;
+            self._pagar(caller, monto)?;
+            Ok(monto)
+        }
+
+        /// Activa o desactiva el modo de alto riesgo.
+        fn _establecer_modo_alto_riesgo(
+            &mut self,
+            caller: AccountId,
+            activo: bool,
+        ) -> Result<(), ContractError> {
+            self._solo_owner(caller)?;
+            self.modo_alto_riesgo = activo;
+            Ok(())
+        }
+
+        /// Cambia la visibilidad de la reputación.
+        fn _establecer_reputacion_publica(
+            &mut self,
+            caller: AccountId,
+            publica: bool,
+        ) -> Result<(), ContractError> {
+            self._solo_owner(caller)?;
+            self.reputacion_publica = publica;
+            Ok(())
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use ink::env::{test, DefaultEnvironment};
+
+        fn default_accounts() -> test::DefaultAccounts<DefaultEnvironment> {
+            test::default_accounts::<DefaultEnvironment>()
+        }
+
+        /// Plazo de envío usado por los tests: 1 día en milisegundos.
+        const TIMEOUT_ENVIO_TEST: u64 = 24 * 60 * 60 * 1000;
+
+        fn init_contract() -> Marketplace {
+            Marketplace::new(0, TIMEOUT_ENVIO_TEST, false, None, 0).unwrap()
+        }
+
+        #[ink::test]
+        fn registrar_y_obtener_rol_funciona() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            assert_eq!(c._registrar_usuario(accounts.alice, Roles::Comprador), Ok(()));
+            assert_eq!(c._obtener_rol(accounts.alice), Some(Roles::Comprador));
+        }
+
+        #[ink::test]
+        fn no_se_puede_registrar_dos_veces() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            let _ = c._registrar_usuario(accounts.alice, Roles::Vendedor);
+            assert_eq!(c._registrar_usuario(accounts.alice, Roles::Comprador), Err(ContractError::YaRegistrado));
+        }
+
+        #[ink::test]
+        fn modificar_rol_funciona_y_falla_si_no_registrado() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            assert_eq!(c._modificar_rol(accounts.alice, Roles::Ambos), Err(ContractError::UsuarioNoRegistrado));
+            c._registrar_usuario(accounts.alice, Roles::Comprador).unwrap();
+            assert_eq!(c._modificar_rol(accounts.alice, Roles::Vendedor), Ok(()));
+            assert_eq!(c._obtener_rol(accounts.alice), Some(Roles::Vendedor));
+        }
+
+        #[ink::test]
+        fn publicar_producto_funciona() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
+            let id = c._publicar_producto(
+                accounts.bob,
+                "Camisa".into(),
+                "Camisa de lino".into(),
+                100,
+                3,
+                "Ropa".into(),
+                None
+            ).unwrap();
+            assert_eq!(id, 1);
+        }
+
+        #[ink::test]
+        fn publicar_producto_con_rol_invalido_falla() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            assert_eq!(
+                c._publicar_producto(accounts.alice, "A".into(), "B".into(), 1, 1, "X".into(), None),
+                Err(ContractError::NoVendedor)
+            );
+        }
+
+        #[ink::test]
+        fn crear_orden_funciona_y_valida_stock() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            c._registrar_usuario(accounts.alice, Roles::Comprador).unwrap();
+            c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
+
+            c._publicar_producto(accounts.bob, "Libro".into(), "Rust".into(), 500, 5, "Libros".into(), None).unwrap();
+            let oid = c._crear_orden(accounts.alice, 1, 3).unwrap();
+            assert_eq!(oid, 1);
+
+            assert_eq!(c._crear_orden(accounts.alice, 1, 10), Err(ContractError::StockInsuficiente));
+            assert_eq!(c._crear_orden(accounts.alice, 999, 1), Err(ContractError::ProductoNoEncontrado));
+        }
+
+        #[ink::test]
+        fn crear_orden_por_usuario_no_autorizado_falla() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
+            c._publicar_producto(accounts.bob, "Item".into(), "Desc".into(), 1, 1, "C".into(), None).unwrap();
+            assert_eq!(
+                c._crear_orden(accounts.charlie, 1, 1),
+                Err(ContractError::UsuarioNoRegistrado)
+            );
+            // Registrado pero sin rol de comprador
+            c._registrar_usuario(accounts.django, Roles::Vendedor).unwrap();
+            assert_eq!(
+                c._crear_orden(accounts.django, 1, 1),
+                Err(ContractError::NoAutorizado)
+            );
+        }
+
+        #[ink::test]
+        fn orden_estado_transiciones_correctas() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            c._registrar_usuario(accounts.alice, Roles::Comprador).unwrap();
+            c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
+
+            c._publicar_producto(accounts.bob, "Mouse".into(), "Gaming".into(), 200, 2, "Perifericos".into(), None).unwrap();
+            let oid = c._crear_orden(accounts.alice, 1, 1).unwrap();
+
+            assert_eq!(c._marcar_enviada(accounts.bob, oid, None), Ok(()));
+            assert_eq!(c._marcar_recibida(accounts.alice, oid), Ok(()));
+        }
+
+        #[ink::test]
+        fn estado_invalido_en_transiciones() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            c._registrar_usuario(accounts.alice, Roles::Comprador).unwrap();
+            c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
+
+            c._publicar_producto(accounts.bob, "K".into(), "J".into(), 2, 2, "Z".into(), None).unwrap();
+            let oid = c._crear_orden(accounts.alice, 1, 1).unwrap();
+
+            c._marcar_enviada(accounts.bob, oid, None).unwrap();
+            assert_eq!(c._marcar_enviada(accounts.bob, oid, None), Err(ContractError::EstadoInvalido));
+            assert_eq!(c._marcar_recibida(accounts.bob, oid), Err(ContractError::NoAutorizado));
+            assert_eq!(c._marcar_recibida(accounts.alice, oid), Ok(()));
+            assert_eq!(c._marcar_recibida(accounts.alice, oid), Err(ContractError::EstadoInvalido));
+        }
+
+        #[ink::test]
+        fn cancelacion_mutua_funciona() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            c._registrar_usuario(accounts.alice, Roles::Comprador).unwrap();
+            c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
+
+            c._publicar_producto(accounts.bob, "Café".into(), "Molido".into(), 100, 1, "Alimentos".into(), None).unwrap();
+            let oid = c._crear_orden(accounts.alice, 1, 1).unwrap();
+
+            assert_eq!(c._solicitar_cancel_comprador(accounts.alice, oid), Ok(()));
+            assert_eq!(c._aceptar_cancel_vendedor(accounts.bob, oid), Ok(()));
+
+            let orden = c.ordenes.get(oid).unwrap();
+            assert_eq!(orden.estado, EstadoOrden::Cancelada);
+        }
+
+        #[ink::test]
+        fn solo_uno_cancela_no_avanza_estado() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            c._registrar_usuario(accounts.alice, Roles::Comprador).unwrap();
+            c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
+
+            c._publicar_producto(accounts.bob, "Mate".into(), "Dulce".into(), 100, 1, "Bebidas".into(), None).unwrap();
+            let oid = c._crear_orden(accounts.alice, 1, 1).unwrap();
+
+            c._solicitar_cancel_comprador(accounts.alice, oid).unwrap();
+            let orden = c.ordenes.get(oid).unwrap();
+            assert_eq!(orden.estado, EstadoOrden::Pendiente);
+        }
+
+        #[ink::test]
+        fn acciones_sobre_orden_inexistente_fallan() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            assert_eq!(c._marcar_enviada(accounts.bob, 123, None), Err(ContractError::OrdenNoExiste));
+            assert_eq!(c._marcar_recibida(accounts.alice, 123), Err(ContractError::OrdenNoExiste));
+            assert_eq!(c._aceptar_cancel_vendedor(accounts.bob, 123), Err(ContractError::OrdenNoExiste));
+            assert_eq!(c._solicitar_cancel_comprador(accounts.alice, 123), Err(ContractError::OrdenNoExiste));
+        }
+
+        #[ink::test]
+        fn ver_mis_productos_y_todos_funciona() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
+            c._publicar_producto(accounts.bob, "A".into(), "B".into(), 1, 1, "X".into(), None).unwrap();
+            c._publicar_producto(accounts.bob, "C".into(), "D".into(), 1, 1, "Y".into(), None).unwrap();
+
+            let personales = c._ver_mis_productos(accounts.bob);
+            assert_eq!(personales.len(), 2);
+
+            let todos = c._ver_todos_los_productos();
+            assert_eq!(todos.len(), 2);
+        }
+
+        #[ink::test]
+        fn overflow_en_productos_y_ordenes() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            c.siguiente_producto_id = u128::MAX;
+            c.siguiente_orden_id = u128::MAX;
+            c._registrar_usuario(accounts.alice, Roles::Comprador).unwrap();
+            c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
+            let prod = c._publicar_producto(accounts.bob, "Z".into(), "Z".into(), 1, 1, "Z".into(), None);
+            assert_eq!(prod, Err(ContractError::Overflow));
+
+            c.siguiente_producto_id = 1;
+            c._publicar_producto(accounts.bob, "A".into(), "B".into(), 1, 1, "C".into(), None).unwrap();
+            let orden = c._crear_orden(accounts.alice, 1, 1);
+            assert_eq!(orden, Err(ContractError::Overflow));
+        }
+
+        // ===== Tests for public message dispatchers =====
+        #[ink::test]
+        fn public_registrar_usuario_mensaje() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            // Alice registers
+            test::set_caller::<DefaultEnvironment>(accounts.alice);
+            assert_eq!(c.registrar_usuario(Roles::Comprador), Ok(()));
+            // Alice duplicate registration
+            assert_eq!(c.registrar_usuario(Roles::Vendedor), Err(ContractError::YaRegistrado));
+        }
+
+        #[ink::test]
+        fn public_modificar_rol_mensaje() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            test::set_caller::<DefaultEnvironment>(accounts.bob);
+            assert_eq!(c.modificar_rol(Roles::Vendedor), Err(ContractError::UsuarioNoRegistrado));
+            // Register and modify
+            assert_eq!(c.registrar_usuario(Roles::Ambos), Ok(()));
+            assert_eq!(c.modificar_rol(Roles::Vendedor), Ok(()));
+        }
+
+        #[ink::test]
+        fn public_obtener_rol_mensaje() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            // None
+            assert_eq!(c.obtener_rol(accounts.charlie), None);
+            // After registration
+            test::set_caller::<DefaultEnvironment>(accounts.charlie);
+            c.registrar_usuario(Roles::Ambos).unwrap();
+            assert_eq!(c.obtener_rol(accounts.charlie), Some(Roles::Ambos));
+        }
+
+        #[ink::test]
+        fn public_publicar_producto_mensaje() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            test::set_caller::<DefaultEnvironment>(accounts.alice);
+            // Fail without role
+            assert_eq!(c.publicar_producto("X".into(), "Y".into(), 10,1,"C".into(), None), Err(ContractError::NoVendedor));
+            // Register and publish
+            c.registrar_usuario(Roles::Vendedor).unwrap();
+            let pid = c.publicar_producto("X".into(),"Y".into(),10,1,"C".into(), None).unwrap();
+            assert_eq!(pid, 1);
+        }
+
+        #[ink::test]
+        fn public_ver_productos_mensaje() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            test::set_caller::<DefaultEnvironment>(accounts.alice);
+            c.registrar_usuario(Roles::Vendedor).unwrap();
+            c.publicar_producto("A".into(),"B".into(),1,1,"Cat".into(), None).unwrap();
+            // ver_mis
+            let own = c.ver_mis_productos();
+            assert_eq!(own.len(),1);
+            // ver_todos
+            let all = c.ver_todos_los_productos();
+            assert_eq!(all.len(),1);
+        }
+
+        #[ink::test]
+        fn public_crear_orden_de_compra_mensaje() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            // setup
+            test::set_caller::<DefaultEnvironment>(accounts.alice);
+            c.registrar_usuario(Roles::Vendedor).unwrap();
+            let pid = c.publicar_producto("P".into(),"D".into(),5,2,"Cat".into(), None).unwrap();
+            test::set_caller::<DefaultEnvironment>(accounts.bob);
+            c.registrar_usuario(Roles::Comprador).unwrap();
+            test::set_value_transferred::<DefaultEnvironment>(5);
+            let oid = c.crear_orden_de_compra(pid, 1, None).unwrap();
+            assert_eq!(oid,1);
+        }
+
+        #[ink::test]
+        fn public_marcar_enviada_recibida_mensajes() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            // prepare
+            test::set_caller::<DefaultEnvironment>(accounts.alice);
+            c.registrar_usuario(Roles::Vendedor).unwrap();
+            let pid = c.publicar_producto("P".into(),"D".into(),5,1,"Cat".into(), None).unwrap();
+            test::set_caller::<DefaultEnvironment>(accounts.bob);
+            c.registrar_usuario(Roles::Comprador).unwrap();
+            test::set_value_transferred::<DefaultEnvironment>(5);
+            let oid = c.crear_orden_de_compra(pid, 1, None).unwrap();
+            // send
+            test::set_caller::<DefaultEnvironment>(accounts.alice);
+            assert_eq!(c.marcar_orden_enviada(oid, None), Ok(()));
+            // receive
+            test::set_caller::<DefaultEnvironment>(accounts.bob);
+            assert_eq!(c.marcar_orden_recibida(oid), Ok(()));
+        }
+
+        #[ink::test]
+        fn public_cancelacion_mensajes() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            test::set_caller::<DefaultEnvironment>(accounts.alice);
+            c.registrar_usuario(Roles::Vendedor).unwrap();
+            let pid = c.publicar_producto("C".into(),"D".into(),5,1,"Cat".into(), None).unwrap();
+            test::set_caller::<DefaultEnvironment>(accounts.bob);
+            c.registrar_usuario(Roles::Comprador).unwrap();
+            test::set_value_transferred::<DefaultEnvironment>(5);
+            let oid = c.crear_orden_de_compra(pid, 1, None).unwrap();
+            // comprador solicita
+            assert_eq!(c.comprador_solicita_cancelacion(oid), Ok(()));
+            // vendedor acepta
+            test::set_caller::<DefaultEnvironment>(accounts.alice);
+            assert_eq!(c.vendedor_acepta_cancelacion(oid), Ok(()));
+        }
+
+        // ===== Tests adicionales solicitados =====
+
+        #[ink::test]
+        fn registrar_usuario_como_vendedor() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            assert_eq!(c._registrar_usuario(accounts.alice, Roles::Vendedor), Ok(()));
+            assert_eq!(c._obtener_rol(accounts.alice), Some(Roles::Vendedor));
+        }
+
+        #[ink::test]
+        fn registrar_usuario_como_ambos() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            assert_eq!(c._registrar_usuario(accounts.alice, Roles::Ambos), Ok(()));
+            assert_eq!(c._obtener_rol(accounts.alice), Some(Roles::Ambos));
+        }
+
+        #[ink::test]
+        fn modificar_rol_solo_permite_agregar_no_quitar() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            // Registrar como Comprador y agregar Vendedor -> Ambos
+            c._registrar_usuario(accounts.alice, Roles::Comprador).unwrap();
+            assert_eq!(c._modificar_rol(accounts.alice, Roles::Vendedor), Ok(()));
+            assert_eq!(c._obtener_rol(accounts.alice), Some(Roles::Ambos));
+            
+            // Registrar como Vendedor y agregar Comprador -> Ambos
+            c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
+            assert_eq!(c._modificar_rol(accounts.bob, Roles::Comprador), Ok(()));
+            assert_eq!(c._obtener_rol(accounts.bob), Some(Roles::Ambos));
+            
+            // Si ya es Ambos, intentar cambiar a Vendedor mantiene Ambos (no quita Comprador)
+            c._registrar_usuario(accounts.charlie, Roles::Ambos).unwrap();
+            assert_eq!(c._modificar_rol(accounts.charlie, Roles::Vendedor), Ok(()));
+            assert_eq!(c._obtener_rol(accounts.charlie), Some(Roles::Ambos));
+        }
+
+        #[ink::test]
+        fn no_se_puede_publicar_con_nombre_vacio() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            c._registrar_usuario(accounts.alice, Roles::Vendedor).unwrap();
+            assert_eq!(
+                c._publicar_producto(accounts.alice, "".into(), "Desc".into(), 100, 1, "Cat".into(), None),
+                Err(ContractError::DatosInvalidos)
+            );
+        }
+
+        #[ink::test]
+        fn no_se_puede_publicar_con_descripcion_vacia() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            c._registrar_usuario(accounts.alice, Roles::Vendedor).unwrap();
+            assert_eq!(
+                c._publicar_producto(accounts.alice, "Nombre".into(), "".into(), 100, 1, "Cat".into(), None),
+                Err(ContractError::DatosInvalidos)
+            );
+        }
+
+        #[ink::test]
+        fn no_se_puede_publicar_con_categoria_vacia() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            c._registrar_usuario(accounts.alice, Roles::Vendedor).unwrap();
+            assert_eq!(
+                c._publicar_producto(accounts.alice, "Nombre".into(), "Desc".into(), 100, 1, "".into(), None),
+                Err(ContractError::DatosInvalidos)
+            );
+        }
+
+        #[ink::test]
+        fn no_se_puede_publicar_con_precio_cero() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            c._registrar_usuario(accounts.alice, Roles::Vendedor).unwrap();
+            assert_eq!(
+                c._publicar_producto(accounts.alice, "Nombre".into(), "Desc".into(), 0, 1, "Cat".into(), None),
+                Err(ContractError::DatosInvalidos)
+            );
+        }
+
+        #[ink::test]
+        fn no_se_puede_publicar_con_cantidad_cero() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            c._registrar_usuario(accounts.alice, Roles::Vendedor).unwrap();
+            assert_eq!(
+                c._publicar_producto(accounts.alice, "Nombre".into(), "Desc".into(), 100, 0, "Cat".into(), None),
+                Err(ContractError::DatosInvalidos)
+            );
+        }
+
+        #[ink::test]
+        fn no_se_puede_crear_orden_con_cantidad_cero() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            c._registrar_usuario(accounts.alice, Roles::Comprador).unwrap();
+            c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
+            c._publicar_producto(accounts.bob, "Producto".into(), "Desc".into(), 100, 5, "Cat".into(), None).unwrap();
+            
+            assert_eq!(
+                c._crear_orden(accounts.alice, 1, 0),
+                Err(ContractError::StockInsuficiente)
+            );
+        }
+
+        #[ink::test]
+        fn no_se_puede_marcar_orden_recibida_desde_pendiente() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            c._registrar_usuario(accounts.alice, Roles::Comprador).unwrap();
+            c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
+            c._publicar_producto(accounts.bob, "Producto".into(), "Desc".into(), 100, 5, "Cat".into(), None).unwrap();
+            let oid = c._crear_orden(accounts.alice, 1, 1).unwrap();
+
+            // No se puede pasar directamente de Pendiente a Recibido
+            assert_eq!(
+                c._marcar_recibida(accounts.alice, oid),
+                Err(ContractError::EstadoInvalido)
+            );
+        }
+
+        #[ink::test]
+        fn no_se_puede_retroceder_de_recibido_a_enviado() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            c._registrar_usuario(accounts.alice, Roles::Comprador).unwrap();
+            c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
+            c._publicar_producto(accounts.bob, "Producto".into(), "Desc".into(), 100, 5, "Cat".into(), None).unwrap();
+            let oid = c._crear_orden(accounts.alice, 1, 1).unwrap();
+
+            c._marcar_enviada(accounts.bob, oid, None).unwrap();
+            c._marcar_recibida(accounts.alice, oid).unwrap();
+
+            // No se puede retroceder de Recibido a Enviado
+            assert_eq!(
+                c._marcar_enviada(accounts.bob, oid, None),
+                Err(ContractError::EstadoInvalido)
+            );
+        }
+
+        #[ink::test]
+        fn no_se_puede_retroceder_de_recibido_a_pendiente() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            c._registrar_usuario(accounts.alice, Roles::Comprador).unwrap();
+            c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
+            c._publicar_producto(accounts.bob, "Producto".into(), "Desc".into(), 100, 5, "Cat".into(), None).unwrap();
+            let oid = c._crear_orden(accounts.alice, 1, 1).unwrap();
+
+            c._marcar_enviada(accounts.bob, oid, None).unwrap();
+            c._marcar_recibida(accounts.alice, oid).unwrap();
+
+            // Verificar que el estado es Recibido
+            let orden = c.ordenes.get(oid).unwrap();
+            assert_eq!(orden.estado, EstadoOrden::Recibido);
+        }
+
+        #[ink::test]
+        fn cancelacion_devuelve_stock() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            c._registrar_usuario(accounts.alice, Roles::Comprador).unwrap();
+            c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
+            
+            // Publicar producto con stock 5
+            let pid = c._publicar_producto(
+                accounts.bob, 
+                "Producto".into(), 
+                "Desc".into(), 
+                100, 
+                5, 
+                "Cat".into(),
+                None
+            ).unwrap();
+            
+            // Verificar stock inicial
+            let producto_antes = c.productos.get(pid).unwrap();
+            assert_eq!(producto_antes.cantidad, 5);
+
+            // Crear orden de 2 unidades
+            let oid = c._crear_orden(accounts.alice, pid, 2).unwrap();
+            
+            // Verificar que el stock se redujo
+            let producto_despues = c.productos.get(pid).unwrap();
+            assert_eq!(producto_despues.cantidad, 3);
+
+            // Cancelar la orden
+            c._solicitar_cancel_comprador(accounts.alice, oid).unwrap();
+            c._aceptar_cancel_vendedor(accounts.bob, oid).unwrap();
+
+            // Verificar que el stock se devolvió
+            let producto_final = c.productos.get(pid).unwrap();
+            assert_eq!(producto_final.cantidad, 5);
+        }
+
+        #[ink::test]
+        fn obtener_estado_orden_funciona() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            c._registrar_usuario(accounts.alice, Roles::Comprador).unwrap();
+            c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
+            c._publicar_producto(accounts.bob, "Producto".into(), "Desc".into(), 100, 5, "Cat".into(), None).unwrap();
+            
+            let oid = c._crear_orden(accounts.alice, 1, 1).unwrap();
+            
+            // Estado inicial: Pendiente
+            assert_eq!(c.obtener_estado_orden(oid), Some(EstadoOrden::Pendiente));
+            
+            // Estado después de marcar como enviada: Enviado
+            c._marcar_enviada(accounts.bob, oid, None).unwrap();
+            assert_eq!(c.obtener_estado_orden(oid), Some(EstadoOrden::Enviado));
+            
+            // Estado después de marcar como recibida: Recibido
+            c._marcar_recibida(accounts.alice, oid).unwrap();
+            assert_eq!(c.obtener_estado_orden(oid), Some(EstadoOrden::Recibido));
+        }
+
+        #[ink::test]
+        fn obtener_estado_orden_inexistente() {
+            let c = init_contract();
+            assert_eq!(c.obtener_estado_orden(999), None);
+        }
+
+        // ===== Tests de sistema de reputación =====
+
+        #[ink::test]
+        fn comprador_califica_vendedor_funciona() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            
+            c._registrar_usuario(accounts.alice, Roles::Comprador).unwrap();
+            c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
+            
+            let pid = c._publicar_producto(
+                accounts.bob,
+                "Producto".into(),
+                "Desc".into(),
+                100,
+                5,
+                "Cat".into(),
+                None
+            ).unwrap();
+            
+            let oid = c._crear_orden(accounts.alice, pid, 1).unwrap();
+            c._marcar_enviada(accounts.bob, oid, None).unwrap();
+            c._marcar_recibida(accounts.alice, oid).unwrap();
+            
+            // Comprador califica al vendedor
+            assert_eq!(c.comprador_califica_vendedor(oid, 5, None), Ok(()));
+            
+            // Verificar que la calificación se guardó
+            let calificaciones = c.obtener_calificaciones_orden(oid).unwrap();
+            assert_eq!(calificaciones.calificacion_comprador, Some(5));
+            assert_eq!(calificaciones.calificacion_vendedor, None);
+            
+            // Verificar reputación del vendedor
+            let reputacion = c.obtener_reputacion(accounts.bob).unwrap();
+            assert_eq!(reputacion.promedio_vendedor(), Some(5));
+        }
+
+        #[ink::test]
+        fn vendedor_califica_comprador_funciona() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            
+            c._registrar_usuario(accounts.alice, Roles::Comprador).unwrap();
+            c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
+            
+            let pid = c._publicar_producto(
+                accounts.bob,
+                "Producto".into(),
+                "Desc".into(),
+                100,
+                5,
+                "Cat".into(),
+                None
+            ).unwrap();
+            
+            let oid = c._crear_orden(accounts.alice, pid, 1).unwrap();
+            c._marcar_enviada(accounts.bob, oid, None).unwrap();
+            c._marcar_recibida(accounts.alice, oid).unwrap();
+            
+            // Vendedor califica al comprador
+            assert_eq!(c.vendedor_califica_comprador(oid, 4, None), Ok(()));
+            
+            // Verificar que la calificación se guardó
+            let calificaciones = c.obtener_calificaciones_orden(oid).unwrap();
+            assert_eq!(calificaciones.calificacion_comprador, None);
+            assert_eq!(calificaciones.calificacion_vendedor, Some(4));
+            
+            // Verificar reputación del comprador
+            let reputacion = c.obtener_reputacion(accounts.alice).unwrap();
+            assert_eq!(reputacion.promedio_comprador(), Some(4));
+        }
+
+        #[ink::test]
+        fn no_se_puede_calificar_si_orden_no_recibida() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            
+            c._registrar_usuario(accounts.alice, Roles::Comprador).unwrap();
+            c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
+            
+            let pid = c._publicar_producto(
+                accounts.bob,
+                "Producto".into(),
+                "Desc".into(),
+                100,
+                5,
+                "Cat".into(),
+                None
+            ).unwrap();
+            
+            let oid = c._crear_orden(accounts.alice, pid, 1).unwrap();
+            
+            // No se puede calificar si la orden está pendiente
+            assert_eq!(
+                c.comprador_califica_vendedor(oid, 5, None),
+                Err(ContractError::OrdenNoRecibida)
+            );
+        }
+
+        #[ink::test]
+        fn no_se_puede_calificar_dos_veces() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            
+            c._registrar_usuario(accounts.alice, Roles::Comprador).unwrap();
+            c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
+            
+            let pid = c._publicar_producto(
+                accounts.bob,
+                "Producto".into(),
+                "Desc".into(),
+                100,
+                5,
+                "Cat".into(),
+                None
+            ).unwrap();
+            
+            let oid = c._crear_orden(accounts.alice, pid, 1).unwrap();
+            c._marcar_enviada(accounts.bob, oid, None).unwrap();
+            c._marcar_recibida(accounts.alice, oid).unwrap();
+            
+            // Primera calificación OK
+            assert_eq!(c.comprador_califica_vendedor(oid, 5, None), Ok(()));
+            
+            // Segunda calificación debe fallar
+            assert_eq!(
+                c.comprador_califica_vendedor(oid, 4, None),
+                Err(ContractError::YaCalificado)
+            );
+        }
+
+        #[ink::test]
+        fn calificacion_invalida_fuera_de_rango() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            
+            c._registrar_usuario(accounts.alice, Roles::Comprador).unwrap();
+            c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
+            
+            let pid = c._publicar_producto(
+                accounts.bob,
+                "Producto".into(),
+                "Desc".into(),
+                100,
+                5,
+                "Cat".into(),
+                None
+            ).unwrap();
+            
+            let oid = c._crear_orden(accounts.alice, pid, 1).unwrap();
+            c._marcar_enviada(accounts.bob, oid, None).unwrap();
+            c._marcar_recibida(accounts.alice, oid).unwrap();
+            
+            // Calificación 0 (inválida)
+            assert_eq!(
+                c.comprador_califica_vendedor(oid, 0, None),
+                Err(ContractError::CalificacionInvalida)
+            );
+            
+            // Calificación 6 (inválida)
+            assert_eq!(
+                c.comprador_califica_vendedor(oid, 6, None),
+                Err(ContractError::CalificacionInvalida)
+            );
+        }
+
+        #[ink::test]
+        fn reputacion_acumulada_multiple_calificaciones() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            
+            c._registrar_usuario(accounts.alice, Roles::Comprador).unwrap();
+            c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
+            c._registrar_usuario(accounts.charlie, Roles::Comprador).unwrap();
+            
+            // Primera orden
+            let pid1 = c._publicar_producto(
+                accounts.bob,
+                "Producto1".into(),
+                "Desc".into(),
+                100,
+                10,
+                "Cat1".into(),
+                None
+            ).unwrap();
+            let oid1 = c._crear_orden(accounts.alice, pid1, 1).unwrap();
+            c._marcar_enviada(accounts.bob, oid1, None).unwrap();
+            c._marcar_recibida(accounts.alice, oid1).unwrap();
+            c.comprador_califica_vendedor(oid1, 5, None).unwrap();
+            
+            // Segunda orden
+            let pid2 = c._publicar_producto(
+                accounts.bob,
+                "Producto2".into(),
+                "Desc".into(),
+                100,
+                10,
+                "Cat1".into(),
+                None
+            ).unwrap();
+            let oid2 = c._crear_orden(accounts.charlie, pid2, 1).unwrap();
+            c._marcar_enviada(accounts.bob, oid2, None).unwrap();
+            c._marcar_recibida(accounts.charlie, oid2).unwrap();
+            c.comprador_califica_vendedor(oid2, 3, None).unwrap();
+            
+            // Verificar promedio: (5 + 3) / 2 = 4
+            let reputacion = c.obtener_reputacion(accounts.bob).unwrap();
+            assert_eq!(reputacion.promedio_vendedor(), Some(4));
+            assert_eq!(reputacion.total_calificaciones_vendedor, 2);
+        }
+
+        #[ink::test]
+        fn obtener_reputacion_como_comprador_y_vendedor() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            
+            c._registrar_usuario(accounts.alice, Roles::Ambos).unwrap();
+            c._registrar_usuario(accounts.bob, Roles::Ambos).unwrap();
+            
+            // Alice como vendedor
+            let pid = c._publicar_producto(
+                accounts.alice,
+                "Producto".into(),
+                "Desc".into(),
+                100,
+                5,
+                "Cat".into(),
+                None
+            ).unwrap();
+            
+            let oid = c._crear_orden(accounts.bob, pid, 1).unwrap();
+            c._marcar_enviada(accounts.alice, oid, None).unwrap();
+            c._marcar_recibida(accounts.bob, oid).unwrap();
+            
+            c.comprador_califica_vendedor(oid, 5, None).unwrap();
+            c.vendedor_califica_comprador(oid, 4, None).unwrap();
+            
+            // Alice tiene reputación como vendedor
+            let reputacion_alice = c.obtener_reputacion(accounts.alice).unwrap();
+            assert_eq!(reputacion_alice.promedio_vendedor(), Some(5));
+            
+            // Bob tiene reputación como comprador
+            let reputacion_bob = c.obtener_reputacion(accounts.bob).unwrap();
+            assert_eq!(reputacion_bob.promedio_comprador(), Some(4));
+        }
+
+        #[ink::test]
+        fn ventas_por_producto_se_registran() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            
+            c._registrar_usuario(accounts.alice, Roles::Comprador).unwrap();
+            c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
+            
+            let pid = c._publicar_producto(
+                accounts.bob,
+                "Producto".into(),
+                "Desc".into(),
+                100,
+                10,
+                "Cat".into(),
+                None
+            ).unwrap();
+            
+            // Antes de recibir, no hay ventas registradas
+            assert_eq!(c.obtener_ventas_producto(pid), 0);
+            
+            let oid = c._crear_orden(accounts.alice, pid, 1).unwrap();
+            c._marcar_enviada(accounts.bob, oid, None).unwrap();
+            
+            // Todavía no hay ventas (orden no recibida)
+            assert_eq!(c.obtener_ventas_producto(pid), 0);
+            
+            c._marcar_recibida(accounts.alice, oid).unwrap();
+            
+            // Ahora sí hay una venta registrada
+            assert_eq!(c.obtener_ventas_producto(pid), 1);
+        }
+
+        #[ink::test]
+        fn estadisticas_por_categoria() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            
+            c._registrar_usuario(accounts.alice, Roles::Comprador).unwrap();
+            c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
+            
+            let pid = c._publicar_producto(
+                accounts.bob,
+                "Producto".into(),
+                "Desc".into(),
+                100,
+                5,
+                "Electronica".into(),
+                None
+            ).unwrap();
+            
+            let oid = c._crear_orden(accounts.alice, pid, 1).unwrap();
+            c._marcar_enviada(accounts.bob, oid, None).unwrap();
+            c._marcar_recibida(accounts.alice, oid).unwrap();
+            
+            c.comprador_califica_vendedor(oid, 5, None).unwrap();
+            
+            // Verificar estadísticas de la categoría
+            let stats = c.obtener_estadisticas_categoria("Electronica".into()).unwrap();
+            assert_eq!(stats.0, 1); // total_ventas
+            assert_eq!(stats.1, 5); // suma_calificaciones
+            assert_eq!(stats.2, 1); // cantidad_calificaciones
+        }
+
+        #[ink::test]
+        fn usuarios_recientes_devuelve_los_ultimos_en_orden() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            c._registrar_usuario(accounts.alice, Roles::Comprador).unwrap();
+            c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
+            c._registrar_usuario(accounts.charlie, Roles::Ambos).unwrap();
+            c._registrar_usuario(accounts.django, Roles::Comprador).unwrap();
+
+            let recientes = c.usuarios_recientes(2);
+            assert_eq!(
+                recientes,
+                vec![(accounts.django, Roles::Comprador), (accounts.charlie, Roles::Ambos)]
+            );
+
+            // Pedir más de los que hay devuelve todos
+            assert_eq!(c.usuarios_recientes(10).len(), 4);
+            assert_eq!(c.usuarios_recientes(0).len(), 0);
+        }
+
+        #[ink::test]
+        fn gmv_en_rango_suma_solo_ordenes_recibidas_en_la_ventana() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            c._registrar_usuario(accounts.alice, Roles::Comprador).unwrap();
+            c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
+            let pid = c._publicar_producto(accounts.bob, "Producto".into(), "Desc".into(), 100, 10, "Cat".into(), None).unwrap();
+
+            // Orden recibida en t = 1000 (fuera de la ventana)
+            let oid1 = c._crear_orden(accounts.alice, pid, 1).unwrap();
+            c._marcar_enviada(accounts.bob, oid1, None).unwrap();
+            test::set_block_timestamp::<DefaultEnvironment>(1000);
+            c._marcar_recibida(accounts.alice, oid1).unwrap();
+
+            // Orden recibida en t = 5000 (dentro de la ventana)
+            let oid2 = c._crear_orden(accounts.alice, pid, 3).unwrap();
+            c._marcar_enviada(accounts.bob, oid2, None).unwrap();
+            test::set_block_timestamp::<DefaultEnvironment>(5000);
+            c._marcar_recibida(accounts.alice, oid2).unwrap();
+
+            // Orden enviada pero no recibida (no cuenta)
+            let oid3 = c._crear_orden(accounts.alice, pid, 2).unwrap();
+            c._marcar_enviada(accounts.bob, oid3, None).unwrap();
+
+            assert_eq!(c.gmv_en_rango(2000, 6000, 0, 10), 300);
+            assert_eq!(c.gmv_en_rango(0, 6000, 0, 10), 400);
+            assert_eq!(c.gmv_en_rango(6000, 9000, 0, 10), 0);
+
+            // Las páginas se suman: la primera orden sola y el resto
+            assert_eq!(c.gmv_en_rango(0, 6000, 1, 1), 100);
+            assert_eq!(c.gmv_en_rango(0, 6000, 2, 10), 300);
+
+            // Cuenta el precio al que se compró, no el actual
+            c._actualizar_precio_producto(accounts.bob, pid, 1_000).unwrap();
+            assert_eq!(c.gmv_en_rango(0, 6000, 0, 10), 400);
+        }
+
+        #[ink::test]
+        fn solo_owner_puede_cambiar_modo_alto_riesgo() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            // El owner es quien despliega (alice por defecto)
+            assert_eq!(
+                c._establecer_modo_alto_riesgo(accounts.bob, true),
+                Err(ContractError::NoAutorizado)
+            );
+            assert_eq!(c._verificar_usuario(accounts.bob, accounts.bob, true), Err(ContractError::NoAutorizado));
+            assert_eq!(c._establecer_modo_alto_riesgo(accounts.alice, true), Ok(()));
+            assert!(c.modo_alto_riesgo());
+            assert_eq!(c._establecer_modo_alto_riesgo(accounts.alice, false), Ok(()));
+            assert!(!c.modo_alto_riesgo());
+        }
+
+        #[ink::test]
+        fn modo_alto_riesgo_bloquea_compradores_no_verificados() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            c._registrar_usuario(accounts.charlie, Roles::Comprador).unwrap();
+            c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
+            let pid = c._publicar_producto(accounts.bob, "Producto".into(), "Desc".into(), 100, 5, "Cat".into(), None).unwrap();
+
+            c._establecer_modo_alto_riesgo(accounts.alice, true).unwrap();
+            assert_eq!(c._crear_orden(accounts.charlie, pid, 1), Err(ContractError::KycRequerido));
+
+            // Una vez verificado por el owner puede comprar
+            c._verificar_usuario(accounts.alice, accounts.charlie, true).unwrap();
+            assert!(c.esta_verificado(accounts.charlie));
+            assert_eq!(c._crear_orden(accounts.charlie, pid, 1), Ok(1));
+
+            // Al desactivar el modo, un no verificado vuelve a poder comprar
+            c._verificar_usuario(accounts.alice, accounts.charlie, false).unwrap();
+            assert_eq!(c._crear_orden(accounts.charlie, pid, 1), Err(ContractError::KycRequerido));
+            c._establecer_modo_alto_riesgo(accounts.alice, false).unwrap();
+            assert_eq!(c._crear_orden(accounts.charlie, pid, 1), Ok(2));
+        }
+
+        #[ink::test]
+        fn reputacion_detallada_incluye_distribuciones() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            c._registrar_usuario(accounts.alice, Roles::Ambos).unwrap();
+            c._registrar_usuario(accounts.bob, Roles::Ambos).unwrap();
+
+            assert_eq!(c.reputacion_detallada(accounts.alice), None);
+
+            // Alice vende a Bob dos veces y Bob le vende a Alice una vez
+            let pid_alice = c._publicar_producto(accounts.alice, "A".into(), "Desc".into(), 10, 5, "Cat".into(), None).unwrap();
+            let pid_bob = c._publicar_producto(accounts.bob, "B".into(), "Desc".into(), 10, 5, "Cat".into(), None).unwrap();
+            for puntaje in [5u8, 3u8] {
+                let oid = c._crear_orden(accounts.bob, pid_alice, 1).unwrap();
+                c._marcar_enviada(accounts.alice, oid, None).unwrap();
+                c._marcar_recibida(accounts.bob, oid).unwrap();
+                c._calificar_vendedor(accounts.bob, oid, puntaje, None).unwrap();
+            }
+            let oid = c._crear_orden(accounts.alice, pid_bob, 1).unwrap();
+            c._marcar_enviada(accounts.bob, oid, None).unwrap();
+            c._marcar_recibida(accounts.alice, oid).unwrap();
+            c._calificar_comprador(accounts.bob, oid, 1, None).unwrap();
+
+            let detalle = c.reputacion_detallada(accounts.alice).unwrap();
+            assert_eq!(detalle.distribucion_vendedor, [0, 0, 1, 0, 1]);
+            assert_eq!(detalle.distribucion_comprador, [1, 0, 0, 0, 0]);
+            assert_eq!(detalle.data.total_calificaciones_vendedor, 2);
+            assert_eq!(detalle.data.suma_calificaciones_vendedor, 8);
+            assert_eq!(detalle.data.total_calificaciones_comprador, 1);
+        }
+
+        #[ink::test]
+        fn vendedor_cancela_todo_pendiente_solo_cancela_pendientes() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            c._registrar_usuario(accounts.alice, Roles::Comprador).unwrap();
+            c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
+            let pid = c._publicar_producto(accounts.bob, "Producto".into(), "Desc".into(), 100, 10, "Cat".into(), None).unwrap();
+
+            let oid1 = c._crear_orden(accounts.alice, pid, 2).unwrap();
+            let oid2 = c._crear_orden(accounts.alice, pid, 3).unwrap();
+            let oid3 = c._crear_orden(accounts.alice, pid, 1).unwrap();
+            c._marcar_enviada(accounts.bob, oid2, None).unwrap();
+            assert_eq!(c.productos.get(pid).unwrap().cantidad, 4);
+
+            assert_eq!(c._cancelar_pendientes_vendedor(accounts.bob), Ok(2));
+
+            assert_eq!(c.obtener_estado_orden(oid1), Some(EstadoOrden::Cancelada));
+            assert_eq!(c.obtener_estado_orden(oid2), Some(EstadoOrden::Enviado));
+            assert_eq!(c.obtener_estado_orden(oid3), Some(EstadoOrden::Cancelada));
+            // Se devuelve solo el stock de las órdenes pendientes (2 + 1)
+            assert_eq!(c.productos.get(pid).unwrap().cantidad, 7);
+
+            // Una segunda llamada no encuentra nada pendiente
+            assert_eq!(c._cancelar_pendientes_vendedor(accounts.bob), Ok(0));
+        }
+
+        #[ink::test]
+        fn mi_ultima_orden_devuelve_la_mas_reciente() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            c._registrar_usuario(accounts.alice, Roles::Comprador).unwrap();
+            c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
+            let pid = c._publicar_producto(accounts.bob, "Producto".into(), "Desc".into(), 100, 10, "Cat".into(), None).unwrap();
+
+            test::set_caller::<DefaultEnvironment>(accounts.alice);
+            assert_eq!(c.mi_ultima_orden(), None);
+
+            c._crear_orden(accounts.alice, pid, 1).unwrap();
+            let oid2 = c._crear_orden(accounts.alice, pid, 4).unwrap();
+
+            let (id, orden) = c.mi_ultima_orden().unwrap();
+            assert_eq!(id, oid2);
+            assert_eq!(orden.cantidad, 4);
+        }
+
+        #[ink::test]
+        fn ciclo_de_vida_de_orden_emite_eventos() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            c._registrar_usuario(accounts.alice, Roles::Comprador).unwrap();
+            c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
+            let pid = c._publicar_producto(accounts.bob, "Producto".into(), "Desc".into(), 100, 10, "Cat".into(), None).unwrap();
+            let eventos_iniciales = test::recorded_events().count();
+
+            // Creada (con su cambio de stock), enviada y recibida
+            let oid = c._crear_orden(accounts.alice, pid, 1).unwrap();
+            c._marcar_enviada(accounts.bob, oid, None).unwrap();
+            c._marcar_recibida(accounts.alice, oid).unwrap();
+            assert_eq!(test::recorded_events().count(), eventos_iniciales + 4);
+
+            // Creada y cancelada (la solicitud unilateral no emite evento)
+            let oid2 = c._crear_orden(accounts.alice, pid, 1).unwrap();
+            c._solicitar_cancel_comprador(accounts.alice, oid2).unwrap();
+            assert_eq!(test::recorded_events().count(), eventos_iniciales + 6);
+            // La cancelación devuelve stock y emite OrdenCancelada
+            c._aceptar_cancel_vendedor(accounts.bob, oid2).unwrap();
+            assert_eq!(test::recorded_events().count(), eventos_iniciales + 8);
+
+            // Los eventos de órdenes llevan comprador y vendedor como topics
+            let ultimo = test::recorded_events().last().unwrap();
+            assert_eq!(ultimo.topics.len(), 3);
+        }
+
+        #[ink::test]
+        fn rotacion_producto_usa_unidades_vendidas_y_stock() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            c._registrar_usuario(accounts.alice, Roles::Comprador).unwrap();
+            c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
+            let pid = c._publicar_producto(accounts.bob, "Producto".into(), "Desc".into(), 100, 10, "Cat".into(), None).unwrap();
+
+            assert_eq!(c.rotacion_producto(pid), Some(0));
+            assert_eq!(c.rotacion_producto(999), None);
+
+            // Se venden 6 unidades y quedan 4 en stock: 6 / 4 = 1,5
+            let oid = c._crear_orden(accounts.alice, pid, 6).unwrap();
+            c._marcar_enviada(accounts.bob, oid, None).unwrap();
+            c._marcar_recibida(accounts.alice, oid).unwrap();
+            assert_eq!(c.rotacion_producto(pid), Some(150));
+
+            // Sin stock la rotación queda indefinida
+            c._crear_orden(accounts.alice, pid, 4).unwrap();
+            assert_eq!(c.rotacion_producto(pid), None);
+        }
+
+        #[ink::test]
+        fn publicar_producto_y_cambios_de_stock_emiten_eventos() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            c._registrar_usuario(accounts.alice, Roles::Comprador).unwrap();
+            c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
+            assert_eq!(test::recorded_events().count(), 0);
+
+            let pid = c._publicar_producto(accounts.bob, "Producto".into(), "Desc".into(), 100, 10, "Cat".into(), None).unwrap();
+            assert_eq!(test::recorded_events().count(), 1);
+            // ProductoPublicado lleva la categoría como topic
+            assert_eq!(test::recorded_events().last().unwrap().topics.len(), 2);
+
+            // El stock vuelve a emitir evento al devolverse
+            c._devolver_stock(pid, 3).unwrap();
+            assert_eq!(test::recorded_events().count(), 2);
+            assert_eq!(c.productos.get(pid).unwrap().cantidad, 13);
+        }
+
+        #[ink::test]
+        fn detalle_cancelacion_refleja_solicitud_unilateral() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            c._registrar_usuario(accounts.alice, Roles::Comprador).unwrap();
+            c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
+            let pid = c._publicar_producto(accounts.bob, "Producto".into(), "Desc".into(), 100, 5, "Cat".into(), None).unwrap();
+            let oid = c._crear_orden(accounts.alice, pid, 1).unwrap();
+
+            assert_eq!(c.detalle_cancelacion(999), None);
+            assert_eq!(
+                c.detalle_cancelacion(oid),
+                Some((EstadoOrden::Pendiente, false, false, true))
+            );
+
+            c._solicitar_cancel_comprador(accounts.alice, oid).unwrap();
+            assert_eq!(
+                c.detalle_cancelacion(oid),
+                Some((EstadoOrden::Pendiente, true, false, true))
+            );
+
+            c._aceptar_cancel_vendedor(accounts.bob, oid).unwrap();
+            assert_eq!(
+                c.detalle_cancelacion(oid),
+                Some((EstadoOrden::Cancelada, true, true, false))
+            );
+        }
+
+        #[ink::test]
+        fn calificar_emite_evento_calificacion_registrada() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            c._registrar_usuario(accounts.alice, Roles::Comprador).unwrap();
+            c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
+            let pid = c._publicar_producto(accounts.bob, "Producto".into(), "Desc".into(), 100, 5, "Cat".into(), None).unwrap();
+            let oid = c._crear_orden(accounts.alice, pid, 1).unwrap();
+            c._marcar_enviada(accounts.bob, oid, None).unwrap();
+            c._marcar_recibida(accounts.alice, oid).unwrap();
+            let eventos = test::recorded_events().count();
+
+            c._calificar_vendedor(accounts.alice, oid, 4, None).unwrap();
+            assert_eq!(test::recorded_events().count(), eventos + 1);
+            c._calificar_comprador(accounts.bob, oid, 5, None).unwrap();
+            assert_eq!(test::recorded_events().count(), eventos + 2);
+
+            // Una calificación rechazada no emite evento
+            assert_eq!(c._calificar_vendedor(accounts.alice, oid, 3, None), Err(ContractError::YaCalificado));
+            assert_eq!(test::recorded_events().count(), eventos + 2);
+        }
+
+        #[ink::test]
+        fn actualizar_precio_producto_funciona_y_conserva_ventas() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            c._registrar_usuario(accounts.alice, Roles::Comprador).unwrap();
+            c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
+            let pid = c._publicar_producto(accounts.bob, "Producto".into(), "Desc".into(), 100, 5, "Cat".into(), None).unwrap();
+            let oid = c._crear_orden(accounts.alice, pid, 1).unwrap();
+            c._marcar_enviada(accounts.bob, oid, None).unwrap();
+            c._marcar_recibida(accounts.alice, oid).unwrap();
+
+            assert_eq!(c._actualizar_precio_producto(accounts.bob, pid, 250), Ok(()));
+            assert_eq!(c.productos.get(pid).unwrap().precio, 250);
+            assert_eq!(c.obtener_ventas_producto(pid), 1);
+        }
+
+        #[ink::test]
+        fn actualizar_precio_producto_valida_datos_y_permisos() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
+            let pid = c._publicar_producto(accounts.bob, "Producto".into(), "Desc".into(), 100, 5, "Cat".into(), None).unwrap();
+
+            assert_eq!(c._actualizar_precio_producto(accounts.charlie, pid, 50), Err(ContractError::NoAutorizado));
+            assert_eq!(c._actualizar_precio_producto(accounts.bob, pid, 0), Err(ContractError::DatosInvalidos));
+            assert_eq!(c._actualizar_precio_producto(accounts.bob, 999, 50), Err(ContractError::ProductoNoEncontrado));
+            assert_eq!(c.productos.get(pid).unwrap().precio, 100);
+        }
+
+        #[ink::test]
+        fn abrir_disputa_valida_participantes_y_estado() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            c._registrar_usuario(accounts.alice, Roles::Comprador).unwrap();
+            c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
+            let pid = c._publicar_producto(accounts.bob, "Producto".into(), "Desc".into(), 100, 5, "Cat".into(), None).unwrap();
+            let oid = c._crear_orden(accounts.alice, pid, 1).unwrap();
+
+            assert_eq!(c._abrir_disputa(accounts.charlie, oid), Err(ContractError::NoAutorizado));
+            assert_eq!(c._abrir_disputa(accounts.bob, oid), Ok(()));
+            assert_eq!(c.obtener_estado_orden(oid), Some(EstadoOrden::Disputado));
+            // No se puede abrir dos veces ni avanzar la orden mientras está en disputa
+            assert_eq!(c._abrir_disputa(accounts.alice, oid), Err(ContractError::EstadoInvalido));
+            assert_eq!(c._marcar_enviada(accounts.bob, oid, None), Err(ContractError::EstadoInvalido));
+        }
+
+        #[ink::test]
+        fn resolver_disputa_timelock_respeta_el_plazo() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            c._registrar_usuario(accounts.alice, Roles::Comprador).unwrap();
+            c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
+            let pid = c._publicar_producto(accounts.bob, "Producto".into(), "Desc".into(), 100, 5, "Cat".into(), None).unwrap();
+            let oid = c._crear_orden(accounts.alice, pid, 2).unwrap();
+
+            // Sin disputa abierta no se puede resolver
+            assert_eq!(c._resolver_disputa_timelock(oid), Err(ContractError::DisputaNoAbierta));
+
+            test::set_block_timestamp::<DefaultEnvironment>(1_000);
+            c._abrir_disputa(accounts.alice, oid).unwrap();
+
+            // Justo en el límite todavía no se alcanzó el plazo
+            test::set_block_timestamp::<DefaultEnvironment>(1_000 + PLAZO_RESOLUCION_DISPUTA);
+            assert_eq!(c._resolver_disputa_timelock(oid), Err(ContractError::TimeoutNoAlcanzado));
+            assert_eq!(c.obtener_estado_orden(oid), Some(EstadoOrden::Disputado));
+
+            // Pasado el plazo, cualquiera puede resolver a favor del comprador
+            test::set_block_timestamp::<DefaultEnvironment>(1_001 + PLAZO_RESOLUCION_DISPUTA);
+            test::set_caller::<DefaultEnvironment>(accounts.charlie);
+            assert_eq!(c.resolver_disputa_timelock(oid), Ok(()));
+            assert_eq!(c.obtener_estado_orden(oid), Some(EstadoOrden::Cancelada));
+            assert_eq!(c.productos.get(pid).unwrap().cantidad, 5);
+        }
+
+        #[ink::test]
+        fn productos_por_categorias_filtra_y_pagina() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
+            c._publicar_producto(accounts.bob, "A".into(), "Desc".into(), 1, 1, "Ropa".into(), None).unwrap();
+            c._publicar_producto(accounts.bob, "B".into(), "Desc".into(), 1, 1, "Libros".into(), None).unwrap();
+            c._publicar_producto(accounts.bob, "C".into(), "Desc".into(), 1, 1, "Hogar".into(), None).unwrap();
+            c._publicar_producto(accounts.bob, "D".into(), "Desc".into(), 1, 1, "Ropa".into(), None).unwrap();
+
+            let filtro: Vec<String> = vec!["Ropa".into(), "Hogar".into()];
+            let ids: Vec<u128> = c._productos_por_categorias(filtro.clone(), 0, 10)
+                .into_iter()
+                .map(|(id, _)| id)
+                .collect();
+            assert_eq!(ids, vec![1, 3, 4]);
+
+            // Paginación: dos resultados a partir del ID 2
+            let ids: Vec<u128> = c._productos_por_categorias(filtro, 2, 2)
+                .into_iter()
+                .map(|(id, _)| id)
+                .collect();
+            assert_eq!(ids, vec![3, 4]);
+        }
+
+        #[ink::test]
+        fn reponer_stock_funciona_y_valida() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
+            let pid = c._publicar_producto(accounts.bob, "Producto".into(), "Desc".into(), 100, 5, "Cat".into(), None).unwrap();
+
+            assert_eq!(c._reponer_stock(accounts.bob, pid, 10), Ok(()));
+            assert_eq!(c.productos.get(pid).unwrap().cantidad, 15);
+
+            assert_eq!(c._reponer_stock(accounts.charlie, pid, 1), Err(ContractError::NoAutorizado));
+            assert_eq!(c._reponer_stock(accounts.bob, pid, 0), Err(ContractError::DatosInvalidos));
+            assert_eq!(c._reponer_stock(accounts.bob, 999, 1), Err(ContractError::ProductoNoEncontrado));
+            assert_eq!(c._reponer_stock(accounts.bob, pid, u32::MAX), Err(ContractError::Overflow));
+            assert_eq!(c.productos.get(pid).unwrap().cantidad, 15);
+        }
+
+        #[ink::test]
+        fn liabilidad_reembolsos_sigue_el_ciclo_de_las_ordenes() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            c._registrar_usuario(accounts.alice, Roles::Comprador).unwrap();
+            c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
+            let pid = c._publicar_producto(accounts.bob, "Producto".into(), "Desc".into(), 100, 10, "Cat".into(), None).unwrap();
+            assert_eq!(c.liabilidad_reembolsos(), 0);
+
+            let oid1 = c._crear_orden(accounts.alice, pid, 2).unwrap();
+            let oid2 = c._crear_orden(accounts.alice, pid, 1).unwrap();
+            assert_eq!(c.liabilidad_reembolsos(), 300);
+
+            // Una disputa abierta sigue siendo reembolsable
+            c._abrir_disputa(accounts.alice, oid1).unwrap();
+            assert_eq!(c.liabilidad_reembolsos(), 300);
+
+            // Al resolverse la disputa con reembolso, deja de contar
+            test::set_block_timestamp::<DefaultEnvironment>(PLAZO_RESOLUCION_DISPUTA + 1);
+            c._resolver_disputa_timelock(oid1).unwrap();
+            assert_eq!(c.liabilidad_reembolsos(), 100);
+
+            // Una orden recibida tampoco es reembolsable
+            c._marcar_enviada(accounts.bob, oid2, None).unwrap();
+            c._marcar_recibida(accounts.alice, oid2).unwrap();
+            assert_eq!(c.liabilidad_reembolsos(), 0);
+        }
+
+        #[ink::test]
+        fn desactivar_producto_bloquea_ordenes_y_lo_oculta_del_catalogo() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            c._registrar_usuario(accounts.alice, Roles::Comprador).unwrap();
+            c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
+            let pid = c._publicar_producto(accounts.bob, "Producto".into(), "Desc".into(), 100, 5, "Cat".into(), None).unwrap();
+            c._publicar_producto(accounts.bob, "Otro".into(), "Desc".into(), 100, 5, "Cat".into(), None).unwrap();
+
+            assert_eq!(c._cambiar_estado_producto(accounts.charlie, pid, false), Err(ContractError::NoAutorizado));
+            assert_eq!(c._cambiar_estado_producto(accounts.bob, pid, false), Ok(()));
+
+            assert_eq!(c._crear_orden(accounts.alice, pid, 1), Err(ContractError::ProductoInactivo));
+            assert_eq!(c._ver_todos_los_productos().len(), 1);
+            // El vendedor sigue viendo el producto inactivo
+            assert_eq!(c._ver_mis_productos(accounts.bob).len(), 2);
+
+            assert_eq!(c._cambiar_estado_producto(accounts.bob, pid, true), Ok(()));
+            assert_eq!(c._crear_orden(accounts.alice, pid, 1), Ok(1));
+        }
+
+        #[ink::test]
+        fn reactivar_producto_sin_stock_falla() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            c._registrar_usuario(accounts.alice, Roles::Comprador).unwrap();
+            c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
+            let pid = c._publicar_producto(accounts.bob, "Producto".into(), "Desc".into(), 100, 1, "Cat".into(), None).unwrap();
+            c._crear_orden(accounts.alice, pid, 1).unwrap();
+
+            test::set_caller::<DefaultEnvironment>(accounts.bob);
+            assert_eq!(c.desactivar_producto(pid), Ok(()));
+            assert_eq!(c.reactivar_producto(pid), Err(ContractError::StockInsuficiente));
+        }
+
+        #[ink::test]
+        fn retirar_comisiones_solo_owner_y_una_vez() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            // Cuenta del contrato con fondos propios
+            test::set_callee::<DefaultEnvironment>(accounts.frank);
+            test::set_account_balance::<DefaultEnvironment>(accounts.frank, 1_000_000);
+            c.fondos_acumulados = 500;
+
+            assert_eq!(c._retirar_comisiones(accounts.bob), Err(ContractError::NoAutorizado));
+
+            let saldo_owner = test::get_account_balance::<DefaultEnvironment>(accounts.alice).unwrap();
+            assert_eq!(c._retirar_comisiones(accounts.alice), Ok(500));
+            assert_eq!(
+                test::get_account_balance::<DefaultEnvironment>(accounts.alice).unwrap(),
+                saldo_owner + 500
+            );
+            assert_eq!(c.fondos_acumulados(), 0);
+
+            // Un segundo retiro no transfiere nada
+            assert_eq!(c._retirar_comisiones(accounts.alice), Ok(0));
+            assert_eq!(test::get_account_balance::<DefaultEnvironment>(accounts.frank).unwrap(), 999_500);
+        }
+
+        #[ink::test]
+        fn producto_tiene_ventas_solo_tras_orden_recibida() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            c._registrar_usuario(accounts.alice, Roles::Comprador).unwrap();
+            c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
+            let pid = c._publicar_producto(accounts.bob, "P".into(), "D".into(), 100, 5, "Cat".into(), None).unwrap();
+
+            assert!(!c.producto_tiene_ventas(pid));
+
+            let oid = c._crear_orden(accounts.alice, pid, 1).unwrap();
+            c._marcar_enviada(accounts.bob, oid, None).unwrap();
+            assert!(!c.producto_tiene_ventas(pid));
+
+            c._marcar_recibida(accounts.alice, oid).unwrap();
+            assert!(c.producto_tiene_ventas(pid));
+        }
+
+        #[ink::test]
+        fn mis_disputas_devuelve_solo_ordenes_disputadas() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            c._registrar_usuario(accounts.alice, Roles::Comprador).unwrap();
+            c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
+            let pid = c._publicar_producto(accounts.bob, "P".into(), "D".into(), 100, 10, "Cat".into(), None).unwrap();
+            let o1 = c._crear_orden(accounts.alice, pid, 1).unwrap();
+            let o2 = c._crear_orden(accounts.alice, pid, 1).unwrap();
+            c._marcar_enviada(accounts.bob, o1, None).unwrap();
+
+            assert!(c._disputas_comprador(accounts.alice).is_empty());
+
+            c._abrir_disputa(accounts.alice, o2).unwrap();
+            let disputas = c._disputas_comprador(accounts.alice);
+            assert_eq!(disputas.len(), 1);
+            assert_eq!(disputas[0].0, o2);
+            // El vendedor no tiene disputas como comprador
+            assert!(c._disputas_comprador(accounts.bob).is_empty());
+        }
+
+        #[ink::test]
+        fn ver_productos_paginado_recorre_en_orden_y_saltea_inactivos() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
+            for _ in 0..5 {
+                c._publicar_producto(accounts.bob, "P".into(), "D".into(), 10, 1, "Cat".into(), None).unwrap();
+            }
+            c._cambiar_estado_producto(accounts.bob, 2, false).unwrap();
+
+            let pagina1: Vec<u128> = c.ver_productos_paginado(0, 2).into_iter().map(|(id, _)| id).collect();
+            assert_eq!(pagina1, vec![1, 3]);
+            let pagina2: Vec<u128> = c.ver_productos_paginado(4, 2).into_iter().map(|(id, _)| id).collect();
+            assert_eq!(pagina2, vec![4, 5]);
+            assert!(c.ver_productos_paginado(6, 2).is_empty());
+            assert!(c.ver_productos_paginado(1, 0).is_empty());
+        }
+
+        #[ink::test]
+        fn reputacion_vendedor_en_categoria_producto_usa_la_categoria_del_producto() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            c._registrar_usuario(accounts.alice, Roles::Comprador).unwrap();
+            c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
+            let libros = c._publicar_producto(accounts.bob, "Libro".into(), "D".into(), 10, 5, "Libros".into(), None).unwrap();
+            let juegos = c._publicar_producto(accounts.bob, "Juego".into(), "D".into(), 10, 5, "Juegos".into(), None).unwrap();
+
+            assert_eq!(c.reputacion_vendedor_en_categoria_producto(libros), None);
+            assert_eq!(c.reputacion_vendedor_en_categoria_producto(99), None);
+
+            for puntaje in [5, 3] {
+                let oid = c._crear_orden(accounts.alice, libros, 1).unwrap();
+                c._marcar_enviada(accounts.bob, oid, None).unwrap();
+                c._marcar_recibida(accounts.alice, oid).unwrap();
+                c._calificar_vendedor(accounts.alice, oid, puntaje, None).unwrap();
+            }
+
+            assert_eq!(c.reputacion_vendedor_en_categoria_producto(libros), Some(4));
+            // Las calificaciones en Libros no cuentan para Juegos
+            assert_eq!(c.reputacion_vendedor_en_categoria_producto(juegos), None);
+        }
+
+        #[ink::test]
+        fn ver_productos_por_categoria_filtra_por_coincidencia_exacta() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
+            let a = c._publicar_producto(accounts.bob, "A".into(), "D".into(), 10, 1, "Hogar".into(), None).unwrap();
+            c._publicar_producto(accounts.bob, "B".into(), "D".into(), 10, 1, "hogar".into(), None).unwrap();
+            let d = c._publicar_producto(accounts.bob, "C".into(), "D".into(), 10, 1, "Hogar".into(), None).unwrap();
+
+            let ids: Vec<u128> = c.ver_productos_por_categoria("Hogar".into()).into_iter().map(|(id, _)| id).collect();
+            assert_eq!(ids, vec![a, d]);
+            assert!(c.ver_productos_por_categoria("Jardin".into()).is_empty());
+        }
+
+        #[ink::test]
+        fn actualizar_stocks_batch_aplica_todo_si_los_productos_son_propios() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
+            let p1 = c._publicar_producto(accounts.bob, "A".into(), "D".into(), 10, 1, "Cat".into(), None).unwrap();
+            let p2 = c._publicar_producto(accounts.bob, "B".into(), "D".into(), 10, 1, "Cat".into(), None).unwrap();
+
+            assert_eq!(c._actualizar_stocks_batch(accounts.bob, vec![(p1, 7), (p2, 0)]), Ok(()));
+            assert_eq!(c.productos.get(p1).unwrap().cantidad, 7);
+            assert_eq!(c.productos.get(p2).unwrap().cantidad, 0);
+
+            let demasiados = vec![(p1, 1); MAX_ACTUALIZACIONES_STOCK + 1];
+            assert_eq!(c._actualizar_stocks_batch(accounts.bob, demasiados), Err(ContractError::DatosInvalidos));
+        }
+
+        #[ink::test]
+        fn actualizar_stocks_batch_no_aplica_nada_si_hay_producto_ajeno() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
+            c._registrar_usuario(accounts.charlie, Roles::Vendedor).unwrap();
+            let propio = c._publicar_producto(accounts.bob, "A".into(), "D".into(), 10, 1, "Cat".into(), None).unwrap();
+            let ajeno = c._publicar_producto(accounts.charlie, "B".into(), "D".into(), 10, 1, "Cat".into(), None).unwrap();
+
+            assert_eq!(
+                c._actualizar_stocks_batch(accounts.bob, vec![(propio, 9), (ajeno, 9)]),
+                Err(ContractError::NoAutorizado)
+            );
+            assert_eq!(c.productos.get(propio).unwrap().cantidad, 1);
+            assert_eq!(c.productos.get(ajeno).unwrap().cantidad, 1);
+        }
+
+        #[ink::test]
+        fn ver_ordenes_como_vendedor_lista_solo_las_propias() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            c._registrar_usuario(accounts.alice, Roles::Comprador).unwrap();
+            c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
+            c._registrar_usuario(accounts.charlie, Roles::Vendedor).unwrap();
+            let pb = c._publicar_producto(accounts.bob, "A".into(), "D".into(), 10, 5, "Cat".into(), None).unwrap();
+            let pc = c._publicar_producto(accounts.charlie, "B".into(), "D".into(), 10, 5, "Cat".into(), None).unwrap();
+            let o1 = c._crear_orden(accounts.alice, pb, 1).unwrap();
+            c._crear_orden(accounts.alice, pc, 1).unwrap();
+            let o3 = c._crear_orden(accounts.alice, pb, 2).unwrap();
+
+            test::set_caller::<DefaultEnvironment>(accounts.bob);
+            let ids: Vec<u128> = c.ver_ordenes_como_vendedor().into_iter().map(|(id, _)| id).collect();
+            assert_eq!(ids, vec![o1, o3]);
+
+            test::set_caller::<DefaultEnvironment>(accounts.alice);
+            assert!(c.ver_ordenes_como_vendedor().is_empty());
+        }
+
+        #[ink::test]
+        fn conteo_productos_descuenta_los_eliminados() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
+            let mut ids = Vec::new();
+            for _ in 0..3 {
+                ids.push(c._publicar_producto(accounts.bob, "P".into(), "D".into(), 10, 1, "Cat".into(), None).unwrap());
+            }
+            assert_eq!(c.conteo_productos(), (3, 3));
+
+            assert_eq!(c._eliminar_producto(accounts.charlie, ids[1]), Err(ContractError::NoAutorizado));
+            assert_eq!(c._eliminar_producto(accounts.bob, ids[1]), Ok(()));
+            assert_eq!(c.conteo_productos(), (3, 2));
+            assert_eq!(c._ver_mis_productos(accounts.bob).len(), 2);
+            assert_eq!(c.ver_productos_por_categoria("Cat".into()).len(), 2);
+            assert_eq!(c._eliminar_producto(accounts.bob, ids[1]), Err(ContractError::ProductoNoEncontrado));
+        }
+
+        #[ink::test]
+        fn eliminar_producto_falla_con_ordenes_abiertas() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            c._registrar_usuario(accounts.alice, Roles::Comprador).unwrap();
+            c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
+            let pid = c._publicar_producto(accounts.bob, "P".into(), "D".into(), 10, 5, "Cat".into(), None).unwrap();
+            let oid = c._crear_orden(accounts.alice, pid, 1).unwrap();
+
+            assert_eq!(c._eliminar_producto(accounts.bob, pid), Err(ContractError::ProductoConOrdenesAbiertas));
+
+            c._marcar_enviada(accounts.bob, oid, None).unwrap();
+            c._marcar_recibida(accounts.alice, oid).unwrap();
+            assert_eq!(c._eliminar_producto(accounts.bob, pid), Ok(()));
+        }
+
+        #[ink::test]
+        fn obtener_orden_devuelve_la_orden_completa() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            c._registrar_usuario(accounts.alice, Roles::Comprador).unwrap();
+            c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
+            let pid = c._publicar_producto(accounts.bob, "P".into(), "D".into(), 10, 5, "Cat".into(), None).unwrap();
+            let oid = c._crear_orden(accounts.alice, pid, 3).unwrap();
+
+            let orden = c.obtener_orden(oid).unwrap();
+            assert_eq!(orden.comprador, accounts.alice);
+            assert_eq!(orden.vendedor, accounts.bob);
+            assert_eq!(orden.cantidad, 3);
+            assert_eq!(orden.estado, EstadoOrden::Pendiente);
+            assert_eq!(c.obtener_orden(oid + 1), None);
+        }
+
+        #[ink::test]
+        fn vendedor_verificado_es_confiable_aunque_tenga_mala_reputacion() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            let mut rep = ReputacionData::new();
+            rep.agregar_calificacion_vendedor(1).unwrap();
+            c.reputaciones.insert(accounts.bob, &rep);
+
+            assert!(!c.es_vendedor_confiable(accounts.bob, 4));
+            c._verificar_usuario(accounts.alice, accounts.bob, true).unwrap();
+            assert!(c.es_vendedor_confiable(accounts.bob, 4));
+        }
+
+        #[ink::test]
+        fn vendedor_no_verificado_es_confiable_con_buena_reputacion_suficiente() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            let mut rep = ReputacionData::new();
+            rep.agregar_calificacion_vendedor(5).unwrap();
+            rep.agregar_calificacion_vendedor(5).unwrap();
+            c.reputaciones.insert(accounts.bob, &rep);
+
+            // Buen promedio, pero todavía pocas calificaciones
+            assert!(!c.es_vendedor_confiable(accounts.bob, 4));
+
+            rep.agregar_calificacion_vendedor(4).unwrap();
+            c.reputaciones.insert(accounts.bob, &rep);
+            assert!(c.es_vendedor_confiable(accounts.bob, 4));
+            assert!(!c.es_vendedor_confiable(accounts.bob, 5));
+        }
+
+        #[ink::test]
+        fn ver_mis_ordenes_devuelve_las_ordenes_del_comprador() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            c._registrar_usuario(accounts.alice, Roles::Comprador).unwrap();
+            c._registrar_usuario(accounts.charlie, Roles::Comprador).unwrap();
+            c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
+            let pid = c._publicar_producto(accounts.bob, "P".into(), "D".into(), 10, 5, "Cat".into(), None).unwrap();
+            let o1 = c._crear_orden(accounts.alice, pid, 1).unwrap();
+            c._crear_orden(accounts.charlie, pid, 1).unwrap();
+            let o3 = c._crear_orden(accounts.alice, pid, 2).unwrap();
+
+            test::set_caller::<DefaultEnvironment>(accounts.alice);
+            let ordenes = c.ver_mis_ordenes();
+            assert_eq!(ordenes.iter().map(|(id, _)| *id).collect::<Vec<_>>(), vec![o1, o3]);
+            assert_eq!(ordenes[1].1.cantidad, 2);
+
+            test::set_caller::<DefaultEnvironment>(accounts.bob);
+            assert!(c.ver_mis_ordenes().is_empty());
+        }
+
+        #[ink::test]
+        fn ordenes_entre_filtra_por_vendedor() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            c._registrar_usuario(accounts.alice, Roles::Comprador).unwrap();
+            c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
+            c._registrar_usuario(accounts.charlie, Roles::Vendedor).unwrap();
+            let pb = c._publicar_producto(accounts.bob, "A".into(), "D".into(), 10, 5, "Cat".into(), None).unwrap();
+            let pc = c._publicar_producto(accounts.charlie, "B".into(), "D".into(), 10, 5, "Cat".into(), None).unwrap();
+            let o1 = c._crear_orden(accounts.alice, pb, 1).unwrap();
+            let o2 = c._crear_orden(accounts.alice, pc, 1).unwrap();
+            let o3 = c._crear_orden(accounts.alice, pb, 1).unwrap();
+
+            let con_bob: Vec<u128> = c.ordenes_entre(accounts.alice, accounts.bob).into_iter().map(|(id, _)| id).collect();
+            assert_eq!(con_bob, vec![o1, o3]);
+            let con_charlie: Vec<u128> = c.ordenes_entre(accounts.alice, accounts.charlie).into_iter().map(|(id, _)| id).collect();
+            assert_eq!(con_charlie, vec![o2]);
+            assert!(c.ordenes_entre(accounts.bob, accounts.charlie).is_empty());
         }
 
-        /// Obtiene el rol de un usuario.
-        fn _obtener_rol(&self, usuario: AccountId) -> Option<Roles> {
-            self.roles.get(usuario)
+        #[ink::test]
+        fn crear_orden_de_compra_exige_el_pago_exacto() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            c._registrar_usuario(accounts.alice, Roles::Comprador).unwrap();
+            c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
+            let pid = c._publicar_producto(accounts.bob, "P".into(), "D".into(), 100, 5, "Cat".into(), None).unwrap();
+
+            test::set_caller::<DefaultEnvironment>(accounts.alice);
+            test::set_value_transferred::<DefaultEnvironment>(150);
+            assert_eq!(c.crear_orden_de_compra(pid, 2, None), Err(ContractError::PagoIncorrecto));
+            assert_eq!(c.productos.get(pid).unwrap().cantidad, 5);
+
+            test::set_value_transferred::<DefaultEnvironment>(200);
+            let oid = c.crear_orden_de_compra(pid, 2, None).unwrap();
+            assert_eq!(c.obtener_orden(oid).unwrap().monto, 200);
         }
 
-        /// Obtiene el estado de una orden.
-        fn _obtener_estado_orden(&self, orden_id: u128) -> Option<EstadoOrden> {
-            self.ordenes.get(orden_id).map(|orden| orden.estado.clone())
+        #[ink::test]
+        fn marcar_recibida_libera_el_escrow_al_vendedor() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            test::set_callee::<DefaultEnvironment>(accounts.frank);
+            test::set_account_balance::<DefaultEnvironment>(accounts.frank, 1_000_000);
+            c._registrar_usuario(accounts.alice, Roles::Comprador).unwrap();
+            c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
+            let pid = c._publicar_producto(accounts.bob, "P".into(), "D".into(), 100, 5, "Cat".into(), None).unwrap();
+            let oid = c._crear_orden(accounts.alice, pid, 3).unwrap();
+            c._marcar_enviada(accounts.bob, oid, None).unwrap();
+
+            let saldo_vendedor = test::get_account_balance::<DefaultEnvironment>(accounts.bob).unwrap();
+            c._marcar_recibida(accounts.alice, oid).unwrap();
+            assert_eq!(
+                test::get_account_balance::<DefaultEnvironment>(accounts.bob).unwrap(),
+                saldo_vendedor + 300
+            );
+            assert_eq!(test::get_account_balance::<DefaultEnvironment>(accounts.frank).unwrap(), 999_700);
         }
-        /// Publica un nuevo producto validando que todos los campos sean válidos.
-        fn _publicar_producto(
-            &mut self,
-            caller: AccountId,
-            nombre: String,
-            descripcion: String,
-            precio: u128,
-            cantidad: u32,
-            categoria: String,
-        ) -> Result<u128, ContractError> {
-            let rol = self.roles.get(&caller);
-            if !rol.map_or(false, |r| r.es_vendedor()) {
-                return Err(ContractError::NoVendedor);
-            }
 
-            let producto = Producto {
-                nombre,
-                descripcion,
-                precio,
-                cantidad,
-                categoria,
-                vendedor: caller,
-            };
+        #[ink::test]
+        fn mix_categorias_vendedor_suma_unidades_recibidas_por_categoria() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            c._registrar_usuario(accounts.alice, Roles::Comprador).unwrap();
+            c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
+            let libro = c._publicar_producto(accounts.bob, "Libro".into(), "D".into(), 10, 20, "Libros".into(), None).unwrap();
+            let juego = c._publicar_producto(accounts.bob, "Juego".into(), "D".into(), 10, 20, "Juegos".into(), None).unwrap();
 
-            // Validar que los datos del producto sean correctos
-            producto.validar()?;
+            for (pid, cantidad) in [(libro, 3), (libro, 2), (juego, 1)] {
+                let oid = c._crear_orden(accounts.alice, pid, cantidad).unwrap();
+                c._marcar_enviada(accounts.bob, oid, None).unwrap();
+                c._marcar_recibida(accounts.alice, oid).unwrap();
+            }
+            // Una orden pendiente no cuenta
+            c._crear_orden(accounts.alice, juego, 4).unwrap();
 
-            let pid = self.siguiente_producto_id;
-            self.productos.insert(pid, &producto);
-            
-            let mut lista = self.productos_por_usuario
-                .get(&caller)
-                .unwrap_or_default();
-            lista.push(pid);
-            self.productos_por_usuario.insert(&caller, &lista);
-            
-            self.siguiente_producto_id = pid
-                .checked_add(1)
-                .ok_or(ContractError::Overflow)?;
-            Ok(pid)
+            assert_eq!(
+                c.mix_categorias_vendedor(accounts.bob),
+                vec![("Juegos".into(), 1), ("Libros".into(), 5)]
+            );
+            assert!(c.mix_categorias_vendedor(accounts.alice).is_empty());
         }
 
-        /// Obtiene todos los productos publicados por un usuario.
-        fn _ver_mis_productos(&self, caller: AccountId) -> Vec<(u128, Producto)> {
-            self.productos_por_usuario
-                .get(&caller)
-                .unwrap_or_default()
-                .into_iter()
-                .filter_map(|id| {
-                    self.productos.get(id).map(|p| (id, p))
-                })
-                .collect()
+        #[ink::test]
+        fn cancelacion_mutua_reembolsa_el_escrow_al_comprador() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            test::set_callee::<DefaultEnvironment>(accounts.frank);
+            test::set_account_balance::<DefaultEnvironment>(accounts.frank, 1_000_000);
+            c._registrar_usuario(accounts.alice, Roles::Comprador).unwrap();
+            c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
+            let pid = c._publicar_producto(accounts.bob, "P".into(), "D".into(), 100, 5, "Cat".into(), None).unwrap();
+            let oid = c._crear_orden(accounts.alice, pid, 2).unwrap();
+
+            let saldo_comprador = test::get_account_balance::<DefaultEnvironment>(accounts.alice).unwrap();
+            c._solicitar_cancel_comprador(accounts.alice, oid).unwrap();
+            c._aceptar_cancel_vendedor(accounts.bob, oid).unwrap();
+
+            assert_eq!(
+                test::get_account_balance::<DefaultEnvironment>(accounts.alice).unwrap(),
+                saldo_comprador + 200
+            );
+            assert_eq!(test::get_account_balance::<DefaultEnvironment>(accounts.frank).unwrap(), 999_800);
+            // El monto queda en cero: no hay nada más que reembolsar
+            assert_eq!(c.obtener_orden(oid).unwrap().monto, 0);
         }
 
-        /// Obtiene todos los productos publicados en el marketplace.
-        fn _ver_todos_los_productos(&self) -> Vec<(u128, Producto)> {
-            let mut acc = Vec::new();
-            for id in 1..self.siguiente_producto_id {
-                if let Some(p) = self.productos.get(id) {
-                    acc.push((id, p));
-                }
+        #[ink::test]
+        fn reputacion_publica_visible_para_todos() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            let mut rep = ReputacionData::new();
+            rep.agregar_calificacion_vendedor(4).unwrap();
+            c.reputaciones.insert(accounts.bob, &rep);
+
+            assert!(c.reputacion_publica());
+            // owner, el propio usuario y un tercero
+            for lector in [accounts.alice, accounts.bob, accounts.charlie] {
+                assert_eq!(c._reputacion_visible(lector, accounts.bob), Some(rep.clone()));
             }
-            acc
         }
 
-        /// Crea una nueva orden de compra validando stock y permisos.
-        fn _crear_orden(
-            &mut self, 
-            comprador: AccountId, 
-            producto_id: u128, 
-            cantidad: u32
-        ) -> Result<u128, ContractError> {
-            // Validar que el usuario tenga permisos de comprador
-            let rol = self.roles.get(&comprador);
-            if !rol.map_or(false, |r| r.es_comprador()) {
-                return Err(ContractError::NoAutorizado);
-            }
+        #[ink::test]
+        fn reputacion_privada_solo_para_owner_y_el_propio_usuario() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            let mut rep = ReputacionData::new();
+            rep.agregar_calificacion_vendedor(4).unwrap();
+            c.reputaciones.insert(accounts.bob, &rep);
 
-            // Validar que la cantidad sea mayor que 0
-            if cantidad == 0 {
-                return Err(ContractError::StockInsuficiente);
+            assert_eq!(c._establecer_reputacion_publica(accounts.bob, false), Err(ContractError::NoAutorizado));
+            c._establecer_reputacion_publica(accounts.alice, false).unwrap();
+
+            assert_eq!(c._reputacion_visible(accounts.alice, accounts.bob), Some(rep.clone()));
+            assert_eq!(c._reputacion_visible(accounts.bob, accounts.bob), Some(rep));
+            assert_eq!(c._reputacion_visible(accounts.charlie, accounts.bob), None);
+
+            test::set_caller::<DefaultEnvironment>(accounts.charlie);
+            assert_eq!(c.reputacion_como_vendedor(accounts.bob), None);
+            test::set_caller::<DefaultEnvironment>(accounts.bob);
+            assert_eq!(c.reputacion_como_vendedor(accounts.bob), Some(4));
+        }
+
+        #[ink::test]
+        fn constructor_rechaza_comision_mayor_al_cien_por_ciento() {
+            assert!(matches!(Marketplace::new(MAX_COMISION_BPS + 1, TIMEOUT_ENVIO_TEST, false, None, 0), Err(ContractError::ComisionInvalida)));
+            assert_eq!(Marketplace::new(MAX_COMISION_BPS, TIMEOUT_ENVIO_TEST, false, None, 0).unwrap().comision_bps(), MAX_COMISION_BPS);
+        }
+
+        #[ink::test]
+        fn marcar_recibida_separa_la_comision_del_pago_al_vendedor() {
+            let accounts = default_accounts();
+            // 2,5% de comisión
+            let mut c = Marketplace::new(250, TIMEOUT_ENVIO_TEST, false, None, 0).unwrap();
+            test::set_callee::<DefaultEnvironment>(accounts.frank);
+            test::set_account_balance::<DefaultEnvironment>(accounts.frank, 1_000_000);
+            c._registrar_usuario(accounts.alice, Roles::Comprador).unwrap();
+            c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
+            let pid = c._publicar_producto(accounts.bob, "P".into(), "D".into(), 200, 5, "Cat".into(), None).unwrap();
+            let oid = c._crear_orden(accounts.alice, pid, 2).unwrap();
+            c._marcar_enviada(accounts.bob, oid, None).unwrap();
+
+            let saldo_vendedor = test::get_account_balance::<DefaultEnvironment>(accounts.bob).unwrap();
+            c._marcar_recibida(accounts.alice, oid).unwrap();
+
+            assert_eq!(c.fondos_acumulados(), 10);
+            assert_eq!(
+                test::get_account_balance::<DefaultEnvironment>(accounts.bob).unwrap(),
+                saldo_vendedor + 390
+            );
+            // La comisión queda en el contrato hasta que el owner la retira
+            assert_eq!(test::get_account_balance::<DefaultEnvironment>(accounts.frank).unwrap(), 999_610);
+        }
+
+        #[ink::test]
+        fn participantes_activos_cuenta_cada_usuario_una_vez() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            c._registrar_usuario(accounts.alice, Roles::Comprador).unwrap();
+            c._registrar_usuario(accounts.charlie, Roles::Comprador).unwrap();
+            c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
+            let pid = c._publicar_producto(accounts.bob, "P".into(), "D".into(), 10, 10, "Cat".into(), None).unwrap();
+
+            assert_eq!(c.participantes_activos(), (0, 0));
+
+            for comprador in [accounts.alice, accounts.charlie, accounts.alice] {
+                let oid = c._crear_orden(comprador, pid, 1).unwrap();
+                c._marcar_enviada(accounts.bob, oid, None).unwrap();
+                c._marcar_recibida(comprador, oid).unwrap();
             }
+            // Una orden sin recibir no cuenta
+            c._crear_orden(accounts.alice, pid, 1).unwrap();
 
-            // Obtener y validar el producto
-            let mut producto = self.productos
-                .get(producto_id)
-                .ok_or(ContractError::ProductoNoEncontrado)?;
-            
-            if producto.cantidad < cantidad {
-                return Err(ContractError::StockInsuficiente);
+            assert_eq!(c.participantes_activos(), (1, 2));
+        }
+
+        #[ink::test]
+        fn transferir_owner_solo_lo_puede_hacer_el_owner() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            assert_eq!(c.obtener_owner(), accounts.alice);
+
+            assert_eq!(c._transferir_owner(accounts.bob, accounts.bob), Err(ContractError::NoAutorizado));
+            assert_eq!(c.obtener_owner(), accounts.alice);
+
+            assert_eq!(c._transferir_owner(accounts.alice, accounts.bob), Ok(()));
+            assert_eq!(c.obtener_owner(), accounts.bob);
+            // El owner anterior pierde los permisos
+            assert_eq!(c._establecer_modo_alto_riesgo(accounts.alice, true), Err(ContractError::NoAutorizado));
+        }
+
+        #[ink::test]
+        fn cancelacion_automatica_cancela_al_instante_las_pendientes() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            c._registrar_usuario(accounts.alice, Roles::Comprador).unwrap();
+            c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
+            let pid = c._publicar_producto(accounts.bob, "P".into(), "D".into(), 10, 5, "Cat".into(), None).unwrap();
+
+            assert_eq!(c._establecer_cancelacion_automatica(accounts.alice, true), Err(ContractError::NoVendedor));
+            c._establecer_cancelacion_automatica(accounts.bob, true).unwrap();
+            assert!(c.tiene_cancelacion_automatica(accounts.bob));
+
+            let oid = c._crear_orden(accounts.alice, pid, 2).unwrap();
+            c._solicitar_cancel_comprador(accounts.alice, oid).unwrap();
+            assert_eq!(c.obtener_estado_orden(oid), Some(EstadoOrden::Cancelada));
+            assert_eq!(c.productos.get(pid).unwrap().cantidad, 5);
+
+            // Una orden ya enviada sigue necesitando la aceptación del vendedor
+            let enviada = c._crear_orden(accounts.alice, pid, 1).unwrap();
+            c._marcar_enviada(accounts.bob, enviada, None).unwrap();
+            c._solicitar_cancel_comprador(accounts.alice, enviada).unwrap();
+            assert_eq!(c.obtener_estado_orden(enviada), Some(EstadoOrden::Enviado));
+        }
+
+        #[ink::test]
+        fn sin_cancelacion_automatica_se_requiere_aceptacion_del_vendedor() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            c._registrar_usuario(accounts.alice, Roles::Comprador).unwrap();
+            c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
+            let pid = c._publicar_producto(accounts.bob, "P".into(), "D".into(), 10, 5, "Cat".into(), None).unwrap();
+            let oid = c._crear_orden(accounts.alice, pid, 2).unwrap();
+
+            c._solicitar_cancel_comprador(accounts.alice, oid).unwrap();
+            assert_eq!(c.obtener_estado_orden(oid), Some(EstadoOrden::Pendiente));
+
+            c._aceptar_cancel_vendedor(accounts.bob, oid).unwrap();
+            assert_eq!(c.obtener_estado_orden(oid), Some(EstadoOrden::Cancelada));
+        }
+
+        #[ink::test]
+        fn pausa_bloquea_compras_pero_no_consultas() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            c._registrar_usuario(accounts.bob, Roles::Comprador).unwrap();
+            c._registrar_usuario(accounts.charlie, Roles::Vendedor).unwrap();
+            let pid = c._publicar_producto(accounts.charlie, "P".into(), "D".into(), 10, 5, "Cat".into(), None).unwrap();
+
+            test::set_caller::<DefaultEnvironment>(accounts.bob);
+            assert_eq!(c.establecer_pausa(true), Err(ContractError::NoAutorizado));
+            test::set_caller::<DefaultEnvironment>(accounts.alice);
+            assert_eq!(c.establecer_pausa(true), Ok(()));
+            assert!(c.esta_pausado());
+
+            test::set_caller::<DefaultEnvironment>(accounts.bob);
+            test::set_value_transferred::<DefaultEnvironment>(10);
+            assert_eq!(c.crear_orden_de_compra(pid, 1, None), Err(ContractError::Pausado));
+            test::set_caller::<DefaultEnvironment>(accounts.charlie);
+            assert_eq!(
+                c.publicar_producto("Q".into(), "D".into(), 10, 1, "Cat".into(), None),
+                Err(ContractError::Pausado)
+            );
+            assert_eq!(c.ver_todos_los_productos().len(), 1);
+
+            // Al reanudar se puede volver a comprar
+            test::set_caller::<DefaultEnvironment>(accounts.alice);
+            c.establecer_pausa(false).unwrap();
+            test::set_caller::<DefaultEnvironment>(accounts.bob);
+            assert!(c.crear_orden_de_compra(pid, 1, None).is_ok());
+        }
+
+        #[ink::test]
+        fn correlacion_calificaciones_detecta_quien_da_bajo_y_recibe_alto() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            c._registrar_usuario(accounts.alice, Roles::Comprador).unwrap();
+            c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
+            let pid = c._publicar_producto(accounts.bob, "P".into(), "D".into(), 10, 5, "Cat".into(), None).unwrap();
+
+            assert_eq!(c.correlacion_calificaciones(accounts.alice), None);
+
+            for _ in 0..2 {
+                let oid = c._crear_orden(accounts.alice, pid, 1).unwrap();
+                c._marcar_enviada(accounts.bob, oid, None).unwrap();
+                c._marcar_recibida(accounts.alice, oid).unwrap();
+                // alice siempre califica con 1 y recibe 5
+                c._calificar_vendedor(accounts.alice, oid, 1, None).unwrap();
+                c._calificar_comprador(accounts.bob, oid, 5, None).unwrap();
             }
 
-            // Reducir el stock del producto
-            producto.cantidad = producto.cantidad
-                .checked_sub(cantidad)
-                .ok_or(ContractError::Overflow)?;
-            self.productos.insert(producto_id, &producto);
+            assert_eq!(c.correlacion_calificaciones(accounts.alice), Some((1, 5)));
+            assert_eq!(c.correlacion_calificaciones(accounts.bob), Some((5, 1)));
+        }
 
-            // Crear la orden
-            let oid = self.siguiente_orden_id;
-            let orden = Orden {
-                comprador,
-                vendedor: producto.vendedor,
-                producto_id,
-                cantidad,
-                estado: EstadoOrden::Pendiente,
-                comprador_acepta_cancelar: false,
-                vendedor_acepta_cancelar: false,
-            };
-            self.ordenes.insert(oid, &orden);
+        #[ink::test]
+        fn correlacion_calificaciones_respeta_la_reputacion_privada() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            let mut rep = ReputacionData::new();
+            rep.agregar_calificacion_vendedor(4).unwrap();
+            c.reputaciones.insert(accounts.bob, &rep);
+            c.calificaciones_dadas.insert(accounts.bob, &(6, 2));
+            c._establecer_reputacion_publica(accounts.alice, false).unwrap();
 
-            // Agregar la orden a la lista del comprador
-            let mut ordenes_usuario = self.ordenes_por_usuario
-                .get(&comprador)
-                .unwrap_or_default();
-            ordenes_usuario.push(oid);
-            self.ordenes_por_usuario.insert(&comprador, &ordenes_usuario);
+            test::set_caller::<DefaultEnvironment>(accounts.charlie);
+            assert_eq!(c.correlacion_calificaciones(accounts.bob), None);
+            test::set_caller::<DefaultEnvironment>(accounts.bob);
+            assert_eq!(c.correlacion_calificaciones(accounts.bob), Some((3, 4)));
+        }
+
+        #[ink::test]
+        fn usuario_baneado_no_puede_registrarse_publicar_ni_comprar() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            c._registrar_usuario(accounts.bob, Roles::Ambos).unwrap();
+            c._registrar_usuario(accounts.charlie, Roles::Vendedor).unwrap();
+            let pid = c._publicar_producto(accounts.charlie, "P".into(), "D".into(), 10, 5, "Cat".into(), None).unwrap();
+
+            assert_eq!(c._cambiar_baneo(accounts.bob, accounts.django, true), Err(ContractError::NoAutorizado));
+            c._cambiar_baneo(accounts.alice, accounts.bob, true).unwrap();
+            c._cambiar_baneo(accounts.alice, accounts.django, true).unwrap();
+            assert!(c.esta_baneado(accounts.bob));
+
+            assert_eq!(c._registrar_usuario(accounts.django, Roles::Comprador), Err(ContractError::UsuarioBaneado));
+            assert_eq!(
+                c._publicar_producto(accounts.bob, "Q".into(), "D".into(), 10, 1, "Cat".into(), None),
+                Err(ContractError::UsuarioBaneado)
+            );
+            assert_eq!(c._crear_orden(accounts.bob, pid, 1), Err(ContractError::UsuarioBaneado));
+
+            c._cambiar_baneo(accounts.alice, accounts.bob, false).unwrap();
+            assert!(!c.esta_baneado(accounts.bob));
+            assert!(c._crear_orden(accounts.bob, pid, 1).is_ok());
+        }
+
+        #[ink::test]
+        fn usuario_baneado_puede_recibir_ordenes_en_curso() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            c._registrar_usuario(accounts.bob, Roles::Comprador).unwrap();
+            c._registrar_usuario(accounts.charlie, Roles::Vendedor).unwrap();
+            let pid = c._publicar_producto(accounts.charlie, "P".into(), "D".into(), 10, 5, "Cat".into(), None).unwrap();
+            let oid = c._crear_orden(accounts.bob, pid, 1).unwrap();
+            c._marcar_enviada(accounts.charlie, oid, None).unwrap();
+
+            c._cambiar_baneo(accounts.alice, accounts.bob, true).unwrap();
+            assert_eq!(c._marcar_recibida(accounts.bob, oid), Ok(()));
+        }
 
-            self.siguiente_orden_id = oid
-                .checked_add(1)
-                .ok_or(ContractError::Overflow)?;
-            Ok(oid)
+        #[ink::test]
+        fn decimales_token_desde_constructor_y_configurable_por_owner() {
+            let accounts = default_accounts();
+            assert_eq!(Marketplace::new(0, TIMEOUT_ENVIO_TEST, false, None, 6).unwrap().decimales_token(), 6);
+            let mut c = init_contract();
+            assert_eq!(c.decimales_token(), 0);
+
+            test::set_caller::<DefaultEnvironment>(accounts.bob);
+            assert_eq!(c.establecer_decimales_token(18), Err(ContractError::NoAutorizado));
+            test::set_caller::<DefaultEnvironment>(accounts.alice);
+            assert_eq!(c.establecer_decimales_token(18), Ok(()));
+            assert_eq!(c.decimales_token(), 18);
         }
 
-        /// Marca una orden como enviada (solo puede ser Pendiente).
-        fn _marcar_enviada(
-            &mut self, 
-            caller: AccountId, 
-            orden_id: u128
-        ) -> Result<(), ContractError> {
-            let mut orden = self.ordenes
-                .get(orden_id)
-                .ok_or(ContractError::OrdenNoExiste)?;
-            
-            if orden.vendedor != caller {
-                return Err(ContractError::NoAutorizado);
-            }
+        #[ink::test]
+        fn orden_registra_momento_de_creacion() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            c._registrar_usuario(accounts.alice, Roles::Comprador).unwrap();
+            c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
+            let pid = c._publicar_producto(accounts.bob, "P".into(), "D".into(), 10, 5, "Cat".into(), None).unwrap();
 
-            // No se puede pasar directamente a Recibido
-            // Solo se puede enviar desde Pendiente
-            if orden.estado != EstadoOrden::Pendiente {
-                return Err(ContractError::EstadoInvalido);
-            }
+            test::set_block_timestamp::<DefaultEnvironment>(1_000);
+            let o1 = c._crear_orden(accounts.alice, pid, 1).unwrap();
+            test::set_block_timestamp::<DefaultEnvironment>(5_000);
+            let o2 = c._crear_orden(accounts.alice, pid, 1).unwrap();
 
-            orden.estado = EstadoOrden::Enviado;
-            self.ordenes.insert(orden_id, &orden);
-            Ok(())
+            assert_eq!(c.obtener_orden(o1).unwrap().creada_en, 1_000);
+            assert_eq!(c.obtener_orden(o2).unwrap().creada_en, 5_000);
         }
 
-        /// Marca una orden como recibida (solo puede ser desde Enviado, no retrocede).
-        fn _marcar_recibida(
-            &mut self, 
-            caller: AccountId, 
-            orden_id: u128
-        ) -> Result<(), ContractError> {
-            let mut orden = self.ordenes
-                .get(orden_id)
-                .ok_or(ContractError::OrdenNoExiste)?;
-            
-            if orden.comprador != caller {
-                return Err(ContractError::NoAutorizado);
-            }
+        #[ink::test]
+        fn calificaciones_pendientes_antiguas_ignora_las_recientes() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            c._registrar_usuario(accounts.alice, Roles::Comprador).unwrap();
+            c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
+            let pid = c._publicar_producto(accounts.bob, "P".into(), "D".into(), 10, 5, "Cat".into(), None).unwrap();
+
+            let vieja = c._crear_orden(accounts.alice, pid, 1).unwrap();
+            let reciente = c._crear_orden(accounts.alice, pid, 1).unwrap();
+            c._marcar_enviada(accounts.bob, vieja, None).unwrap();
+            c._marcar_enviada(accounts.bob, reciente, None).unwrap();
+
+            test::set_block_timestamp::<DefaultEnvironment>(1_000);
+            c._marcar_recibida(accounts.alice, vieja).unwrap();
+            test::set_block_timestamp::<DefaultEnvironment>(9_000);
+            c._marcar_recibida(accounts.alice, reciente).unwrap();
+            test::set_block_timestamp::<DefaultEnvironment>(10_000);
+
+            assert_eq!(c.ordenes_calificacion_pendiente_antiguas(accounts.alice, 5_000), vec![vieja]);
+            assert_eq!(c.ordenes_calificacion_pendiente_antiguas(accounts.bob, 5_000), vec![vieja]);
+
+            // Una vez que el comprador califica, deja de figurar solo para él
+            c._calificar_vendedor(accounts.alice, vieja, 5, None).unwrap();
+            assert!(c.ordenes_calificacion_pendiente_antiguas(accounts.alice, 5_000).is_empty());
+            assert_eq!(c.ordenes_calificacion_pendiente_antiguas(accounts.bob, 5_000), vec![vieja]);
+        }
 
-            // Solo se puede recibir desde Enviado
-            // No se puede pasar directamente de Pendiente a Recibido
-            if orden.estado != EstadoOrden::Enviado {
-                return Err(ContractError::EstadoInvalido);
-            }
+        #[ink::test]
+        fn cancelar_por_inactividad_respeta_el_timeout_de_envio() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            test::set_callee::<DefaultEnvironment>(accounts.frank);
+            test::set_account_balance::<DefaultEnvironment>(accounts.frank, 1_000_000);
+            c._registrar_usuario(accounts.alice, Roles::Comprador).unwrap();
+            c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
+            let pid = c._publicar_producto(accounts.bob, "P".into(), "D".into(), 100, 5, "Cat".into(), None).unwrap();
+            let oid = c._crear_orden(accounts.alice, pid, 2).unwrap();
 
-            // Una vez recibido, no se puede retroceder
-            orden.estado = EstadoOrden::Recibido;
-            self.ordenes.insert(orden_id, &orden);
+            test::set_block_timestamp::<DefaultEnvironment>(TIMEOUT_ENVIO_TEST);
+            assert_eq!(c._cancelar_por_inactividad(accounts.alice, oid), Err(ContractError::TimeoutNoAlcanzado));
+            test::set_block_timestamp::<DefaultEnvironment>(TIMEOUT_ENVIO_TEST + 1);
+            assert_eq!(c._cancelar_por_inactividad(accounts.bob, oid), Err(ContractError::NoAutorizado));
 
-            // Registrar venta del producto
-            let ventas_actuales = self.ventas_por_producto.get(orden.producto_id).unwrap_or(0);
-            self.ventas_por_producto.insert(
-                orden.producto_id,
-                &ventas_actuales.checked_add(1).ok_or(ContractError::Overflow)?,
+            let saldo_comprador = test::get_account_balance::<DefaultEnvironment>(accounts.alice).unwrap();
+            assert_eq!(c._cancelar_por_inactividad(accounts.alice, oid), Ok(()));
+            assert_eq!(c.obtener_estado_orden(oid), Some(EstadoOrden::Cancelada));
+            assert_eq!(c.productos.get(pid).unwrap().cantidad, 5);
+            assert_eq!(
+                test::get_account_balance::<DefaultEnvironment>(accounts.alice).unwrap(),
+                saldo_comprador + 200
             );
+        }
 
-            // Inicializar calificaciones vacías para esta orden
-            let calificaciones = CalificacionesOrden {
-                calificacion_comprador: None,
-                calificacion_vendedor: None,
-            };
-            self.calificaciones_por_orden.insert(orden_id, &calificaciones);
+        #[ink::test]
+        fn cancelar_por_inactividad_no_aplica_a_ordenes_enviadas() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            c._registrar_usuario(accounts.alice, Roles::Comprador).unwrap();
+            c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
+            let pid = c._publicar_producto(accounts.bob, "P".into(), "D".into(), 10, 5, "Cat".into(), None).unwrap();
+            let oid = c._crear_orden(accounts.alice, pid, 1).unwrap();
+            c._marcar_enviada(accounts.bob, oid, None).unwrap();
 
-            Ok(())
+            test::set_block_timestamp::<DefaultEnvironment>(TIMEOUT_ENVIO_TEST * 2);
+            assert_eq!(c._cancelar_por_inactividad(accounts.alice, oid), Err(ContractError::EstadoInvalido));
         }
 
-        /// El comprador solicita la cancelación de una orden.
-        /// Si ambos aceptan, se cancela y se devuelve el stock.
-        fn _solicitar_cancel_comprador(
-            &mut self, 
-            caller: AccountId, 
-            orden_id: u128
-        ) -> Result<(), ContractError> {
-            let mut orden = self.ordenes
-                .get(orden_id)
-                .ok_or(ContractError::OrdenNoExiste)?;
-            
-            if orden.comprador != caller {
-                return Err(ContractError::NoAutorizado);
+        #[ink::test]
+        fn snapshot_directorio_reconstruye_todo_paginando() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            let usuarios = [
+                (accounts.alice, Roles::Comprador),
+                (accounts.bob, Roles::Vendedor),
+                (accounts.charlie, Roles::Ambos),
+                (accounts.django, Roles::Comprador),
+                (accounts.eve, Roles::Vendedor),
+            ];
+            for (cuenta, rol) in usuarios.iter() {
+                c._registrar_usuario(*cuenta, rol.clone()).unwrap();
             }
-
-            // Solo se puede cancelar si está Pendiente o Enviado
-            if !orden.puede_cancelarse() {
-                return Err(ContractError::EstadoInvalido);
+            let mut rep = ReputacionData::new();
+            rep.agregar_calificacion_vendedor(5).unwrap();
+            c.reputaciones.insert(accounts.bob, &rep);
+
+            let mut directorio = Vec::new();
+            let mut inicio = 0;
+            loop {
+                let pagina = c.snapshot_directorio(inicio, 2);
+                if pagina.is_empty() {
+                    break;
+                }
+                inicio += pagina.len() as u32;
+                directorio.extend(pagina);
             }
 
-            orden.comprador_acepta_cancelar = true;
-            
-            // Si ambos aceptan, cancelar y devolver stock
-            if orden.marcar_cancelada_si_ambos_aceptan() {
-                self._devolver_stock(orden.producto_id, orden.cantidad)?;
+            assert_eq!(directorio.len(), usuarios.len());
+            for ((cuenta, rol, _), (esperada, rol_esperado)) in directorio.iter().zip(usuarios.iter()) {
+                assert_eq!(cuenta, esperada);
+                assert_eq!(rol, rol_esperado);
             }
-            
-            self.ordenes.insert(orden_id, &orden);
-            Ok(())
+            assert_eq!(directorio[1].2, Some(rep.clone()));
+            assert_eq!(directorio[0].2, Some(ReputacionData::new()));
+
+            // Con la reputación privada, las que el caller no puede ver vienen como None
+            c._establecer_reputacion_publica(accounts.alice, false).unwrap();
+            test::set_caller::<DefaultEnvironment>(accounts.charlie);
+            let pagina = c.snapshot_directorio(1, 2);
+            assert_eq!(pagina[0].2, None);
+            assert_eq!(pagina[1].2, Some(ReputacionData::new()));
         }
 
-        /// El vendedor acepta la cancelación de una orden.
-        /// Si ambos aceptan, se cancela y se devuelve el stock.
-        fn _aceptar_cancel_vendedor(
-            &mut self, 
-            caller: AccountId, 
-            orden_id: u128
-        ) -> Result<(), ContractError> {
-            let mut orden = self.ordenes
-                .get(orden_id)
-                .ok_or(ContractError::OrdenNoExiste)?;
-            
-            if orden.vendedor != caller {
-                return Err(ContractError::NoAutorizado);
-            }
+        #[ink::test]
+        fn calificacion_promedio_producto_es_independiente_por_producto() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            c._registrar_usuario(accounts.alice, Roles::Comprador).unwrap();
+            c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
+            let p1 = c._publicar_producto(accounts.bob, "A".into(), "D".into(), 10, 5, "Cat".into(), None).unwrap();
+            let p2 = c._publicar_producto(accounts.bob, "B".into(), "D".into(), 10, 5, "Cat".into(), None).unwrap();
 
-            // Solo se puede cancelar si está Pendiente o Enviado
-            if !orden.puede_cancelarse() {
-                return Err(ContractError::EstadoInvalido);
-            }
+            assert_eq!(c.calificacion_promedio_producto(p1), None);
 
-            orden.vendedor_acepta_cancelar = true;
-            
-            // Si ambos aceptan, cancelar y devolver stock
-            if orden.marcar_cancelada_si_ambos_aceptan() {
-                self._devolver_stock(orden.producto_id, orden.cantidad)?;
+            for (pid, puntaje) in [(p1, 5), (p1, 4), (p2, 1)] {
+                let oid = c._crear_orden(accounts.alice, pid, 1).unwrap();
+                c._marcar_enviada(accounts.bob, oid, None).unwrap();
+                c._marcar_recibida(accounts.alice, oid).unwrap();
+                c._calificar_vendedor(accounts.alice, oid, puntaje, None).unwrap();
             }
-            
-            self.ordenes.insert(orden_id, &orden);
-            Ok(())
-        }
 
-        /// Devuelve stock a un producto cuando se cancela una orden.
-        fn _devolver_stock(
-            &mut self, 
-            producto_id: u128, 
-            cantidad: u32
-        ) -> Result<(), ContractError> {
-            let mut producto = self.productos
-                .get(producto_id)
-                .ok_or(ContractError::ProductoNoEncontrado)?;
-            
-            producto.aumentar_stock(cantidad)?;
-            self.productos.insert(producto_id, &producto);
-            Ok(())
+            assert_eq!(c.calificacion_promedio_producto(p1), Some(4));
+            assert_eq!(c.calificacion_promedio_producto(p2), Some(1));
         }
 
-        /// Valida que una calificación esté en el rango válido (1-5).
-        fn _validar_calificacion(calificacion: u8) -> Result<(), ContractError> {
-            if calificacion < 1 || calificacion > 5 {
-                return Err(ContractError::CalificacionInvalida);
-            }
-            Ok(())
+        #[ink::test]
+        fn calificacion_con_comentario_se_guarda_y_respeta_el_largo_maximo() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            c._registrar_usuario(accounts.alice, Roles::Comprador).unwrap();
+            c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
+            let pid = c._publicar_producto(accounts.bob, "P".into(), "D".into(), 10, 5, "Cat".into(), None).unwrap();
+            let oid = c._crear_orden(accounts.alice, pid, 1).unwrap();
+            c._marcar_enviada(accounts.bob, oid, None).unwrap();
+            c._marcar_recibida(accounts.alice, oid).unwrap();
+
+            let largo = "a".repeat(MAX_LONGITUD_COMENTARIO + 1);
+            assert_eq!(
+                c._calificar_vendedor(accounts.alice, oid, 5, Some(largo)),
+                Err(ContractError::DatosInvalidos)
+            );
+
+            c._calificar_vendedor(accounts.alice, oid, 5, Some("Excelente".into())).unwrap();
+            c._calificar_comprador(accounts.bob, oid, 4, None).unwrap();
+
+            let calificaciones = c.obtener_calificaciones_orden(oid).unwrap();
+            assert_eq!(calificaciones.comentario_comprador, Some("Excelente".into()));
+            assert_eq!(calificaciones.comentario_vendedor, None);
         }
 
-        /// El comprador califica al vendedor.
-        fn _calificar_vendedor(
-            &mut self,
-            comprador: AccountId,
-            orden_id: u128,
-            calificacion: u8,
-        ) -> Result<(), ContractError> {
-            // Validar rango de calificación
-            Self::_validar_calificacion(calificacion)?;
+        #[ink::test]
+        fn histograma_reputacion_separa_roles_y_puntajes() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            c._registrar_usuario(accounts.alice, Roles::Comprador).unwrap();
+            c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
+            let pid = c._publicar_producto(accounts.bob, "P".into(), "D".into(), 10, 5, "Cat".into(), None).unwrap();
 
-            // Obtener y validar la orden
-            let orden = self.ordenes
-                .get(orden_id)
-                .ok_or(ContractError::OrdenNoExiste)?;
+            assert_eq!(c.histograma_reputacion(accounts.bob), None);
 
-            // Verificar que el caller es el comprador
-            if orden.comprador != comprador {
-                return Err(ContractError::NoAutorizado);
+            for puntaje in [5, 5, 1] {
+                let oid = c._crear_orden(accounts.alice, pid, 1).unwrap();
+                c._marcar_enviada(accounts.bob, oid, None).unwrap();
+                c._marcar_recibida(accounts.alice, oid).unwrap();
+                c._calificar_vendedor(accounts.alice, oid, puntaje, None).unwrap();
+                c._calificar_comprador(accounts.bob, oid, 3, None).unwrap();
             }
 
-            // Solo se puede calificar si la orden está recibida
-            if orden.estado != EstadoOrden::Recibido {
-                return Err(ContractError::OrdenNoRecibida);
-            }
+            assert_eq!(c.histograma_reputacion(accounts.bob), Some(([0; 5], [1, 0, 0, 0, 2])));
+            assert_eq!(c.histograma_reputacion(accounts.alice), Some(([0, 0, 3, 0, 0], [0; 5])));
+        }
 
-            // Obtener calificaciones existentes
-            let mut calificaciones = self.calificaciones_por_orden
-                .get(orden_id)
-                .ok_or(ContractError::EstadoInvalido)?;
+        #[ink::test]
+        fn editar_producto_cambia_descripcion_y_categoria() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
+            let pid = c._publicar_producto(accounts.bob, "P".into(), "D".into(), 10, 5, "Viejo".into(), None).unwrap();
 
-            // Verificar que no haya calificado antes
-            if calificaciones.calificacion_comprador.is_some() {
-                return Err(ContractError::YaCalificado);
-            }
+            assert_eq!(
+                c._editar_producto(accounts.charlie, pid, "Nueva".into(), "Nuevo".into()),
+                Err(ContractError::NoAutorizado)
+            );
+            assert_eq!(
+                c._editar_producto(accounts.bob, pid, "".into(), "Nuevo".into()),
+                Err(ContractError::DatosInvalidos)
+            );
 
-            // Guardar la calificación
-            calificaciones.calificacion_comprador = Some(calificacion);
-            self.calificaciones_por_orden.insert(orden_id, &calificaciones);
+            assert_eq!(c._editar_producto(accounts.bob, pid, "Nueva".into(), "Nuevo".into()), Ok(()));
+            let producto = c.productos.get(pid).unwrap();
+            assert_eq!(producto.nombre, "P");
+            assert_eq!(producto.descripcion, "Nueva");
+            assert_eq!(producto.categoria, "Nuevo");
+            assert!(c.ver_productos_por_categoria("Viejo".into()).is_empty());
+            assert_eq!(c.ver_productos_por_categoria("Nuevo".into()).len(), 1);
+        }
 
-            // Actualizar reputación del vendedor
-            let mut reputacion = self.reputaciones
-                .get(orden.vendedor)
-                .unwrap_or_else(ReputacionData::new);
-            reputacion.agregar_calificacion_vendedor(calificacion)?;
-            self.reputaciones.insert(orden.vendedor, &reputacion);
+        #[ink::test]
+        fn ordenes_por_estado_combina_roles_de_comprador_y_vendedor() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            c._registrar_usuario(accounts.alice, Roles::Comprador).unwrap();
+            c._registrar_usuario(accounts.bob, Roles::Ambos).unwrap();
+            c._registrar_usuario(accounts.charlie, Roles::Vendedor).unwrap();
+            let de_bob = c._publicar_producto(accounts.bob, "A".into(), "D".into(), 10, 5, "Cat".into(), None).unwrap();
+            let de_charlie = c._publicar_producto(accounts.charlie, "B".into(), "D".into(), 10, 5, "Cat".into(), None).unwrap();
+
+            // bob como vendedor
+            let o1 = c._crear_orden(accounts.alice, de_bob, 1).unwrap();
+            let o2 = c._crear_orden(accounts.alice, de_bob, 1).unwrap();
+            c._marcar_enviada(accounts.bob, o2, None).unwrap();
+            // bob como comprador
+            let o3 = c._crear_orden(accounts.bob, de_charlie, 1).unwrap();
+
+            let ids = |v: Vec<(u128, Orden)>| v.into_iter().map(|(id, _)| id).collect::<Vec<_>>();
+            assert_eq!(ids(c._ordenes_por_estado(accounts.bob, EstadoOrden::Pendiente)), vec![o1, o3]);
+            assert_eq!(ids(c._ordenes_por_estado(accounts.bob, EstadoOrden::Enviado)), vec![o2]);
+            assert_eq!(ids(c._ordenes_por_estado(accounts.charlie, EstadoOrden::Pendiente)), vec![o3]);
+            assert!(c._ordenes_por_estado(accounts.alice, EstadoOrden::Recibido).is_empty());
+        }
 
-            // Actualizar estadísticas de categoría
-            if let Some(producto) = self.productos.get(orden.producto_id) {
-                let mut stats = self.estadisticas_por_categoria
-                    .get(&producto.categoria)
-                    .unwrap_or((0, 0, 0));
-                
-                stats.0 = stats.0.checked_add(1).ok_or(ContractError::Overflow)?;
-                stats.1 = stats.1.checked_add(calificacion as u128).ok_or(ContractError::Overflow)?;
-                stats.2 = stats.2.checked_add(1).ok_or(ContractError::Overflow)?;
-                
-                self.estadisticas_por_categoria.insert(&producto.categoria, &stats);
-            }
+        #[ink::test]
+        fn resolver_disputa_a_favor_del_comprador_reembolsa() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            test::set_callee::<DefaultEnvironment>(accounts.frank);
+            test::set_account_balance::<DefaultEnvironment>(accounts.frank, 1_000_000);
+            c._registrar_usuario(accounts.django, Roles::Comprador).unwrap();
+            c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
+            let pid = c._publicar_producto(accounts.bob, "P".into(), "D".into(), 100, 5, "Cat".into(), None).unwrap();
+            let oid = c._crear_orden(accounts.django, pid, 2).unwrap();
+
+            assert_eq!(c._resolver_disputa(accounts.alice, oid, true), Err(ContractError::DisputaNoAbierta));
+            c._abrir_disputa(accounts.django, oid).unwrap();
+            assert_eq!(c._resolver_disputa(accounts.bob, oid, false), Err(ContractError::NoAutorizado));
+
+            let saldo_comprador = test::get_account_balance::<DefaultEnvironment>(accounts.django).unwrap();
+            assert_eq!(c._resolver_disputa(accounts.alice, oid, true), Ok(()));
+            assert_eq!(c.obtener_estado_orden(oid), Some(EstadoOrden::Cancelada));
+            assert_eq!(c.productos.get(pid).unwrap().cantidad, 5);
+            assert_eq!(
+                test::get_account_balance::<DefaultEnvironment>(accounts.django).unwrap(),
+                saldo_comprador + 200
+            );
+        }
 
-            Ok(())
+        #[ink::test]
+        fn resolver_disputa_a_favor_del_vendedor_libera_los_fondos() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            test::set_callee::<DefaultEnvironment>(accounts.frank);
+            test::set_account_balance::<DefaultEnvironment>(accounts.frank, 1_000_000);
+            c._registrar_usuario(accounts.django, Roles::Comprador).unwrap();
+            c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
+            let pid = c._publicar_producto(accounts.bob, "P".into(), "D".into(), 100, 5, "Cat".into(), None).unwrap();
+            let oid = c._crear_orden(accounts.django, pid, 2).unwrap();
+            c._marcar_enviada(accounts.bob, oid, None).unwrap();
+            c._abrir_disputa(accounts.bob, oid).unwrap();
+
+            let saldo_vendedor = test::get_account_balance::<DefaultEnvironment>(accounts.bob).unwrap();
+            assert_eq!(c._resolver_disputa(accounts.alice, oid, false), Ok(()));
+            assert_eq!(c.obtener_estado_orden(oid), Some(EstadoOrden::Recibido));
+            assert_eq!(c.obtener_ventas_producto(pid), 1);
+            assert_eq!(
+                test::get_account_balance::<DefaultEnvironment>(accounts.bob).unwrap(),
+                saldo_vendedor + 200
+            );
         }
 
-        /// El vendedor califica al comprador.
-        fn _calificar_comprador(
-            &mut self,
-            vendedor: AccountId,
-            orden_id: u128,
-            calificacion: u8,
-        ) -> Result<(), ContractError> {
-            // Validar rango de calificación
-            Self::_validar_calificacion(calificacion)?;
+        #[ink::test]
+        fn cancelacion_parcial_devuelve_stock_y_escrow_proporcional() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            test::set_callee::<DefaultEnvironment>(accounts.frank);
+            test::set_account_balance::<DefaultEnvironment>(accounts.frank, 1_000_000);
+            c._registrar_usuario(accounts.django, Roles::Comprador).unwrap();
+            c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
+            let pid = c._publicar_producto(accounts.bob, "P".into(), "D".into(), 100, 10, "Cat".into(), None).unwrap();
+            let oid = c._crear_orden(accounts.django, pid, 5).unwrap();
 
-            // Obtener y validar la orden
-            let orden = self.ordenes
-                .get(orden_id)
-                .ok_or(ContractError::OrdenNoExiste)?;
+            assert_eq!(c._solicitar_cancelacion_parcial(accounts.django, oid, 6), Err(ContractError::DatosInvalidos));
+            assert_eq!(c._aceptar_cancelacion_parcial(accounts.bob, oid), Err(ContractError::EstadoInvalido));
 
-            // Verificar que el caller es el vendedor
-            if orden.vendedor != vendedor {
-                return Err(ContractError::NoAutorizado);
-            }
+            c._solicitar_cancelacion_parcial(accounts.django, oid, 2).unwrap();
+            // Hasta que el vendedor acepta no cambia nada
+            assert_eq!(c.obtener_orden(oid).unwrap().cantidad, 5);
+            assert_eq!(c._aceptar_cancelacion_parcial(accounts.django, oid), Err(ContractError::NoAutorizado));
 
-            // Solo se puede calificar si la orden está recibida
-            if orden.estado != EstadoOrden::Recibido {
-                return Err(ContractError::OrdenNoRecibida);
-            }
+            let saldo_comprador = test::get_account_balance::<DefaultEnvironment>(accounts.django).unwrap();
+            c._aceptar_cancelacion_parcial(accounts.bob, oid).unwrap();
 
-            // Obtener calificaciones existentes
-            let mut calificaciones = self.calificaciones_por_orden
-                .get(orden_id)
-                .ok_or(ContractError::EstadoInvalido)?;
+            let orden = c.obtener_orden(oid).unwrap();
+            assert_eq!(orden.cantidad, 3);
+            assert_eq!(orden.monto, 300);
+            assert_eq!(orden.estado, EstadoOrden::Pendiente);
+            assert_eq!(c.productos.get(pid).unwrap().cantidad, 7);
+            assert_eq!(c.liabilidad_reembolsos(), 300);
+            assert_eq!(
+                test::get_account_balance::<DefaultEnvironment>(accounts.django).unwrap(),
+                saldo_comprador + 200
+            );
+        }
 
-            // Verificar que no haya calificado antes
-            if calificaciones.calificacion_vendedor.is_some() {
-                return Err(ContractError::YaCalificado);
-            }
+        #[ink::test]
+        fn cancelacion_parcial_de_todas_las_unidades_cancela_la_orden() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            c._registrar_usuario(accounts.alice, Roles::Comprador).unwrap();
+            c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
+            let pid = c._publicar_producto(accounts.bob, "P".into(), "D".into(), 10, 10, "Cat".into(), None).unwrap();
+            let oid = c._crear_orden(accounts.alice, pid, 4).unwrap();
 
-            // Guardar la calificación
-            calificaciones.calificacion_vendedor = Some(calificacion);
-            self.calificaciones_por_orden.insert(orden_id, &calificaciones);
+            c._solicitar_cancelacion_parcial(accounts.alice, oid, 4).unwrap();
+            c._aceptar_cancelacion_parcial(accounts.bob, oid).unwrap();
 
-            // Actualizar reputación del comprador
-            let mut reputacion = self.reputaciones
-                .get(orden.comprador)
-                .unwrap_or_else(ReputacionData::new);
-            reputacion.agregar_calificacion_comprador(calificacion)?;
-            self.reputaciones.insert(orden.comprador, &reputacion);
+            assert_eq!(c.obtener_estado_orden(oid), Some(EstadoOrden::Cancelada));
+            assert_eq!(c.productos.get(pid).unwrap().cantidad, 10);
+        }
 
-            Ok(())
+        #[ink::test]
+        fn conteo_por_categoria_cuenta_activos_ordenados_por_nombre() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
+            for categoria in ["Ropa", "Hogar", "Ropa", "Autos"] {
+                c._publicar_producto(accounts.bob, "P".into(), "D".into(), 10, 1, categoria.into(), None).unwrap();
+            }
+            // El producto de Autos se desactiva
+            c._cambiar_estado_producto(accounts.bob, 4, false).unwrap();
+
+            assert_eq!(
+                c.conteo_por_categoria(),
+                vec![("Hogar".into(), 1), ("Ropa".into(), 2)]
+            );
         }
 
-        /// Obtiene la reputación de un usuario.
-        fn _obtener_reputacion(&self, usuario: AccountId) -> Option<ReputacionData> {
-            self.reputaciones.get(usuario)
+        #[ink::test]
+        fn vendedor_no_puede_comprar_su_propio_producto() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            c._registrar_usuario(accounts.bob, Roles::Ambos).unwrap();
+            let pid = c._publicar_producto(accounts.bob, "P".into(), "D".into(), 10, 5, "Cat".into(), None).unwrap();
+
+            assert_eq!(c._crear_orden(accounts.bob, pid, 1), Err(ContractError::CompraPropia));
+            assert_eq!(c.productos.get(pid).unwrap().cantidad, 5);
         }
-    }
 
-    #[cfg(test)]
-    mod tests {
-        use super::*;
-        use ink::env::{test, DefaultEnvironment};
+        #[ink::test]
+        fn no_se_puede_calificar_una_orden_cancelada() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            c._registrar_usuario(accounts.alice, Roles::Comprador).unwrap();
+            c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
+            let pid = c._publicar_producto(accounts.bob, "P".into(), "D".into(), 10, 5, "Cat".into(), None).unwrap();
+            let oid = c._crear_orden(accounts.alice, pid, 1).unwrap();
+            c._solicitar_cancel_comprador(accounts.alice, oid).unwrap();
+            c._aceptar_cancel_vendedor(accounts.bob, oid).unwrap();
 
-        fn default_accounts() -> test::DefaultAccounts<DefaultEnvironment> {
-            test::default_accounts::<DefaultEnvironment>()
+            test::set_caller::<DefaultEnvironment>(accounts.alice);
+            assert_eq!(c.comprador_califica_vendedor(oid, 5, None), Err(ContractError::OrdenNoRecibida));
+            test::set_caller::<DefaultEnvironment>(accounts.bob);
+            assert_eq!(c.vendedor_califica_comprador(oid, 5, None), Err(ContractError::OrdenNoRecibida));
         }
 
-        fn init_contract() -> Marketplace {
-            Marketplace::new()
+        #[ink::test]
+        fn publicar_productos_batch_devuelve_ids_en_orden() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
+            let lote = vec![
+                ("A".into(), "D".into(), 10, 1, "Cat".into()),
+                ("B".into(), "D".into(), 20, 2, "Cat".into()),
+                ("C".into(), "D".into(), 30, 3, "Otra".into()),
+            ];
+
+            assert_eq!(c._publicar_productos_batch(accounts.bob, lote), Ok(vec![1, 2, 3]));
+            assert_eq!(c.productos.get(2).unwrap().nombre, "B");
+            assert_eq!(c._ver_mis_productos(accounts.bob).len(), 3);
         }
 
         #[ink::test]
-        fn registrar_y_obtener_rol_funciona() {
+        fn publicar_productos_batch_no_publica_nada_si_una_entrada_es_invalida() {
             let accounts = default_accounts();
             let mut c = init_contract();
-            assert_eq!(c._registrar_usuario(accounts.alice, Roles::Comprador), Ok(()));
-            assert_eq!(c._obtener_rol(accounts.alice), Some(Roles::Comprador));
+            c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
+            let lote = vec![
+                ("A".into(), "D".into(), 10, 1, "Cat".into()),
+                ("B".into(), "D".into(), 0, 2, "Cat".into()),
+            ];
+
+            assert_eq!(c._publicar_productos_batch(accounts.bob, lote), Err(ContractError::DatosInvalidos));
+            assert_eq!(c.conteo_productos(), (0, 0));
+
+            let demasiados = vec![("A".into(), "D".into(), 10, 1, "Cat".into()); MAX_PUBLICACIONES_BATCH + 1];
+            assert_eq!(c._publicar_productos_batch(accounts.bob, demasiados), Err(ContractError::DatosInvalidos));
         }
 
         #[ink::test]
-        fn no_se_puede_registrar_dos_veces() {
+        fn estado_calificacion_orden_refleja_quien_ya_califico() {
             let accounts = default_accounts();
             let mut c = init_contract();
-            let _ = c._registrar_usuario(accounts.alice, Roles::Vendedor);
-            assert_eq!(c._registrar_usuario(accounts.alice, Roles::Comprador), Err(ContractError::YaRegistrado));
+            c._registrar_usuario(accounts.alice, Roles::Comprador).unwrap();
+            c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
+            let pid = c._publicar_producto(accounts.bob, "P".into(), "D".into(), 10, 5, "Cat".into(), None).unwrap();
+            let oid = c._crear_orden(accounts.alice, pid, 1).unwrap();
+            c._marcar_enviada(accounts.bob, oid, None).unwrap();
+            assert_eq!(c.estado_calificacion_orden(oid), None);
+
+            c._marcar_recibida(accounts.alice, oid).unwrap();
+            assert_eq!(c.estado_calificacion_orden(oid), Some((false, false)));
+
+            c._calificar_vendedor(accounts.alice, oid, 4, None).unwrap();
+            assert_eq!(c.estado_calificacion_orden(oid), Some((true, false)));
+
+            c._calificar_comprador(accounts.bob, oid, 5, None).unwrap();
+            assert_eq!(c.estado_calificacion_orden(oid), Some((true, true)));
         }
 
         #[ink::test]
-        fn modificar_rol_funciona_y_falla_si_no_registrado() {
+        fn reputacion_minima_comprador_bloquea_compradores_mal_calificados() {
             let accounts = default_accounts();
             let mut c = init_contract();
-            assert_eq!(c._modificar_rol(accounts.alice, Roles::Ambos), Err(ContractError::UsuarioNoRegistrado));
             c._registrar_usuario(accounts.alice, Roles::Comprador).unwrap();
-            assert_eq!(c._modificar_rol(accounts.alice, Roles::Vendedor), Ok(()));
-            assert_eq!(c._obtener_rol(accounts.alice), Some(Roles::Vendedor));
+            c._registrar_usuario(accounts.charlie, Roles::Comprador).unwrap();
+            c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
+            let pid = c._publicar_producto(accounts.bob, "P".into(), "D".into(), 10, 10, "Cat".into(), None).unwrap();
+
+            // Alice queda con promedio 2 como compradora
+            let oid = c._crear_orden(accounts.alice, pid, 1).unwrap();
+            c._marcar_enviada(accounts.bob, oid, None).unwrap();
+            c._marcar_recibida(accounts.alice, oid).unwrap();
+            c._calificar_comprador(accounts.bob, oid, 2, None).unwrap();
+
+            assert_eq!(
+                c._establecer_reputacion_minima_comprador(accounts.charlie, pid, Some(3)),
+                Err(ContractError::NoAutorizado)
+            );
+            assert_eq!(
+                c._establecer_reputacion_minima_comprador(accounts.bob, pid, Some(6)),
+                Err(ContractError::DatosInvalidos)
+            );
+            c._establecer_reputacion_minima_comprador(accounts.bob, pid, Some(3)).unwrap();
+
+            assert_eq!(c._crear_orden(accounts.alice, pid, 1), Err(ContractError::ReputacionInsuficiente));
+            // Charlie no tiene calificaciones todavía, así que puede comprar
+            assert!(c._crear_orden(accounts.charlie, pid, 1).is_ok());
+
+            c._establecer_reputacion_minima_comprador(accounts.bob, pid, None).unwrap();
+            assert!(c._crear_orden(accounts.alice, pid, 1).is_ok());
         }
 
         #[ink::test]
-        fn publicar_producto_funciona() {
+        fn totales_de_productos_y_ordenes_ignoran_huecos_en_ids() {
             let accounts = default_accounts();
             let mut c = init_contract();
+            c._registrar_usuario(accounts.alice, Roles::Comprador).unwrap();
             c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
-            let id = c._publicar_producto(
-                accounts.bob,
-                "Camisa".into(),
-                "Camisa de lino".into(),
-                100,
-                3,
-                "Ropa".into()
-            ).unwrap();
-            assert_eq!(id, 1);
+            let p1 = c._publicar_producto(accounts.bob, "P".into(), "D".into(), 10, 5, "Cat".into(), None).unwrap();
+            let p2 = c._publicar_producto(accounts.bob, "Q".into(), "D".into(), 10, 5, "Cat".into(), None).unwrap();
+            assert_eq!((c.total_productos(), c.total_ordenes()), (2, 0));
+
+            c._eliminar_producto(accounts.bob, p2).unwrap();
+            c._crear_orden(accounts.alice, p1, 1).unwrap();
+            c._crear_orden(accounts.alice, p1, 2).unwrap();
+            assert_eq!((c.total_productos(), c.total_ordenes()), (1, 2));
         }
 
         #[ink::test]
-        fn publicar_producto_con_rol_invalido_falla() {
+        fn buscar_productos_ignora_mayusculas_ascii_e_inactivos() {
             let accounts = default_accounts();
             let mut c = init_contract();
-            assert_eq!(
-                c._publicar_producto(accounts.alice, "A".into(), "B".into(), 1, 1, "X".into()),
-                Err(ContractError::NoVendedor)
-            );
+            c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
+            let p1 = c._publicar_producto(accounts.bob, "Mate de Calabaza".into(), "D".into(), 10, 1, "Cat".into(), None).unwrap();
+            let p2 = c._publicar_producto(accounts.bob, "Bombilla".into(), "D".into(), 10, 1, "Cat".into(), None).unwrap();
+            let p3 = c._publicar_producto(accounts.bob, "MATE listo".into(), "D".into(), 10, 1, "Cat".into(), None).unwrap();
+            c._publicar_producto(accounts.bob, "Termo".into(), "D".into(), 10, 1, "Cat".into(), None).unwrap();
+
+            let ids: Vec<u128> = c.buscar_productos("mate".into()).into_iter().map(|(id, _)| id).collect();
+            assert_eq!(ids, vec![p1, p3]);
+            assert_eq!(c.buscar_productos("BOMB".into())[0].0, p2);
+            assert!(c.buscar_productos("".into()).is_empty());
+
+            c._eliminar_producto(accounts.bob, p1).unwrap();
+            assert_eq!(c.buscar_productos("mate".into()).len(), 1);
         }
 
         #[ink::test]
-        fn crear_orden_funciona_y_valida_stock() {
+        fn limite_por_orden_permite_hasta_el_limite_inclusive() {
             let accounts = default_accounts();
             let mut c = init_contract();
             c._registrar_usuario(accounts.alice, Roles::Comprador).unwrap();
             c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
+            let pid = c._publicar_producto(accounts.bob, "P".into(), "D".into(), 10, 10, "Cat".into(), Some(3)).unwrap();
 
-            c._publicar_producto(accounts.bob, "Libro".into(), "Rust".into(), 500, 5, "Libros".into()).unwrap();
-            let oid = c._crear_orden(accounts.alice, 1, 3).unwrap();
-            assert_eq!(oid, 1);
+            assert_eq!(c._crear_orden(accounts.alice, pid, 4), Err(ContractError::LimitePorOrdenExcedido));
+            assert!(c._crear_orden(accounts.alice, pid, 3).is_ok());
+            assert_eq!(c.productos.get(pid).unwrap().cantidad, 7);
 
-            assert_eq!(c._crear_orden(accounts.alice, 1, 10), Err(ContractError::StockInsuficiente));
-            assert_eq!(c._crear_orden(accounts.alice, 999, 1), Err(ContractError::ProductoNoEncontrado));
+            let sin_limite = c._publicar_producto(accounts.bob, "Q".into(), "D".into(), 10, 10, "Cat".into(), None).unwrap();
+            assert!(c._crear_orden(accounts.alice, sin_limite, 10).is_ok());
         }
 
         #[ink::test]
-        fn crear_orden_por_usuario_no_autorizado_falla() {
+        fn publicar_producto_rechaza_limite_por_orden_cero() {
             let accounts = default_accounts();
             let mut c = init_contract();
             c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
-            c._publicar_producto(accounts.bob, "Item".into(), "Desc".into(), 1, 1, "C".into()).unwrap();
             assert_eq!(
-                c._crear_orden(accounts.charlie, 1, 1),
-                Err(ContractError::NoAutorizado)
+                c._publicar_producto(accounts.bob, "P".into(), "D".into(), 10, 10, "Cat".into(), Some(0)),
+                Err(ContractError::DatosInvalidos)
             );
         }
 
         #[ink::test]
-        fn orden_estado_transiciones_correctas() {
+        fn stock_disponible_refleja_compras_y_productos_inactivos() {
             let accounts = default_accounts();
             let mut c = init_contract();
             c._registrar_usuario(accounts.alice, Roles::Comprador).unwrap();
             c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
+            let pid = c._publicar_producto(accounts.bob, "P".into(), "D".into(), 10, 5, "Cat".into(), None).unwrap();
 
-            c._publicar_producto(accounts.bob, "Mouse".into(), "Gaming".into(), 200, 2, "Perifericos".into()).unwrap();
-            let oid = c._crear_orden(accounts.alice, 1, 1).unwrap();
+            c._crear_orden(accounts.alice, pid, 3).unwrap();
+            assert_eq!(c._crear_orden(accounts.alice, pid, 3), Err(ContractError::StockInsuficiente));
+            assert_eq!(c.stock_disponible(pid), Some(2));
+            assert_eq!(c.stock_disponible(999), None);
 
-            assert_eq!(c._marcar_enviada(accounts.bob, oid), Ok(()));
-            assert_eq!(c._marcar_recibida(accounts.alice, oid), Ok(()));
+            c._cambiar_estado_producto(accounts.bob, pid, false).unwrap();
+            assert_eq!(c.stock_disponible(pid), Some(0));
         }
 
         #[ink::test]
-        fn estado_invalido_en_transiciones() {
+        fn modificar_cantidad_orden_ajusta_stock_y_escrow() {
             let accounts = default_accounts();
             let mut c = init_contract();
+            test::set_callee::<DefaultEnvironment>(accounts.frank);
+            test::set_account_balance::<DefaultEnvironment>(accounts.frank, 1_000_000);
             c._registrar_usuario(accounts.alice, Roles::Comprador).unwrap();
             c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
+            let pid = c._publicar_producto(accounts.bob, "P".into(), "D".into(), 10, 5, "Cat".into(), None).unwrap();
+            let oid = c._crear_orden(accounts.alice, pid, 2).unwrap();
 
-            c._publicar_producto(accounts.bob, "K".into(), "J".into(), 2, 2, "Z".into()).unwrap();
-            let oid = c._crear_orden(accounts.alice, 1, 1).unwrap();
-
-            c._marcar_enviada(accounts.bob, oid).unwrap();
-            assert_eq!(c._marcar_enviada(accounts.bob, oid), Err(ContractError::EstadoInvalido));
-            assert_eq!(c._marcar_recibida(accounts.bob, oid), Err(ContractError::NoAutorizado));
-            assert_eq!(c._marcar_recibida(accounts.alice, oid), Ok(()));
-            assert_eq!(c._marcar_recibida(accounts.alice, oid), Err(ContractError::EstadoInvalido));
+            // Aumentar requiere pagar exactamente la diferencia
+            assert_eq!(c._modificar_cantidad_orden(accounts.alice, oid, 4, 10), Err(ContractError::PagoIncorrecto));
+            assert_eq!(c._modificar_cantidad_orden(accounts.alice, oid, 6, 40), Err(ContractError::StockInsuficiente));
+            assert_eq!(c._modificar_cantidad_orden(accounts.alice, oid, 4, 20), Ok(()));
+            assert_eq!(c.stock_disponible(pid), Some(1));
+            assert_eq!(c.liabilidad_reembolsos(), 40);
+
+            // Disminuir devuelve stock y reembolsa al comprador
+            let saldo = test::get_account_balance::<DefaultEnvironment>(accounts.alice).unwrap();
+            assert_eq!(c._modificar_cantidad_orden(accounts.alice, oid, 1, 0), Ok(()));
+            assert_eq!(test::get_account_balance::<DefaultEnvironment>(accounts.alice).unwrap(), saldo + 30);
+            let orden = c.obtener_orden(oid).unwrap();
+            assert_eq!((orden.cantidad, orden.monto), (1, 10));
+            assert_eq!(c.stock_disponible(pid), Some(4));
+            assert_eq!(c.liabilidad_reembolsos(), 10);
         }
 
         #[ink::test]
-        fn cancelacion_mutua_funciona() {
+        fn modificar_cantidad_orden_valida_comprador_estado_y_cantidad() {
             let accounts = default_accounts();
             let mut c = init_contract();
             c._registrar_usuario(accounts.alice, Roles::Comprador).unwrap();
             c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
+            let pid = c._publicar_producto(accounts.bob, "P".into(), "D".into(), 10, 5, "Cat".into(), None).unwrap();
+            let oid = c._crear_orden(accounts.alice, pid, 2).unwrap();
 
-            c._publicar_producto(accounts.bob, "Café".into(), "Molido".into(), 100, 1, "Alimentos".into()).unwrap();
-            let oid = c._crear_orden(accounts.alice, 1, 1).unwrap();
+            assert_eq!(c._modificar_cantidad_orden(accounts.bob, oid, 1, 0), Err(ContractError::NoAutorizado));
+            assert_eq!(c._modificar_cantidad_orden(accounts.alice, oid, 0, 0), Err(ContractError::DatosInvalidos));
 
-            assert_eq!(c._solicitar_cancel_comprador(accounts.alice, oid), Ok(()));
-            assert_eq!(c._aceptar_cancel_vendedor(accounts.bob, oid), Ok(()));
+            c._marcar_enviada(accounts.bob, oid, None).unwrap();
+            assert_eq!(c._modificar_cantidad_orden(accounts.alice, oid, 1, 0), Err(ContractError::EstadoInvalido));
+            assert_eq!(c.obtener_orden(oid).unwrap().cantidad, 2);
+        }
 
-            let orden = c.ordenes.get(oid).unwrap();
-            assert_eq!(orden.estado, EstadoOrden::Cancelada);
+        #[ink::test]
+        fn usuarios_con_reputacion_paginado_recorre_por_indice_de_registro() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            c._registrar_usuario(accounts.alice, Roles::Comprador).unwrap();
+            c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
+            c._registrar_usuario(accounts.charlie, Roles::Comprador).unwrap();
+            let pid = c._publicar_producto(accounts.bob, "P".into(), "D".into(), 10, 5, "Cat".into(), None).unwrap();
+            let oid = c._crear_orden(accounts.charlie, pid, 1).unwrap();
+            c._marcar_enviada(accounts.bob, oid, None).unwrap();
+            c._marcar_recibida(accounts.charlie, oid).unwrap();
+            c._calificar_vendedor(accounts.charlie, oid, 4, None).unwrap();
+            c._calificar_comprador(accounts.bob, oid, 5, None).unwrap();
+
+            assert_eq!(c.total_usuarios(), 3);
+            // Alice (índice 0) no tiene reputación: la primera página solo trae a Bob
+            let pagina: Vec<AccountId> = c.usuarios_con_reputacion_paginado(0, 2).into_iter().map(|(u, _)| u).collect();
+            assert_eq!(pagina, vec![accounts.bob]);
+            let pagina: Vec<AccountId> = c.usuarios_con_reputacion_paginado(2, 2).into_iter().map(|(u, _)| u).collect();
+            assert_eq!(pagina, vec![accounts.charlie]);
+            assert!(c.usuarios_con_reputacion_paginado(3, 10).is_empty());
+            assert_eq!(c.usuarios_con_reputacion_paginado(0, u32::MAX), c.obtener_usuarios_con_reputacion());
         }
 
         #[ink::test]
-        fn solo_uno_cancela_no_avanza_estado() {
+        fn total_usuarios_cuenta_registros_unicos() {
             let accounts = default_accounts();
             let mut c = init_contract();
+            assert_eq!(c.total_usuarios(), 0);
             c._registrar_usuario(accounts.alice, Roles::Comprador).unwrap();
             c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
+            assert!(c._registrar_usuario(accounts.bob, Roles::Vendedor).is_err());
+            assert_eq!(c.total_usuarios(), 2);
+        }
 
-            c._publicar_producto(accounts.bob, "Mate".into(), "Dulce".into(), 100, 1, "Bebidas".into()).unwrap();
-            let oid = c._crear_orden(accounts.alice, 1, 1).unwrap();
+        #[ink::test]
+        fn cancelaciones_usuario_cuenta_una_vez_por_orden_cancelada() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            test::set_callee::<DefaultEnvironment>(accounts.frank);
+            test::set_account_balance::<DefaultEnvironment>(accounts.frank, 1_000_000);
+            c._registrar_usuario(accounts.django, Roles::Comprador).unwrap();
+            c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
+            let pid = c._publicar_producto(accounts.bob, "P".into(), "D".into(), 10, 10, "Cat".into(), None).unwrap();
+            let oid = c._crear_orden(accounts.django, pid, 4).unwrap();
+
+            // Una cancelación parcial no cuenta
+            c._solicitar_cancelacion_parcial(accounts.django, oid, 1).unwrap();
+            c._aceptar_cancelacion_parcial(accounts.bob, oid).unwrap();
+            assert_eq!(c.cancelaciones_usuario(accounts.django), 0);
+
+            // Cancelar el resto cierra la orden y cuenta para ambas partes
+            c._solicitar_cancelacion_parcial(accounts.django, oid, 3).unwrap();
+            c._aceptar_cancelacion_parcial(accounts.bob, oid).unwrap();
+            assert_eq!(c.obtener_orden(oid).unwrap().estado, EstadoOrden::Cancelada);
+            assert_eq!(c.cancelaciones_usuario(accounts.django), 1);
+            assert_eq!(c.cancelaciones_usuario(accounts.bob), 1);
+            assert_eq!(c.cancelaciones_usuario(accounts.charlie), 0);
+        }
 
-            c._solicitar_cancel_comprador(accounts.alice, oid).unwrap();
-            let orden = c.ordenes.get(oid).unwrap();
-            assert_eq!(orden.estado, EstadoOrden::Pendiente);
+        #[ink::test]
+        fn promedio_escalado_conserva_decimales() {
+            let mut reputacion = ReputacionData::default();
+            assert_eq!(reputacion.promedio_vendedor_escalado(), None);
+            reputacion.agregar_calificacion_vendedor(5).unwrap();
+            reputacion.agregar_calificacion_vendedor(4).unwrap();
+            reputacion.agregar_calificacion_comprador(5).unwrap();
+            reputacion.agregar_calificacion_comprador(5).unwrap();
+            reputacion.agregar_calificacion_comprador(4).unwrap();
+
+            assert_eq!(reputacion.promedio_vendedor(), Some(4));
+            assert_eq!(reputacion.promedio_vendedor_escalado(), Some(450));
+            assert_eq!(reputacion.promedio_comprador_escalado(), Some(466));
         }
 
         #[ink::test]
-        fn acciones_sobre_orden_inexistente_fallan() {
+        fn vendedor_rechaza_orden_pendiente_devuelve_stock_y_escrow() {
             let accounts = default_accounts();
             let mut c = init_contract();
-            assert_eq!(c._marcar_enviada(accounts.bob, 123), Err(ContractError::OrdenNoExiste));
-            assert_eq!(c._marcar_recibida(accounts.alice, 123), Err(ContractError::OrdenNoExiste));
-            assert_eq!(c._aceptar_cancel_vendedor(accounts.bob, 123), Err(ContractError::OrdenNoExiste));
-            assert_eq!(c._solicitar_cancel_comprador(accounts.alice, 123), Err(ContractError::OrdenNoExiste));
+            test::set_callee::<DefaultEnvironment>(accounts.frank);
+            test::set_account_balance::<DefaultEnvironment>(accounts.frank, 1_000_000);
+            c._registrar_usuario(accounts.django, Roles::Comprador).unwrap();
+            c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
+            let pid = c._publicar_producto(accounts.bob, "P".into(), "D".into(), 10, 5, "Cat".into(), None).unwrap();
+            let oid = c._crear_orden(accounts.django, pid, 3).unwrap();
+
+            assert_eq!(c._vendedor_rechaza_orden(accounts.django, oid), Err(ContractError::NoAutorizado));
+
+            let saldo = test::get_account_balance::<DefaultEnvironment>(accounts.django).unwrap();
+            assert_eq!(c._vendedor_rechaza_orden(accounts.bob, oid), Ok(()));
+            assert_eq!(c.obtener_orden(oid).unwrap().estado, EstadoOrden::Cancelada);
+            assert_eq!(c.stock_disponible(pid), Some(5));
+            assert_eq!(test::get_account_balance::<DefaultEnvironment>(accounts.django).unwrap(), saldo + 30);
+            assert_eq!(c.liabilidad_reembolsos(), 0);
         }
 
         #[ink::test]
-        fn ver_mis_productos_y_todos_funciona() {
+        fn vendedor_rechaza_orden_falla_si_ya_fue_enviada() {
             let accounts = default_accounts();
             let mut c = init_contract();
+            c._registrar_usuario(accounts.alice, Roles::Comprador).unwrap();
             c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
-            c._publicar_producto(accounts.bob, "A".into(), "B".into(), 1, 1, "X".into()).unwrap();
-            c._publicar_producto(accounts.bob, "C".into(), "D".into(), 1, 1, "Y".into()).unwrap();
+            let pid = c._publicar_producto(accounts.bob, "P".into(), "D".into(), 10, 5, "Cat".into(), None).unwrap();
+            let oid = c._crear_orden(accounts.alice, pid, 1).unwrap();
+            c._marcar_enviada(accounts.bob, oid, None).unwrap();
 
-            let personales = c._ver_mis_productos(accounts.bob);
-            assert_eq!(personales.len(), 2);
+            assert_eq!(c._vendedor_rechaza_orden(accounts.bob, oid), Err(ContractError::EstadoInvalido));
+            assert_eq!(c._vendedor_rechaza_orden(accounts.bob, 999), Err(ContractError::OrdenNoExiste));
+        }
 
-            let todos = c._ver_todos_los_productos();
-            assert_eq!(todos.len(), 2);
+        #[ink::test]
+        fn favoritos_agregar_quitar_y_omitir_eliminados() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
+            let p1 = c._publicar_producto(accounts.bob, "P".into(), "D".into(), 10, 5, "Cat".into(), None).unwrap();
+            let p2 = c._publicar_producto(accounts.bob, "Q".into(), "D".into(), 10, 5, "Cat".into(), None).unwrap();
+
+            assert_eq!(c._agregar_favorito(accounts.alice, 999), Err(ContractError::ProductoNoEncontrado));
+            c._agregar_favorito(accounts.alice, p1).unwrap();
+            c._agregar_favorito(accounts.alice, p2).unwrap();
+            assert_eq!(c._agregar_favorito(accounts.alice, p1), Err(ContractError::YaEnFavoritos));
+            assert_eq!(c._ver_favoritos(accounts.alice).len(), 2);
+
+            c._eliminar_producto(accounts.bob, p2).unwrap();
+            let favoritos: Vec<u128> = c._ver_favoritos(accounts.alice).into_iter().map(|(id, _)| id).collect();
+            assert_eq!(favoritos, vec![p1]);
+
+            assert_eq!(c._quitar_favorito(accounts.alice, p1), Ok(()));
+            assert_eq!(c._quitar_favorito(accounts.alice, p1), Err(ContractError::ProductoNoEncontrado));
+            assert!(c._ver_favoritos(accounts.alice).is_empty());
         }
 
         #[ink::test]
-        fn overflow_en_productos_y_ordenes() {
+        fn envio_parcial_pasa_a_enviado_al_completar_la_cantidad() {
             let accounts = default_accounts();
             let mut c = init_contract();
-            c.siguiente_producto_id = u128::MAX;
-            c.siguiente_orden_id = u128::MAX;
             c._registrar_usuario(accounts.alice, Roles::Comprador).unwrap();
             c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
-            let prod = c._publicar_producto(accounts.bob, "Z".into(), "Z".into(), 1, 1, "Z".into());
-            assert_eq!(prod, Err(ContractError::Overflow));
+            let pid = c._publicar_producto(accounts.bob, "P".into(), "D".into(), 10, 5, "Cat".into(), None).unwrap();
+            let oid = c._crear_orden(accounts.alice, pid, 5).unwrap();
 
-            c.siguiente_producto_id = 1;
-            c._publicar_producto(accounts.bob, "A".into(), "B".into(), 1, 1, "C".into()).unwrap();
-            let orden = c._crear_orden(accounts.alice, 1, 1);
-            assert_eq!(orden, Err(ContractError::Overflow));
+            assert_eq!(c._marcar_enviada(accounts.bob, oid, Some(2)), Ok(()));
+            assert_eq!(c.obtener_orden(oid).unwrap().estado, EstadoOrden::ParcialmenteEnviado);
+            // El comprador no puede confirmar hasta que se envíe todo
+            assert_eq!(c._marcar_recibida(accounts.alice, oid), Err(ContractError::EstadoInvalido));
+            assert_eq!(c._marcar_enviada(accounts.bob, oid, Some(4)), Err(ContractError::EstadoInvalido));
+
+            assert_eq!(c._marcar_enviada(accounts.bob, oid, Some(3)), Ok(()));
+            let orden = c.obtener_orden(oid).unwrap();
+            assert_eq!((orden.estado, orden.cantidad_enviada), (EstadoOrden::Enviado, 5));
+            assert_eq!(c._marcar_recibida(accounts.alice, oid), Ok(()));
+        }
+
+        #[ink::test]
+        fn envio_sin_cantidad_despacha_lo_que_falta() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            c._registrar_usuario(accounts.alice, Roles::Comprador).unwrap();
+            c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
+            let pid = c._publicar_producto(accounts.bob, "P".into(), "D".into(), 10, 5, "Cat".into(), None).unwrap();
+            let oid = c._crear_orden(accounts.alice, pid, 4).unwrap();
+
+            assert_eq!(c._marcar_enviada(accounts.bob, oid, Some(0)), Err(ContractError::DatosInvalidos));
+            c._marcar_enviada(accounts.bob, oid, Some(1)).unwrap();
+            c._marcar_enviada(accounts.bob, oid, None).unwrap();
+            assert_eq!(c.obtener_orden(oid).unwrap().estado, EstadoOrden::Enviado);
+            assert_eq!(c._marcar_enviada(accounts.bob, oid, None), Err(ContractError::EstadoInvalido));
+        }
+
+        #[ink::test]
+        fn tiempo_envio_promedio_considera_solo_ordenes_enviadas() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            c._registrar_usuario(accounts.alice, Roles::Comprador).unwrap();
+            c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
+            let pid = c._publicar_producto(accounts.bob, "P".into(), "D".into(), 10, 10, "Cat".into(), None).unwrap();
+            assert_eq!(c.tiempo_envio_promedio(accounts.bob), None);
+
+            test::set_block_timestamp::<DefaultEnvironment>(1000);
+            let o1 = c._crear_orden(accounts.alice, pid, 1).unwrap();
+            let o2 = c._crear_orden(accounts.alice, pid, 2).unwrap();
+            c._crear_orden(accounts.alice, pid, 1).unwrap();
+
+            test::set_block_timestamp::<DefaultEnvironment>(3000);
+            c._marcar_enviada(accounts.bob, o1, None).unwrap();
+            // Un envío parcial no cuenta hasta completarse
+            c._marcar_enviada(accounts.bob, o2, Some(1)).unwrap();
+            test::set_block_timestamp::<DefaultEnvironment>(7000);
+            c._marcar_enviada(accounts.bob, o2, None).unwrap();
+
+            assert_eq!(c.obtener_orden(o1).unwrap().enviada_en, Some(3000));
+            assert_eq!(c.tiempo_envio_promedio(accounts.bob), Some(4000));
+        }
+
+        #[ink::test]
+        fn cupon_reduce_el_pago_exigido_y_el_escrow() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
+            c._registrar_usuario(accounts.charlie, Roles::Comprador).unwrap();
+            let pid = c._publicar_producto(accounts.bob, "P".into(), "D".into(), 100, 5, "Cat".into(), None).unwrap();
+            c._crear_cupon(accounts.alice, "PROMO".into(), 2_500).unwrap();
+
+            test::set_caller::<DefaultEnvironment>(accounts.charlie);
+            test::set_value_transferred::<DefaultEnvironment>(200);
+            assert_eq!(
+                c.crear_orden_de_compra(pid, 2, Some("OTRO".into())),
+                Err(ContractError::CuponInvalido)
+            );
+            assert_eq!(
+                c.crear_orden_de_compra(pid, 2, Some("PROMO".into())),
+                Err(ContractError::PagoIncorrecto)
+            );
+
+            test::set_value_transferred::<DefaultEnvironment>(150);
+            let oid = c.crear_orden_de_compra(pid, 2, Some("PROMO".into())).unwrap();
+            assert_eq!(c.obtener_orden(oid).unwrap().monto, 150);
+            assert_eq!(c.liabilidad_reembolsos(), 150);
         }
 
-        // ===== Tests for public message dispatchers =====
         #[ink::test]
-        fn public_registrar_usuario_mensaje() {
+        fn cupones_solo_owner_y_descuento_valido() {
             let accounts = default_accounts();
             let mut c = init_contract();
-            // Alice registers
-            test::set_caller::<DefaultEnvironment>(accounts.alice);
-            assert_eq!(c.registrar_usuario(Roles::Comprador), Ok(()));
-            // Alice duplicate registration
-            assert_eq!(c.registrar_usuario(Roles::Vendedor), Err(ContractError::YaRegistrado));
+            assert_eq!(c._crear_cupon(accounts.bob, "PROMO".into(), 100), Err(ContractError::NoAutorizado));
+            assert_eq!(c._crear_cupon(accounts.alice, "PROMO".into(), 10_001), Err(ContractError::CuponInvalido));
+            assert_eq!(c._crear_cupon(accounts.alice, "PROMO".into(), 10_000), Ok(()));
+            assert_eq!(c.obtener_cupon("PROMO".into()), Some(10_000));
+
+            assert_eq!(c._eliminar_cupon(accounts.bob, "PROMO".into()), Err(ContractError::NoAutorizado));
+            assert_eq!(c._eliminar_cupon(accounts.alice, "PROMO".into()), Ok(()));
+            assert_eq!(c._eliminar_cupon(accounts.alice, "PROMO".into()), Err(ContractError::CuponInvalido));
+            assert_eq!(c.obtener_cupon("PROMO".into()), None);
         }
 
         #[ink::test]
-        fn public_modificar_rol_mensaje() {
+        fn resumen_producto_junta_producto_ventas_y_calificacion() {
             let accounts = default_accounts();
             let mut c = init_contract();
-            test::set_caller::<DefaultEnvironment>(accounts.bob);
-            assert_eq!(c.modificar_rol(Roles::Vendedor), Err(ContractError::UsuarioNoRegistrado));
-            // Register and modify
-            assert_eq!(c.registrar_usuario(Roles::Ambos), Ok(()));
-            assert_eq!(c.modificar_rol(Roles::Vendedor), Ok(()));
+            c._registrar_usuario(accounts.alice, Roles::Comprador).unwrap();
+            c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
+            let pid = c._publicar_producto(accounts.bob, "P".into(), "D".into(), 10, 5, "Cat".into(), None).unwrap();
+            assert_eq!(c.resumen_producto(999), None);
+
+            let (_, ventas, promedio) = c.resumen_producto(pid).unwrap();
+            assert_eq!((ventas, promedio), (0, None));
+
+            let oid = c._crear_orden(accounts.alice, pid, 2).unwrap();
+            c._marcar_enviada(accounts.bob, oid, None).unwrap();
+            c._marcar_recibida(accounts.alice, oid).unwrap();
+            c._calificar_vendedor(accounts.alice, oid, 4, None).unwrap();
+
+            let (producto, ventas, promedio) = c.resumen_producto(pid).unwrap();
+            assert_eq!(producto.cantidad, 3);
+            assert_eq!((ventas, promedio), (1, Some(4)));
         }
 
         #[ink::test]
-        fn public_obtener_rol_mensaje() {
+        fn cancelar_dos_veces_no_devuelve_stock_de_nuevo() {
             let accounts = default_accounts();
             let mut c = init_contract();
-            // None
-            assert_eq!(c.obtener_rol(accounts.charlie), None);
-            // After registration
-            test::set_caller::<DefaultEnvironment>(accounts.charlie);
-            c.registrar_usuario(Roles::Ambos).unwrap();
-            assert_eq!(c.obtener_rol(accounts.charlie), Some(Roles::Ambos));
+            c._registrar_usuario(accounts.alice, Roles::Comprador).unwrap();
+            c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
+            let pid = c._publicar_producto(accounts.bob, "P".into(), "D".into(), 10, 5, "Cat".into(), None).unwrap();
+            let oid = c._crear_orden(accounts.alice, pid, 2).unwrap();
+
+            c._solicitar_cancel_comprador(accounts.alice, oid).unwrap();
+            c._aceptar_cancel_vendedor(accounts.bob, oid).unwrap();
+            assert_eq!(c.obtener_orden(oid).unwrap().estado, EstadoOrden::Cancelada);
+            assert_eq!(c.stock_disponible(pid), Some(5));
+
+            assert_eq!(c._solicitar_cancel_comprador(accounts.alice, oid), Err(ContractError::EstadoInvalido));
+            assert_eq!(c._aceptar_cancel_vendedor(accounts.bob, oid), Err(ContractError::EstadoInvalido));
+            assert_eq!(c.stock_disponible(pid), Some(5));
+            assert_eq!(c.cancelaciones_usuario(accounts.alice), 1);
         }
 
         #[ink::test]
-        fn public_publicar_producto_mensaje() {
+        fn ordenes_globales_por_estado_pagina_y_exige_owner() {
             let accounts = default_accounts();
             let mut c = init_contract();
-            test::set_caller::<DefaultEnvironment>(accounts.alice);
-            // Fail without role
-            assert_eq!(c.publicar_producto("X".into(), "Y".into(), 10,1,"C".into()), Err(ContractError::NoVendedor));
-            // Register and publish
-            c.registrar_usuario(Roles::Vendedor).unwrap();
-            let pid = c.publicar_producto("X".into(),"Y".into(),10,1,"C".into()).unwrap();
-            assert_eq!(pid, 1);
+            c._registrar_usuario(accounts.charlie, Roles::Comprador).unwrap();
+            c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
+            let pid = c._publicar_producto(accounts.bob, "P".into(), "D".into(), 10, 10, "Cat".into(), None).unwrap();
+            let mut ids = Vec::new();
+            for _ in 0..4 {
+                ids.push(c._crear_orden(accounts.charlie, pid, 1).unwrap());
+            }
+            for oid in [ids[0], ids[2], ids[3]] {
+                c._marcar_enviada(accounts.bob, oid, None).unwrap();
+            }
+
+            assert_eq!(
+                c._ordenes_globales_por_estado(accounts.bob, EstadoOrden::Enviado, 0, 10),
+                Err(ContractError::NoAutorizado)
+            );
+            let pagina: Vec<u128> = c._ordenes_globales_por_estado(accounts.alice, EstadoOrden::Enviado, 0, 2)
+                .unwrap().into_iter().map(|(id, _)| id).collect();
+            assert_eq!(pagina, vec![ids[0], ids[2]]);
+            let pagina: Vec<u128> = c._ordenes_globales_por_estado(accounts.alice, EstadoOrden::Enviado, ids[2] + 1, 2)
+                .unwrap().into_iter().map(|(id, _)| id).collect();
+            assert_eq!(pagina, vec![ids[3]]);
         }
 
         #[ink::test]
-        fn public_ver_productos_mensaje() {
+        fn categorias_restringidas_solo_aceptan_las_habilitadas() {
             let accounts = default_accounts();
-            let mut c = init_contract();
-            test::set_caller::<DefaultEnvironment>(accounts.alice);
-            c.registrar_usuario(Roles::Vendedor).unwrap();
-            c.publicar_producto("A".into(),"B".into(),1,1,"Cat".into()).unwrap();
-            // ver_mis
-            let own = c.ver_mis_productos();
-            assert_eq!(own.len(),1);
-            // ver_todos
-            let all = c.ver_todos_los_productos();
-            assert_eq!(all.len(),1);
+            let mut c = Marketplace::new(0, TIMEOUT_ENVIO_TEST, true, None, 0).unwrap();
+            c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
+
+            assert_eq!(
+                c._publicar_producto(accounts.bob, "P".into(), "D".into(), 10, 5, "Ropa".into(), None),
+                Err(ContractError::CategoriaNoPermitida)
+            );
+            assert_eq!(c._agregar_categoria(accounts.bob, "Ropa".into()), Err(ContractError::NoAutorizado));
+            c._agregar_categoria(accounts.alice, "Ropa".into()).unwrap();
+            let pid = c._publicar_producto(accounts.bob, "P".into(), "D".into(), 10, 5, "Ropa".into(), None).unwrap();
+
+            // Editar hacia una categoría no habilitada tampoco está permitido
+            assert_eq!(
+                c._editar_producto(accounts.bob, pid, "D".into(), "ropa".into()),
+                Err(ContractError::CategoriaNoPermitida)
+            );
+
+            c._quitar_categoria(accounts.alice, "Ropa".into()).unwrap();
+            assert!(!c.categoria_permitida("Ropa".into()));
+            assert_eq!(c._quitar_categoria(accounts.alice, "Ropa".into()), Err(ContractError::CategoriaNoPermitida));
+            // El producto existente se conserva y se puede editar sin cambiar de categoría
+            assert_eq!(c._editar_producto(accounts.bob, pid, "Nueva".into(), "Ropa".into()), Ok(()));
         }
 
         #[ink::test]
-        fn public_crear_orden_de_compra_mensaje() {
+        fn sin_restriccion_cualquier_categoria_es_valida() {
             let accounts = default_accounts();
             let mut c = init_contract();
-            // setup
-            test::set_caller::<DefaultEnvironment>(accounts.alice);
-            c.registrar_usuario(Roles::Vendedor).unwrap();
-            let pid = c.publicar_producto("P".into(),"D".into(),5,2,"Cat".into()).unwrap();
-            test::set_caller::<DefaultEnvironment>(accounts.bob);
-            c.registrar_usuario(Roles::Comprador).unwrap();
-            let oid = c.crear_orden_de_compra(pid,1).unwrap();
-            assert_eq!(oid,1);
+            c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
+            assert!(c.categoria_permitida("Cualquiera".into()));
+            assert!(c._publicar_producto(accounts.bob, "P".into(), "D".into(), 10, 5, "Cualquiera".into(), None).is_ok());
         }
 
         #[ink::test]
-        fn public_marcar_enviada_recibida_mensajes() {
+        fn con_token_psp22_no_se_acepta_valor_nativo() {
             let accounts = default_accounts();
-            let mut c = init_contract();
-            // prepare
-            test::set_caller::<DefaultEnvironment>(accounts.alice);
-            c.registrar_usuario(Roles::Vendedor).unwrap();
-            let pid = c.publicar_producto("P".into(),"D".into(),5,1,"Cat".into()).unwrap();
-            test::set_caller::<DefaultEnvironment>(accounts.bob);
-            c.registrar_usuario(Roles::Comprador).unwrap();
-            let oid = c.crear_orden_de_compra(pid,1).unwrap();
-            // send
-            test::set_caller::<DefaultEnvironment>(accounts.alice);
-            assert_eq!(c.marcar_orden_enviada(oid), Ok(()));
-            // receive
-            test::set_caller::<DefaultEnvironment>(accounts.bob);
-            assert_eq!(c.marcar_orden_recibida(oid), Ok(()));
+            let mut c = Marketplace::new(0, TIMEOUT_ENVIO_TEST, false, Some(accounts.eve), 0).unwrap();
+            assert_eq!(c.token(), Some(accounts.eve));
+            c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
+            c._registrar_usuario(accounts.charlie, Roles::Comprador).unwrap();
+            let pid = c._publicar_producto(accounts.bob, "P".into(), "D".into(), 100, 5, "Cat".into(), None).unwrap();
+
+            test::set_caller::<DefaultEnvironment>(accounts.charlie);
+            test::set_value_transferred::<DefaultEnvironment>(100);
+            assert_eq!(c.crear_orden_de_compra(pid, 1, None), Err(ContractError::PagoIncorrecto));
+            assert_eq!(c.stock_disponible(pid), Some(5));
+            assert_eq!(init_contract().token(), None);
         }
 
         #[ink::test]
-        fn public_cancelacion_mensajes() {
+        fn reputacion_global_combina_ambos_roles() {
             let accounts = default_accounts();
             let mut c = init_contract();
-            test::set_caller::<DefaultEnvironment>(accounts.alice);
-            c.registrar_usuario(Roles::Vendedor).unwrap();
-            let pid = c.publicar_producto("C".into(),"D".into(),5,1,"Cat".into()).unwrap();
-            test::set_caller::<DefaultEnvironment>(accounts.bob);
-            c.registrar_usuario(Roles::Comprador).unwrap();
-            let oid = c.crear_orden_de_compra(pid,1).unwrap();
-            // comprador solicita
-            assert_eq!(c.comprador_solicita_cancelacion(oid), Ok(()));
-            // vendedor acepta
-            test::set_caller::<DefaultEnvironment>(accounts.alice);
-            assert_eq!(c.vendedor_acepta_cancelacion(oid), Ok(()));
+            c._registrar_usuario(accounts.charlie, Roles::Comprador).unwrap();
+            c._registrar_usuario(accounts.bob, Roles::Ambos).unwrap();
+            c._registrar_usuario(accounts.django, Roles::Vendedor).unwrap();
+            let pid_bob = c._publicar_producto(accounts.bob, "P".into(), "D".into(), 10, 5, "Cat".into(), None).unwrap();
+            let pid_django = c._publicar_producto(accounts.django, "Q".into(), "D".into(), 10, 5, "Cat".into(), None).unwrap();
+            assert_eq!(c.reputacion_global(accounts.bob), None);
+
+            // Charlie compra a Bob: Bob recibe 5 como vendedor, Charlie 2 como comprador
+            let oid = c._crear_orden(accounts.charlie, pid_bob, 1).unwrap();
+            c._marcar_enviada(accounts.bob, oid, None).unwrap();
+            c._marcar_recibida(accounts.charlie, oid).unwrap();
+            c._calificar_vendedor(accounts.charlie, oid, 5, None).unwrap();
+            c._calificar_comprador(accounts.bob, oid, 2, None).unwrap();
+
+            // Bob compra a Django: Bob recibe 2 como comprador
+            let oid = c._crear_orden(accounts.bob, pid_django, 1).unwrap();
+            c._marcar_enviada(accounts.django, oid, None).unwrap();
+            c._marcar_recibida(accounts.bob, oid).unwrap();
+            c._calificar_comprador(accounts.django, oid, 2, None).unwrap();
+
+            // Solo como comprador, solo como vendedor y ambos: (5 + 2) / 2 = 3
+            assert_eq!(c.reputacion_global(accounts.charlie), Some(2));
+            assert_eq!(c.reputacion_global(accounts.django), None);
+            assert_eq!(c.reputacion_global(accounts.bob), Some(3));
         }
 
-        // ===== Tests adicionales solicitados =====
+        #[ink::test]
+        fn promedio_global_de_solo_vendedor() {
+            let mut reputacion = ReputacionData::default();
+            reputacion.agregar_calificacion_vendedor(4).unwrap();
+            reputacion.agregar_calificacion_vendedor(5).unwrap();
+            assert_eq!(reputacion.promedio_global(), Some(4));
+        }
 
         #[ink::test]
-        fn registrar_usuario_como_vendedor() {
+        fn marcar_recibida_y_calificar_es_atomico() {
             let accounts = default_accounts();
             let mut c = init_contract();
-            assert_eq!(c._registrar_usuario(accounts.alice, Roles::Vendedor), Ok(()));
-            assert_eq!(c._obtener_rol(accounts.alice), Some(Roles::Vendedor));
+            c._registrar_usuario(accounts.charlie, Roles::Comprador).unwrap();
+            c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
+            let pid = c._publicar_producto(accounts.bob, "P".into(), "D".into(), 10, 5, "Cat".into(), None).unwrap();
+            let oid = c._crear_orden(accounts.charlie, pid, 1).unwrap();
+            c._marcar_enviada(accounts.bob, oid, None).unwrap();
+
+            assert_eq!(
+                c._marcar_recibida_y_calificar(accounts.charlie, oid, 6),
+                Err(ContractError::CalificacionInvalida)
+            );
+            assert_eq!(c.obtener_orden(oid).unwrap().estado, EstadoOrden::Enviado);
+
+            assert_eq!(c._marcar_recibida_y_calificar(accounts.charlie, oid, 4), Ok(()));
+            assert_eq!(c.obtener_orden(oid).unwrap().estado, EstadoOrden::Recibido);
+            assert_eq!(c.estado_calificacion_orden(oid), Some((true, false)));
+            assert_eq!(c.reputacion_como_vendedor(accounts.bob), Some(4));
         }
 
         #[ink::test]
-        fn registrar_usuario_como_ambos() {
+        fn cantidad_productos_vendedor_cuenta_solo_activos() {
             let accounts = default_accounts();
             let mut c = init_contract();
-            assert_eq!(c._registrar_usuario(accounts.alice, Roles::Ambos), Ok(()));
-            assert_eq!(c._obtener_rol(accounts.alice), Some(Roles::Ambos));
+            c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
+            let p1 = c._publicar_producto(accounts.bob, "A".into(), "D".into(), 10, 5, "Cat".into(), None).unwrap();
+            let p2 = c._publicar_producto(accounts.bob, "B".into(), "D".into(), 10, 5, "Cat".into(), None).unwrap();
+            c._publicar_producto(accounts.bob, "C".into(), "D".into(), 10, 5, "Cat".into(), None).unwrap();
+            assert_eq!(c.cantidad_productos_vendedor(accounts.bob), 3);
+
+            c._cambiar_estado_producto(accounts.bob, p1, false).unwrap();
+            c._eliminar_producto(accounts.bob, p2).unwrap();
+            assert_eq!(c.cantidad_productos_vendedor(accounts.bob), 1);
+            assert_eq!(c.cantidad_productos_vendedor(accounts.charlie), 0);
         }
 
         #[ink::test]
-        fn modificar_rol_solo_permite_agregar_no_quitar() {
+        fn motivo_cancelacion_distingue_el_origen() {
             let accounts = default_accounts();
             let mut c = init_contract();
-            // Registrar como Comprador y agregar Vendedor -> Ambos
             c._registrar_usuario(accounts.alice, Roles::Comprador).unwrap();
-            assert_eq!(c._modificar_rol(accounts.alice, Roles::Vendedor), Ok(()));
-            assert_eq!(c._obtener_rol(accounts.alice), Some(Roles::Ambos));
-            
-            // Registrar como Vendedor y agregar Comprador -> Ambos
             c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
-            assert_eq!(c._modificar_rol(accounts.bob, Roles::Comprador), Ok(()));
-            assert_eq!(c._obtener_rol(accounts.bob), Some(Roles::Ambos));
-            
-            // Si ya es Ambos, intentar cambiar a Vendedor mantiene Ambos (no quita Comprador)
-            c._registrar_usuario(accounts.charlie, Roles::Ambos).unwrap();
-            assert_eq!(c._modificar_rol(accounts.charlie, Roles::Vendedor), Ok(()));
-            assert_eq!(c._obtener_rol(accounts.charlie), Some(Roles::Ambos));
+            let pid = c._publicar_producto(accounts.bob, "P".into(), "D".into(), 10, 10, "Cat".into(), None).unwrap();
+            test::set_block_timestamp::<DefaultEnvironment>(0);
+            let mutua = c._crear_orden(accounts.alice, pid, 1).unwrap();
+            let rechazada = c._crear_orden(accounts.alice, pid, 1).unwrap();
+            let vencida = c._crear_orden(accounts.alice, pid, 1).unwrap();
+            assert_eq!(c.obtener_orden(mutua).unwrap().motivo_cancelacion, None);
+
+            c._solicitar_cancel_comprador(accounts.alice, mutua).unwrap();
+            c._aceptar_cancel_vendedor(accounts.bob, mutua).unwrap();
+            c._vendedor_rechaza_orden(accounts.bob, rechazada).unwrap();
+            test::set_block_timestamp::<DefaultEnvironment>(TIMEOUT_ENVIO_TEST + 1);
+            c._cancelar_por_inactividad(accounts.alice, vencida).unwrap();
+
+            let motivo = |oid| c.obtener_orden(oid).unwrap().motivo_cancelacion;
+            assert_eq!(motivo(mutua), Some(MotivoCancelacion::MutuoAcuerdo));
+            assert_eq!(motivo(rechazada), Some(MotivoCancelacion::RechazoVendedor));
+            assert_eq!(motivo(vencida), Some(MotivoCancelacion::TimeoutEnvio));
         }
 
         #[ink::test]
-        fn no_se_puede_publicar_con_nombre_vacio() {
+        fn reservar_stock_descuenta_y_confirmar_crea_la_orden() {
             let accounts = default_accounts();
             let mut c = init_contract();
-            c._registrar_usuario(accounts.alice, Roles::Vendedor).unwrap();
-            assert_eq!(
-                c._publicar_producto(accounts.alice, "".into(), "Desc".into(), 100, 1, "Cat".into()),
-                Err(ContractError::DatosInvalidos)
-            );
+            c._registrar_usuario(accounts.alice, Roles::Comprador).unwrap();
+            c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
+            let pid = c._publicar_producto(accounts.bob, "P".into(), "D".into(), 100, 5, "Cat".into(), None).unwrap();
+
+            assert_eq!(c._reservar_stock(accounts.alice, pid, 6, 1_000), Err(ContractError::StockInsuficiente));
+            assert_eq!(c._reservar_stock(accounts.alice, pid, 2, 0), Err(ContractError::DatosInvalidos));
+            let rid = c._reservar_stock(accounts.alice, pid, 2, 1_000).unwrap();
+            assert_eq!(c.productos.get(pid).unwrap().cantidad, 3);
+
+            assert_eq!(c._confirmar_reserva(accounts.charlie, rid, 0, 200), Err(ContractError::NoAutorizado));
+            assert_eq!(c._confirmar_reserva(accounts.alice, rid, 0, 150), Err(ContractError::PagoIncorrecto));
+            let oid = c._confirmar_reserva(accounts.alice, rid, 0, 200).unwrap();
+            assert_eq!(c.obtener_orden(oid).unwrap().cantidad, 2);
+            assert_eq!(c.productos.get(pid).unwrap().cantidad, 3);
+            assert_eq!(c.obtener_reserva(rid), None);
+            assert_eq!(c._confirmar_reserva(accounts.alice, rid, 0, 200), Err(ContractError::ReservaNoExiste));
         }
 
         #[ink::test]
-        fn no_se_puede_publicar_con_descripcion_vacia() {
+        fn liberar_reservas_vencidas_devuelve_el_stock() {
             let accounts = default_accounts();
             let mut c = init_contract();
-            c._registrar_usuario(accounts.alice, Roles::Vendedor).unwrap();
-            assert_eq!(
-                c._publicar_producto(accounts.alice, "Nombre".into(), "".into(), 100, 1, "Cat".into()),
-                Err(ContractError::DatosInvalidos)
-            );
+            c._registrar_usuario(accounts.alice, Roles::Comprador).unwrap();
+            c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
+            let pid = c._publicar_producto(accounts.bob, "P".into(), "D".into(), 100, 5, "Cat".into(), None).unwrap();
+            let corta = c._reservar_stock(accounts.alice, pid, 2, 1_000).unwrap();
+            let larga = c._reservar_stock(accounts.alice, pid, 1, 5_000).unwrap();
+            assert_eq!(c.liberar_reservas_vencidas(), 0);
+
+            test::set_block_timestamp::<DefaultEnvironment>(1_000);
+            assert_eq!(c._confirmar_reserva(accounts.alice, corta, 0, 200), Err(ContractError::ReservaVencida));
+            assert_eq!(c.liberar_reservas_vencidas(), 1);
+            assert_eq!(c.productos.get(pid).unwrap().cantidad, 4);
+            assert_eq!(c.obtener_reserva(corta), None);
+            assert!(c.obtener_reserva(larga).is_some());
+            assert_eq!(c.primera_reserva_pendiente, larga);
         }
 
         #[ink::test]
-        fn no_se_puede_publicar_con_categoria_vacia() {
+        fn forzar_recepcion_libera_el_escrow_tras_el_plazo() {
             let accounts = default_accounts();
             let mut c = init_contract();
-            c._registrar_usuario(accounts.alice, Roles::Vendedor).unwrap();
-            assert_eq!(
-                c._publicar_producto(accounts.alice, "Nombre".into(), "Desc".into(), 100, 1, "".into()),
-                Err(ContractError::DatosInvalidos)
-            );
+            test::set_callee::<DefaultEnvironment>(accounts.frank);
+            test::set_account_balance::<DefaultEnvironment>(accounts.frank, 1_000_000);
+            c._registrar_usuario(accounts.charlie, Roles::Comprador).unwrap();
+            c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
+            let pid = c._publicar_producto(accounts.bob, "P".into(), "D".into(), 100, 5, "Cat".into(), None).unwrap();
+            let oid = c._crear_orden(accounts.charlie, pid, 2).unwrap();
+            assert_eq!(c._forzar_recepcion(accounts.alice, oid), Err(ContractError::EstadoInvalido));
+            c._marcar_enviada(accounts.bob, oid, None).unwrap();
+
+            assert_eq!(c._forzar_recepcion(accounts.bob, oid), Err(ContractError::NoAutorizado));
+            test::set_block_timestamp::<DefaultEnvironment>(TIMEOUT_RECEPCION_POR_DEFECTO);
+            assert_eq!(c._forzar_recepcion(accounts.alice, oid), Err(ContractError::TimeoutNoAlcanzado));
+
+            let saldo_vendedor = test::get_account_balance::<DefaultEnvironment>(accounts.bob).unwrap();
+            test::set_block_timestamp::<DefaultEnvironment>(TIMEOUT_RECEPCION_POR_DEFECTO + 1);
+            c._forzar_recepcion(accounts.alice, oid).unwrap();
+            assert_eq!(c.obtener_orden(oid).unwrap().estado, EstadoOrden::Recibido);
+            assert_eq!(test::get_account_balance::<DefaultEnvironment>(accounts.bob).unwrap(), saldo_vendedor + 200);
         }
 
         #[ink::test]
-        fn no_se_puede_publicar_con_precio_cero() {
+        fn establecer_timeout_recepcion_solo_owner() {
             let accounts = default_accounts();
             let mut c = init_contract();
-            c._registrar_usuario(accounts.alice, Roles::Vendedor).unwrap();
-            assert_eq!(
-                c._publicar_producto(accounts.alice, "Nombre".into(), "Desc".into(), 0, 1, "Cat".into()),
-                Err(ContractError::DatosInvalidos)
-            );
+            assert_eq!(c.timeout_recepcion(), TIMEOUT_RECEPCION_POR_DEFECTO);
+            assert_eq!(c._establecer_timeout_recepcion(accounts.bob, 10), Err(ContractError::NoAutorizado));
+            c._establecer_timeout_recepcion(accounts.alice, 10).unwrap();
+            assert_eq!(c.timeout_recepcion(), 10);
         }
 
         #[ink::test]
-        fn no_se_puede_publicar_con_cantidad_cero() {
+        fn obtener_productos_devuelve_none_para_ids_faltantes() {
             let accounts = default_accounts();
             let mut c = init_contract();
-            c._registrar_usuario(accounts.alice, Roles::Vendedor).unwrap();
-            assert_eq!(
-                c._publicar_producto(accounts.alice, "Nombre".into(), "Desc".into(), 100, 0, "Cat".into()),
-                Err(ContractError::DatosInvalidos)
-            );
+            c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
+            let p1 = c._publicar_producto(accounts.bob, "A".into(), "D".into(), 100, 5, "Cat".into(), None).unwrap();
+            let p2 = c._publicar_producto(accounts.bob, "B".into(), "D".into(), 100, 5, "Cat".into(), None).unwrap();
+            c._eliminar_producto(accounts.bob, p2).unwrap();
+
+            let res = c.obtener_productos(vec![p2, p1, 99]).unwrap();
+            assert_eq!(res.len(), 3);
+            assert_eq!(res[0], (p2, None));
+            assert_eq!(res[1].1.as_ref().unwrap().nombre, "A");
+            assert_eq!(res[2], (99, None));
+
+            let demasiados: Vec<u128> = (0..=MAX_PRODUCTOS_CONSULTA as u128).collect();
+            assert_eq!(c.obtener_productos(demasiados), Err(ContractError::DatosInvalidos));
         }
 
         #[ink::test]
-        fn no_se_puede_crear_orden_con_cantidad_cero() {
+        fn terceros_no_pueden_calificar_una_orden_recibida() {
             let accounts = default_accounts();
             let mut c = init_contract();
             c._registrar_usuario(accounts.alice, Roles::Comprador).unwrap();
             c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
-            c._publicar_producto(accounts.bob, "Producto".into(), "Desc".into(), 100, 5, "Cat".into()).unwrap();
-            
-            assert_eq!(
-                c._crear_orden(accounts.alice, 1, 0),
-                Err(ContractError::StockInsuficiente)
-            );
+            c._registrar_usuario(accounts.charlie, Roles::Ambos).unwrap();
+            let pid = c._publicar_producto(accounts.bob, "P".into(), "D".into(), 100, 5, "Cat".into(), None).unwrap();
+            let oid = c._crear_orden(accounts.alice, pid, 1).unwrap();
+            c._marcar_enviada(accounts.bob, oid, None).unwrap();
+            c._marcar_recibida(accounts.alice, oid).unwrap();
+
+            assert_eq!(c._calificar_vendedor(accounts.charlie, oid, 5, None), Err(ContractError::NoAutorizado));
+            assert_eq!(c._calificar_comprador(accounts.charlie, oid, 5, None), Err(ContractError::NoAutorizado));
+            let calificaciones = c.calificaciones_por_orden.get(oid).unwrap();
+            assert_eq!(calificaciones.calificacion_comprador, None);
+            assert_eq!(calificaciones.calificacion_vendedor, None);
+            assert_eq!(c.reputaciones.get(accounts.charlie), None);
         }
 
         #[ink::test]
-        fn no_se_puede_marcar_orden_recibida_desde_pendiente() {
+        fn destacados_solo_owner_y_omiten_eliminados() {
             let accounts = default_accounts();
             let mut c = init_contract();
-            c._registrar_usuario(accounts.alice, Roles::Comprador).unwrap();
             c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
-            c._publicar_producto(accounts.bob, "Producto".into(), "Desc".into(), 100, 5, "Cat".into()).unwrap();
-            let oid = c._crear_orden(accounts.alice, 1, 1).unwrap();
-
-            // No se puede pasar directamente de Pendiente a Recibido
-            assert_eq!(
-                c._marcar_recibida(accounts.alice, oid),
-                Err(ContractError::EstadoInvalido)
-            );
+            let p1 = c._publicar_producto(accounts.bob, "A".into(), "D".into(), 100, 5, "Cat".into(), None).unwrap();
+            let p2 = c._publicar_producto(accounts.bob, "B".into(), "D".into(), 100, 5, "Cat".into(), None).unwrap();
+
+            assert_eq!(c._destacar_producto(accounts.bob, p1), Err(ContractError::NoAutorizado));
+            assert_eq!(c._destacar_producto(accounts.alice, 99), Err(ContractError::ProductoNoEncontrado));
+            c._destacar_producto(accounts.alice, p2).unwrap();
+            c._destacar_producto(accounts.alice, p1).unwrap();
+            assert_eq!(c._destacar_producto(accounts.alice, p1), Err(ContractError::YaDestacado));
+            let ids: Vec<u128> = c.ver_destacados().into_iter().map(|(id, _)| id).collect();
+            assert_eq!(ids, vec![p2, p1]);
+
+            c._eliminar_producto(accounts.bob, p2).unwrap();
+            assert_eq!(c.ver_destacados().len(), 1);
+            assert_eq!(c._quitar_destacado(accounts.bob, p1), Err(ContractError::NoAutorizado));
+            c._quitar_destacado(accounts.alice, p1).unwrap();
+            assert_eq!(c._quitar_destacado(accounts.alice, p1), Err(ContractError::ProductoNoEncontrado));
         }
 
         #[ink::test]
-        fn no_se_puede_retroceder_de_recibido_a_enviado() {
+        fn destacados_respeta_el_maximo() {
             let accounts = default_accounts();
             let mut c = init_contract();
-            c._registrar_usuario(accounts.alice, Roles::Comprador).unwrap();
             c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
-            c._publicar_producto(accounts.bob, "Producto".into(), "Desc".into(), 100, 5, "Cat".into()).unwrap();
-            let oid = c._crear_orden(accounts.alice, 1, 1).unwrap();
-
-            c._marcar_enviada(accounts.bob, oid).unwrap();
-            c._marcar_recibida(accounts.alice, oid).unwrap();
-
-            // No se puede retroceder de Recibido a Enviado
+            for _ in 0..=MAX_DESTACADOS {
+                c._publicar_producto(accounts.bob, "P".into(), "D".into(), 100, 5, "Cat".into(), None).unwrap();
+            }
+            for id in 1..=MAX_DESTACADOS as u128 {
+                c._destacar_producto(accounts.alice, id).unwrap();
+            }
             assert_eq!(
-                c._marcar_enviada(accounts.bob, oid),
-                Err(ContractError::EstadoInvalido)
+                c._destacar_producto(accounts.alice, MAX_DESTACADOS as u128 + 1),
+                Err(ContractError::DatosInvalidos)
             );
         }
 
         #[ink::test]
-        fn no_se_puede_retroceder_de_recibido_a_pendiente() {
+        fn permisos_segun_rol() {
             let accounts = default_accounts();
             let mut c = init_contract();
             c._registrar_usuario(accounts.alice, Roles::Comprador).unwrap();
             c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
-            c._publicar_producto(accounts.bob, "Producto".into(), "Desc".into(), 100, 5, "Cat".into()).unwrap();
-            let oid = c._crear_orden(accounts.alice, 1, 1).unwrap();
-
-            c._marcar_enviada(accounts.bob, oid).unwrap();
-            c._marcar_recibida(accounts.alice, oid).unwrap();
-
-            // Verificar que el estado es Recibido
-            let orden = c.ordenes.get(oid).unwrap();
-            assert_eq!(orden.estado, EstadoOrden::Recibido);
+            c._registrar_usuario(accounts.charlie, Roles::Ambos).unwrap();
+            assert_eq!(c.permisos(accounts.alice), Some((true, false)));
+            assert_eq!(c.permisos(accounts.bob), Some((false, true)));
+            assert_eq!(c.permisos(accounts.charlie), Some((true, true)));
+            assert_eq!(c.permisos(accounts.django), None);
         }
 
         #[ink::test]
-        fn cancelacion_devuelve_stock() {
+        fn precio_total_orden_usa_el_precio_actual() {
             let accounts = default_accounts();
             let mut c = init_contract();
             c._registrar_usuario(accounts.alice, Roles::Comprador).unwrap();
             c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
-            
-            // Publicar producto con stock 5
-            let pid = c._publicar_producto(
-                accounts.bob, 
-                "Producto".into(), 
-                "Desc".into(), 
-                100, 
-                5, 
-                "Cat".into()
-            ).unwrap();
-            
-            // Verificar stock inicial
-            let producto_antes = c.productos.get(pid).unwrap();
-            assert_eq!(producto_antes.cantidad, 5);
-
-            // Crear orden de 2 unidades
-            let oid = c._crear_orden(accounts.alice, pid, 2).unwrap();
-            
-            // Verificar que el stock se redujo
-            let producto_despues = c.productos.get(pid).unwrap();
-            assert_eq!(producto_despues.cantidad, 3);
-
-            // Cancelar la orden
-            c._solicitar_cancel_comprador(accounts.alice, oid).unwrap();
-            c._aceptar_cancel_vendedor(accounts.bob, oid).unwrap();
-
-            // Verificar que el stock se devolvió
-            let producto_final = c.productos.get(pid).unwrap();
-            assert_eq!(producto_final.cantidad, 5);
+            let pid = c._publicar_producto(accounts.bob, "P".into(), "D".into(), 100, 5, "Cat".into(), None).unwrap();
+            let oid = c._crear_orden(accounts.alice, pid, 3).unwrap();
+            assert_eq!(c.precio_total_orden(oid), Some(300));
+            assert_eq!(c.precio_total_orden(99), None);
+
+            let mut producto = c.productos.get(pid).unwrap();
+            producto.precio = u128::MAX;
+            c.productos.insert(pid, &producto);
+            assert_eq!(c.precio_total_orden(oid), None);
         }
 
         #[ink::test]
-        fn obtener_estado_orden_funciona() {
+        fn calificadores_vendedor_lista_compradores_y_notas() {
             let accounts = default_accounts();
             let mut c = init_contract();
             c._registrar_usuario(accounts.alice, Roles::Comprador).unwrap();
+            c._registrar_usuario(accounts.charlie, Roles::Comprador).unwrap();
             c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
-            c._publicar_producto(accounts.bob, "Producto".into(), "Desc".into(), 100, 5, "Cat".into()).unwrap();
-            
-            let oid = c._crear_orden(accounts.alice, 1, 1).unwrap();
-            
-            // Estado inicial: Pendiente
-            assert_eq!(c.obtener_estado_orden(oid), Some(EstadoOrden::Pendiente));
-            
-            // Estado después de marcar como enviada: Enviado
-            c._marcar_enviada(accounts.bob, oid).unwrap();
-            assert_eq!(c.obtener_estado_orden(oid), Some(EstadoOrden::Enviado));
-            
-            // Estado después de marcar como recibida: Recibido
-            c._marcar_recibida(accounts.alice, oid).unwrap();
-            assert_eq!(c.obtener_estado_orden(oid), Some(EstadoOrden::Recibido));
+            let pid = c._publicar_producto(accounts.bob, "P".into(), "D".into(), 100, 10, "Cat".into(), None).unwrap();
+            let o1 = c._crear_orden(accounts.alice, pid, 1).unwrap();
+            let o2 = c._crear_orden(accounts.charlie, pid, 1).unwrap();
+            let _sin_calificar = c._crear_orden(accounts.charlie, pid, 1).unwrap();
+            for oid in [o1, o2] {
+                c._marcar_enviada(accounts.bob, oid, None).unwrap();
+            }
+            c._marcar_recibida(accounts.alice, o1).unwrap();
+            c._marcar_recibida(accounts.charlie, o2).unwrap();
+            c._calificar_vendedor(accounts.alice, o1, 4, None).unwrap();
+            c._calificar_vendedor(accounts.charlie, o2, 2, None).unwrap();
+
+            assert_eq!(
+                c.calificadores_vendedor(accounts.bob),
+                vec![(accounts.alice, 4), (accounts.charlie, 2)]
+            );
+            assert_eq!(c.calificadores_vendedor_paginado(accounts.bob, 1, 1), vec![(accounts.charlie, 2)]);
+            assert_eq!(c.calificadores_vendedor_paginado(accounts.bob, 2, 5), vec![]);
+            assert_eq!(c.calificadores_vendedor(accounts.alice), vec![]);
+
+            // Con la reputación privada solo la ven el vendedor y el owner
+            c._establecer_reputacion_publica(accounts.alice, false).unwrap();
+            assert_eq!(c._calificadores_vendedor(accounts.charlie, accounts.bob, 0, 10), vec![]);
+            assert_eq!(c._calificadores_vendedor(accounts.bob, accounts.bob, 0, 10).len(), 2);
+            assert_eq!(c._calificadores_vendedor(accounts.alice, accounts.bob, 0, 10).len(), 2);
+        }
+
+        #[ink::test]
+        fn registrar_o_actualizar_rol_registra_cuentas_nuevas() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            c._registrar_o_actualizar_rol(accounts.bob, Roles::Vendedor).unwrap();
+            assert_eq!(c.obtener_rol(accounts.bob), Some(Roles::Vendedor));
+            assert_eq!(c.total_usuarios(), 1);
         }
 
         #[ink::test]
-        fn obtener_estado_orden_inexistente() {
-            let c = init_contract();
-            assert_eq!(c.obtener_estado_orden(999), None);
+        fn registrar_o_actualizar_rol_suma_roles_sin_duplicar_usuario() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            c._registrar_usuario(accounts.bob, Roles::Comprador).unwrap();
+            c._registrar_o_actualizar_rol(accounts.bob, Roles::Comprador).unwrap();
+            assert_eq!(c.obtener_rol(accounts.bob), Some(Roles::Comprador));
+            c._registrar_o_actualizar_rol(accounts.bob, Roles::Vendedor).unwrap();
+            assert_eq!(c.obtener_rol(accounts.bob), Some(Roles::Ambos));
+            assert_eq!(c.total_usuarios(), 1);
         }
 
-        // ===== Tests de sistema de reputación =====
-
         #[ink::test]
-        fn comprador_califica_vendedor_funciona() {
+        fn ingresos_reales_vendedor_usa_el_precio_de_cada_orden() {
             let accounts = default_accounts();
             let mut c = init_contract();
-            
             c._registrar_usuario(accounts.alice, Roles::Comprador).unwrap();
             c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
-            
-            let pid = c._publicar_producto(
-                accounts.bob,
-                "Producto".into(),
-                "Desc".into(),
-                100,
-                5,
-                "Cat".into()
-            ).unwrap();
-            
-            let oid = c._crear_orden(accounts.alice, pid, 1).unwrap();
-            c._marcar_enviada(accounts.bob, oid).unwrap();
-            c._marcar_recibida(accounts.alice, oid).unwrap();
-            
-            // Comprador califica al vendedor
-            assert_eq!(c.comprador_califica_vendedor(oid, 5), Ok(()));
-            
-            // Verificar que la calificación se guardó
-            let calificaciones = c.obtener_calificaciones_orden(oid).unwrap();
-            assert_eq!(calificaciones.calificacion_comprador, Some(5));
-            assert_eq!(calificaciones.calificacion_vendedor, None);
-            
-            // Verificar reputación del vendedor
-            let reputacion = c.obtener_reputacion(accounts.bob).unwrap();
-            assert_eq!(reputacion.promedio_vendedor(), Some(5));
+            let pid = c._publicar_producto(accounts.bob, "P".into(), "D".into(), 100, 10, "Cat".into(), None).unwrap();
+            let o1 = c._crear_orden(accounts.alice, pid, 2).unwrap();
+            c._actualizar_precio_producto(accounts.bob, pid, 300).unwrap();
+            let o2 = c._crear_orden(accounts.alice, pid, 1).unwrap();
+            let _pendiente = c._crear_orden(accounts.alice, pid, 1).unwrap();
+            assert_eq!(c.obtener_orden(o1).unwrap().precio_unitario, 100);
+            for oid in [o1, o2] {
+                c._marcar_enviada(accounts.bob, oid, None).unwrap();
+                c._marcar_recibida(accounts.alice, oid).unwrap();
+            }
+            c._actualizar_precio_producto(accounts.bob, pid, 1_000).unwrap();
+
+            assert_eq!(c.ingresos_reales_vendedor(accounts.bob), 2 * 100 + 300);
+            assert_eq!(c.ingresos_reales_vendedor(accounts.alice), 0);
         }
 
         #[ink::test]
-        fn vendedor_califica_comprador_funciona() {
+        fn cancelacion_parcial_tras_envio_parcial_solo_cubre_lo_no_enviado() {
             let accounts = default_accounts();
             let mut c = init_contract();
-            
-            c._registrar_usuario(accounts.alice, Roles::Comprador).unwrap();
+            test::set_callee::<DefaultEnvironment>(accounts.frank);
+            test::set_account_balance::<DefaultEnvironment>(accounts.frank, 1_000_000);
+            c._registrar_usuario(accounts.django, Roles::Comprador).unwrap();
             c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
-            
-            let pid = c._publicar_producto(
-                accounts.bob,
-                "Producto".into(),
-                "Desc".into(),
-                100,
-                5,
-                "Cat".into()
-            ).unwrap();
-            
-            let oid = c._crear_orden(accounts.alice, pid, 1).unwrap();
-            c._marcar_enviada(accounts.bob, oid).unwrap();
-            c._marcar_recibida(accounts.alice, oid).unwrap();
-            
-            // Vendedor califica al comprador
-            assert_eq!(c.vendedor_califica_comprador(oid, 4), Ok(()));
-            
-            // Verificar que la calificación se guardó
-            let calificaciones = c.obtener_calificaciones_orden(oid).unwrap();
-            assert_eq!(calificaciones.calificacion_comprador, None);
-            assert_eq!(calificaciones.calificacion_vendedor, Some(4));
-            
-            // Verificar reputación del comprador
-            let reputacion = c.obtener_reputacion(accounts.alice).unwrap();
-            assert_eq!(reputacion.promedio_comprador(), Some(4));
+            let pid = c._publicar_producto(accounts.bob, "P".into(), "D".into(), 100, 10, "Cat".into(), None).unwrap();
+            let oid = c._crear_orden(accounts.django, pid, 5).unwrap();
+            c._marcar_enviada(accounts.bob, oid, Some(3)).unwrap();
+
+            assert_eq!(c._solicitar_cancelacion_parcial(accounts.django, oid, 3), Err(ContractError::DatosInvalidos));
+            c._solicitar_cancelacion_parcial(accounts.django, oid, 2).unwrap();
+            c._aceptar_cancelacion_parcial(accounts.bob, oid).unwrap();
+
+            let orden = c.obtener_orden(oid).unwrap();
+            assert_eq!((orden.cantidad, orden.cantidad_enviada, orden.monto), (3, 3, 300));
+            assert_eq!(orden.estado, EstadoOrden::Enviado);
+            assert!(orden.enviada_en.is_some());
+            assert_eq!(c.productos.get(pid).unwrap().cantidad, 7);
+            c._marcar_recibida(accounts.django, oid).unwrap();
         }
 
         #[ink::test]
-        fn no_se_puede_calificar_si_orden_no_recibida() {
+        fn reservar_stock_limita_las_reservas_por_comprador() {
             let accounts = default_accounts();
             let mut c = init_contract();
-            
             c._registrar_usuario(accounts.alice, Roles::Comprador).unwrap();
             c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
-            
-            let pid = c._publicar_producto(
-                accounts.bob,
-                "Producto".into(),
-                "Desc".into(),
-                100,
-                5,
-                "Cat".into()
-            ).unwrap();
-            
-            let oid = c._crear_orden(accounts.alice, pid, 1).unwrap();
-            
-            // No se puede calificar si la orden está pendiente
-            assert_eq!(
-                c.comprador_califica_vendedor(oid, 5),
-                Err(ContractError::OrdenNoRecibida)
-            );
+            let pid = c._publicar_producto(accounts.bob, "P".into(), "D".into(), 100, 50, "Cat".into(), None).unwrap();
+            for _ in 0..MAX_RESERVAS_POR_COMPRADOR {
+                c._reservar_stock(accounts.alice, pid, 1, 1_000).unwrap();
+            }
+            assert_eq!(c._reservar_stock(accounts.alice, pid, 1, 1_000), Err(ContractError::LimiteReservasAlcanzado));
+
+            // Al liberarse las vencidas el comprador puede volver a reservar
+            test::set_block_timestamp::<DefaultEnvironment>(1_000);
+            assert_eq!(c.liberar_reservas_vencidas(), MAX_RESERVAS_POR_COMPRADOR);
+            assert_eq!(c.primera_reserva_pendiente, MAX_RESERVAS_POR_COMPRADOR as u128 + 1);
+            assert!(c._reservar_stock(accounts.alice, pid, 1, 1_000).is_ok());
         }
 
         #[ink::test]
-        fn no_se_puede_calificar_dos_veces() {
+        fn usuarios_con_reputacion_respeta_la_reputacion_privada() {
             let accounts = default_accounts();
             let mut c = init_contract();
-            
-            c._registrar_usuario(accounts.alice, Roles::Comprador).unwrap();
-            c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
-            
-            let pid = c._publicar_producto(
-                accounts.bob,
-                "Producto".into(),
-                "Desc".into(),
-                100,
-                5,
-                "Cat".into()
-            ).unwrap();
-            
-            let oid = c._crear_orden(accounts.alice, pid, 1).unwrap();
-            c._marcar_enviada(accounts.bob, oid).unwrap();
-            c._marcar_recibida(accounts.alice, oid).unwrap();
-            
-            // Primera calificación OK
-            assert_eq!(c.comprador_califica_vendedor(oid, 5), Ok(()));
-            
-            // Segunda calificación debe fallar
-            assert_eq!(
-                c.comprador_califica_vendedor(oid, 4),
-                Err(ContractError::YaCalificado)
-            );
+            let mut rep = ReputacionData::new();
+            rep.agregar_calificacion_vendedor(4).unwrap();
+            for usuario in [accounts.bob, accounts.charlie] {
+                c._registrar_usuario(usuario, Roles::Vendedor).unwrap();
+                c.reputaciones.insert(usuario, &rep);
+            }
+            c._establecer_reputacion_publica(accounts.alice, false).unwrap();
+
+            test::set_caller::<DefaultEnvironment>(accounts.bob);
+            assert_eq!(c.obtener_usuarios_con_reputacion(), vec![(accounts.bob, rep.clone())]);
+            assert_eq!(c.usuarios_con_reputacion_paginado(0, 10), vec![(accounts.bob, rep.clone())]);
+            test::set_caller::<DefaultEnvironment>(accounts.django);
+            assert!(c.usuarios_con_reputacion_paginado(0, 10).is_empty());
+            assert!(c.obtener_usuarios_con_reputacion().is_empty());
+
+            // El owner ve a todos
+            test::set_caller::<DefaultEnvironment>(accounts.alice);
+            assert_eq!(c.usuarios_con_reputacion_paginado(0, 10).len(), 2);
         }
 
         #[ink::test]
-        fn calificacion_invalida_fuera_de_rango() {
+        fn modificar_cantidad_orden_es_proporcional_al_monto_con_descuento() {
             let accounts = default_accounts();
             let mut c = init_contract();
-            
+            test::set_callee::<DefaultEnvironment>(accounts.frank);
+            test::set_account_balance::<DefaultEnvironment>(accounts.frank, 1_000_000);
             c._registrar_usuario(accounts.alice, Roles::Comprador).unwrap();
             c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
-            
-            let pid = c._publicar_producto(
-                accounts.bob,
-                "Producto".into(),
-                "Desc".into(),
-                100,
-                5,
-                "Cat".into()
-            ).unwrap();
-            
-            let oid = c._crear_orden(accounts.alice, pid, 1).unwrap();
-            c._marcar_enviada(accounts.bob, oid).unwrap();
-            c._marcar_recibida(accounts.alice, oid).unwrap();
-            
-            // Calificación 0 (inválida)
-            assert_eq!(
-                c.comprador_califica_vendedor(oid, 0),
-                Err(ContractError::CalificacionInvalida)
-            );
-            
-            // Calificación 6 (inválida)
-            assert_eq!(
-                c.comprador_califica_vendedor(oid, 6),
-                Err(ContractError::CalificacionInvalida)
-            );
+            let pid = c._publicar_producto(accounts.bob, "P".into(), "D".into(), 100, 10, "Cat".into(), None).unwrap();
+            // 4 unidades con un descuento que deja un monto no divisible por la cantidad
+            let oid = c._crear_orden_con_descuento(accounts.alice, pid, 4, 3_750).unwrap();
+            assert_eq!(c.obtener_orden(oid).unwrap().monto, 250);
+
+            let saldo = test::get_account_balance::<DefaultEnvironment>(accounts.alice).unwrap();
+            c._modificar_cantidad_orden(accounts.alice, oid, 1, 0).unwrap();
+            assert_eq!(test::get_account_balance::<DefaultEnvironment>(accounts.alice).unwrap(), saldo + 187);
+            assert_eq!(c.obtener_orden(oid).unwrap().monto, 63);
+
+            assert_eq!(c._modificar_cantidad_orden(accounts.alice, oid, 2, 62), Err(ContractError::PagoIncorrecto));
+            c._modificar_cantidad_orden(accounts.alice, oid, 2, 63).unwrap();
+            assert_eq!(c.obtener_orden(oid).unwrap().monto, 126);
+            assert_eq!(c.liabilidad_reembolsos(), 126);
         }
 
         #[ink::test]
-        fn reputacion_acumulada_multiple_calificaciones() {
+        fn modificar_cantidad_orden_revalida_la_compra_al_aumentar() {
             let accounts = default_accounts();
             let mut c = init_contract();
-            
-            c._registrar_usuario(accounts.alice, Roles::Comprador).unwrap();
+            c._registrar_usuario(accounts.django, Roles::Comprador).unwrap();
             c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
-            c._registrar_usuario(accounts.charlie, Roles::Comprador).unwrap();
-            
-            // Primera orden
-            let pid1 = c._publicar_producto(
-                accounts.bob,
-                "Producto1".into(),
-                "Desc".into(),
-                100,
-                10,
-                "Cat1".into()
-            ).unwrap();
-            let oid1 = c._crear_orden(accounts.alice, pid1, 1).unwrap();
-            c._marcar_enviada(accounts.bob, oid1).unwrap();
-            c._marcar_recibida(accounts.alice, oid1).unwrap();
-            c.comprador_califica_vendedor(oid1, 5).unwrap();
-            
-            // Segunda orden
-            let pid2 = c._publicar_producto(
-                accounts.bob,
-                "Producto2".into(),
-                "Desc".into(),
-                100,
-                10,
-                "Cat1".into()
-            ).unwrap();
-            let oid2 = c._crear_orden(accounts.charlie, pid2, 1).unwrap();
-            c._marcar_enviada(accounts.bob, oid2).unwrap();
-            c._marcar_recibida(accounts.charlie, oid2).unwrap();
-            c.comprador_califica_vendedor(oid2, 3).unwrap();
-            
-            // Verificar promedio: (5 + 3) / 2 = 4
-            let reputacion = c.obtener_reputacion(accounts.bob).unwrap();
-            assert_eq!(reputacion.promedio_vendedor(), Some(4));
-            assert_eq!(reputacion.total_calificaciones_vendedor, 2);
+            let pid = c._publicar_producto(accounts.bob, "P".into(), "D".into(), 10, 10, "Cat".into(), None).unwrap();
+            let oid = c._crear_orden(accounts.django, pid, 2).unwrap();
+
+            c._cambiar_estado_producto(accounts.bob, pid, false).unwrap();
+            assert_eq!(c._modificar_cantidad_orden(accounts.django, oid, 3, 10), Err(ContractError::ProductoInactivo));
+            c._cambiar_estado_producto(accounts.bob, pid, true).unwrap();
+
+            c._establecer_pausa(accounts.alice, true).unwrap();
+            assert_eq!(c._modificar_cantidad_orden(accounts.django, oid, 3, 10), Err(ContractError::Pausado));
+            c._establecer_pausa(accounts.alice, false).unwrap();
+
+            c._establecer_modo_alto_riesgo(accounts.alice, true).unwrap();
+            assert_eq!(c._modificar_cantidad_orden(accounts.django, oid, 3, 10), Err(ContractError::KycRequerido));
+            c._establecer_modo_alto_riesgo(accounts.alice, false).unwrap();
+
+            assert_eq!(c._modificar_cantidad_orden(accounts.django, oid, 3, 10), Ok(()));
+            assert_eq!(c.obtener_orden(oid).unwrap().cantidad, 3);
         }
 
         #[ink::test]
-        fn obtener_reputacion_como_comprador_y_vendedor() {
+        fn crear_orden_de_compra_valida_permisos_antes_del_pago() {
             let accounts = default_accounts();
             let mut c = init_contract();
-            
-            c._registrar_usuario(accounts.alice, Roles::Ambos).unwrap();
-            c._registrar_usuario(accounts.bob, Roles::Ambos).unwrap();
-            
-            // Alice como vendedor
-            let pid = c._publicar_producto(
-                accounts.alice,
-                "Producto".into(),
-                "Desc".into(),
-                100,
-                5,
-                "Cat".into()
-            ).unwrap();
-            
-            let oid = c._crear_orden(accounts.bob, pid, 1).unwrap();
-            c._marcar_enviada(accounts.alice, oid).unwrap();
-            c._marcar_recibida(accounts.bob, oid).unwrap();
-            
-            c.comprador_califica_vendedor(oid, 5).unwrap();
-            c.vendedor_califica_comprador(oid, 4).unwrap();
-            
-            // Alice tiene reputación como vendedor
-            let reputacion_alice = c.obtener_reputacion(accounts.alice).unwrap();
-            assert_eq!(reputacion_alice.promedio_vendedor(), Some(5));
-            
-            // Bob tiene reputación como comprador
-            let reputacion_bob = c.obtener_reputacion(accounts.bob).unwrap();
-            assert_eq!(reputacion_bob.promedio_comprador(), Some(4));
+            c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
+            c._registrar_usuario(accounts.charlie, Roles::Comprador).unwrap();
+            let pid = c._publicar_producto(accounts.bob, "P".into(), "D".into(), 100, 5, "Cat".into(), None).unwrap();
+            c._cambiar_baneo(accounts.alice, accounts.charlie, true).unwrap();
+
+            test::set_value_transferred::<DefaultEnvironment>(1);
+            test::set_caller::<DefaultEnvironment>(accounts.django);
+            assert_eq!(c.crear_orden_de_compra(pid, 1, None), Err(ContractError::UsuarioNoRegistrado));
+            test::set_caller::<DefaultEnvironment>(accounts.charlie);
+            assert_eq!(c.crear_orden_de_compra(pid, 1, None), Err(ContractError::UsuarioBaneado));
         }
 
         #[ink::test]
-        fn ventas_por_producto_se_registran() {
+        fn editar_producto_agotado_valida_como_al_publicar() {
             let accounts = default_accounts();
             let mut c = init_contract();
-            
             c._registrar_usuario(accounts.alice, Roles::Comprador).unwrap();
             c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
-            
-            let pid = c._publicar_producto(
-                accounts.bob,
-                "Producto".into(),
-                "Desc".into(),
-                100,
-                10,
-                "Cat".into()
-            ).unwrap();
-            
-            // Antes de recibir, no hay ventas registradas
-            assert_eq!(c.obtener_ventas_producto(pid), 0);
-            
-            let oid = c._crear_orden(accounts.alice, pid, 1).unwrap();
-            c._marcar_enviada(accounts.bob, oid).unwrap();
-            
-            // Todavía no hay ventas (orden no recibida)
-            assert_eq!(c.obtener_ventas_producto(pid), 0);
-            
-            c._marcar_recibida(accounts.alice, oid).unwrap();
-            
-            // Ahora sí hay una venta registrada
-            assert_eq!(c.obtener_ventas_producto(pid), 1);
+            let pid = c._publicar_producto(accounts.bob, "P".into(), "D".into(), 10, 1, "Cat".into(), None).unwrap();
+            c._crear_orden(accounts.alice, pid, 1).unwrap();
+            assert_eq!(c.productos.get(pid).unwrap().cantidad, 0);
+
+            assert_eq!(c._editar_producto(accounts.bob, pid, "Nueva".into(), "".into()), Err(ContractError::DatosInvalidos));
+            assert_eq!(c._editar_producto(accounts.bob, pid, "Nueva".into(), "Cat".into()), Ok(()));
+            assert_eq!(c.productos.get(pid).unwrap().descripcion, "Nueva");
         }
 
         #[ink::test]
-        fn estadisticas_por_categoria() {
+        fn reputacion_por_categoria_y_confiabilidad_respetan_la_reputacion_privada() {
             let accounts = default_accounts();
             let mut c = init_contract();
-            
             c._registrar_usuario(accounts.alice, Roles::Comprador).unwrap();
             c._registrar_usuario(accounts.bob, Roles::Vendedor).unwrap();
-            
-            let pid = c._publicar_producto(
-                accounts.bob,
-                "Producto".into(),
-                "Desc".into(),
-                100,
-                5,
-                "Electronica".into()
-            ).unwrap();
-            
-            let oid = c._crear_orden(accounts.alice, pid, 1).unwrap();
-            c._marcar_enviada(accounts.bob, oid).unwrap();
-            c._marcar_recibida(accounts.alice, oid).unwrap();
-            
-            c.comprador_califica_vendedor(oid, 5).unwrap();
-            
-            // Verificar estadísticas de la categoría
-            let stats = c.obtener_estadisticas_categoria("Electronica".into()).unwrap();
-            assert_eq!(stats.0, 1); // total_ventas
-            assert_eq!(stats.1, 5); // suma_calificaciones
-            assert_eq!(stats.2, 1); // cantidad_calificaciones
+            let pid = c._publicar_producto(accounts.bob, "P".into(), "D".into(), 10, 50, "Cat".into(), None).unwrap();
+            for _ in 0..MIN_CALIFICACIONES_CONFIABLE {
+                let oid = c._crear_orden(accounts.alice, pid, 1).unwrap();
+                c._marcar_enviada(accounts.bob, oid, None).unwrap();
+                c._marcar_recibida(accounts.alice, oid).unwrap();
+                c._calificar_vendedor(accounts.alice, oid, 5, None).unwrap();
+            }
+            c._establecer_reputacion_publica(accounts.alice, false).unwrap();
+
+            test::set_caller::<DefaultEnvironment>(accounts.charlie);
+            assert_eq!(c.reputacion_vendedor_en_categoria(accounts.bob, "Cat".into()), None);
+            assert_eq!(c.reputacion_vendedor_en_categoria_producto(pid), None);
+            assert!(!c.es_vendedor_confiable(accounts.bob, 4));
+
+            test::set_caller::<DefaultEnvironment>(accounts.bob);
+            assert_eq!(c.reputacion_vendedor_en_categoria(accounts.bob, "Cat".into()), Some(5));
+            assert_eq!(c.reputacion_vendedor_en_categoria_producto(pid), Some(5));
+            assert!(c.es_vendedor_confiable(accounts.bob, 4));
+
+            // La verificación del owner es pública
+            c._verificar_usuario(accounts.alice, accounts.bob, true).unwrap();
+            test::set_caller::<DefaultEnvironment>(accounts.charlie);
+            assert!(c.es_vendedor_confiable(accounts.bob, 4));
+        }
+
+        #[ink::test]
+        fn aplicar_descuento_devuelve_overflow_en_vez_de_saturar() {
+            assert_eq!(Marketplace::_aplicar_descuento(1_000, 1_500), Ok(850));
+            assert_eq!(Marketplace::_aplicar_descuento(1_000, 0), Ok(1_000));
+            assert_eq!(Marketplace::_aplicar_descuento(u128::MAX, 1_500), Err(ContractError::Overflow));
+        }
+
+        #[ink::test]
+        fn pausa_bloquea_la_recepcion_con_y_sin_calificacion() {
+            let accounts = default_accounts();
+            let mut c = init_contract();
+            c._registrar_usuario(accounts.bob, Roles::Comprador).unwrap();
+            c._registrar_usuario(accounts.charlie, Roles::Vendedor).unwrap();
+            let pid = c._publicar_producto(accounts.charlie, "P".into(), "D".into(), 10, 5, "Cat".into(), None).unwrap();
+            let o1 = c._crear_orden(accounts.bob, pid, 1).unwrap();
+            let o2 = c._crear_orden(accounts.bob, pid, 1).unwrap();
+            for oid in [o1, o2] {
+                c._marcar_enviada(accounts.charlie, oid, None).unwrap();
+            }
+            c._establecer_pausa(accounts.alice, true).unwrap();
+
+            test::set_caller::<DefaultEnvironment>(accounts.bob);
+            assert_eq!(c.marcar_orden_recibida(o1), Err(ContractError::Pausado));
+            assert_eq!(c.marcar_recibida_y_calificar(o2, 5), Err(ContractError::Pausado));
+
+            c._establecer_pausa(accounts.alice, false).unwrap();
+            assert_eq!(c.marcar_orden_recibida(o1), Ok(()));
+            assert_eq!(c.marcar_recibida_y_calificar(o2, 5), Ok(()));
         }
     }
 }
@@ -7,6 +7,12 @@ mod reportes_view {
     use ink::prelude::{string::String, vec::Vec};
     use ink::prelude::collections::BTreeMap;
 
+    /// Máxima cantidad de usuarios que puede devolver un ranking.
+    pub const MAX_TOP_N: u32 = 100;
+
+    /// Cantidad de usuarios que se piden al marketplace en cada llamada paginada.
+    pub const TAMANO_PAGINA_USUARIOS: u32 = 100;
+
     /// Tipo para representar un producto (debe coincidir con el del contrato Marketplace).
     #[derive(Clone, Debug, PartialEq, Eq, scale::Encode, scale::Decode)]
     #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
@@ -17,6 +23,9 @@ mod reportes_view {
         pub cantidad: u32,
         pub categoria: String,
         pub vendedor: AccountId,
+        pub activo: bool,
+        pub reputacion_minima_comprador: Option<u128>,
+        pub limite_por_orden: Option<u32>,
     }
 
     /// Tipo para representar datos de reputación (debe coincidir con el del contrato Marketplace).
@@ -27,6 +36,8 @@ mod reportes_view {
         pub suma_calificaciones_comprador: u128,
         pub total_calificaciones_vendedor: u32,
         pub suma_calificaciones_vendedor: u128,
+        pub distribucion_comprador: [u32; 5],
+        pub distribucion_vendedor: [u32; 5],
     }
 
     impl ReputacionData {
@@ -49,6 +60,29 @@ mod reportes_view {
                 None
             }
         }
+
+        /// Promedio como vendedor multiplicado por 100 (4.5 -> 450), para no perder los decimales
+        /// que trunca `promedio_vendedor`.
+        pub fn promedio_vendedor_escalado(&self) -> Option<u128> {
+            if self.total_calificaciones_vendedor > 0 {
+                self.suma_calificaciones_vendedor
+                    .checked_mul(100)?
+                    .checked_div(self.total_calificaciones_vendedor as u128)
+            } else {
+                None
+            }
+        }
+
+        /// Promedio como comprador multiplicado por 100, análogo a `promedio_vendedor_escalado`.
+        pub fn promedio_comprador_escalado(&self) -> Option<u128> {
+            if self.total_calificaciones_comprador > 0 {
+                self.suma_calificaciones_comprador
+                    .checked_mul(100)?
+                    .checked_div(self.total_calificaciones_comprador as u128)
+            } else {
+                None
+            }
+        }
     }
 
     /// Enum para errores del contrato de reportes.
@@ -90,43 +124,112 @@ mod reportes_view {
         /// Obtiene el top 5 de vendedores con mejor reputación.
         /// Retorna un vector de tuplas (AccountId, promedio_reputacion).
         #[ink(message)]
-        pub fn top_5_vendedores(&self) -> Vec<(AccountId, u128)> {
-            self._obtener_top_vendedores(5)
+        pub fn top_5_vendedores(&self) -> Result<Vec<(AccountId, u128)>, ReportesError> {
+            self.top_vendedores(5)
+        }
+
+        /// Obtiene el top `n` de vendedores con mejor reputación (máximo `MAX_TOP_N`).
+        #[ink(message)]
+        pub fn top_vendedores(&self, n: u32) -> Result<Vec<(AccountId, u128)>, ReportesError> {
+            self._obtener_top_vendedores(n.min(MAX_TOP_N) as usize)
+        }
+
+        /// Obtiene los `n` vendedores peor calificados (máximo `MAX_TOP_N`), de menor a mayor
+        /// promedio. Solo incluye vendedores con al menos una calificación.
+        #[ink(message)]
+        pub fn peores_vendedores(&self, n: u32) -> Result<Vec<(AccountId, u128)>, ReportesError> {
+            self._obtener_peores_vendedores(n.min(MAX_TOP_N) as usize)
         }
 
         /// Obtiene el top 5 de compradores con mejor reputación.
         /// Retorna un vector de tuplas (AccountId, promedio_reputacion).
         #[ink(message)]
-        pub fn top_5_compradores(&self) -> Vec<(AccountId, u128)> {
-            self._obtener_top_compradores(5)
+        pub fn top_5_compradores(&self) -> Result<Vec<(AccountId, u128)>, ReportesError> {
+            self.top_compradores(5)
+        }
+
+        /// Obtiene el top `n` de compradores con mejor reputación (máximo `MAX_TOP_N`).
+        #[ink(message)]
+        pub fn top_compradores(&self, n: u32) -> Result<Vec<(AccountId, u128)>, ReportesError> {
+            self._obtener_top_compradores(n.min(MAX_TOP_N) as usize)
         }
 
         /// Obtiene los productos más vendidos.
         /// Retorna un vector de tuplas (producto_id, cantidad_ventas).
         #[ink(message)]
-        pub fn productos_mas_vendidos(&self) -> Vec<(u128, u32)> {
+        pub fn productos_mas_vendidos(&self) -> Result<Vec<(u128, u32)>, ReportesError> {
             self._obtener_productos_mas_vendidos()
         }
 
+        /// Obtiene el volumen total vendido en el marketplace: la suma de `precio * ventas`
+        /// de cada producto. Es una aproximación: usa el precio actual y no el de cada venta,
+        /// y no incluye productos inactivos o eliminados.
+        #[ink(message)]
+        pub fn volumen_total(&self) -> Result<u128, ReportesError> {
+            self._calcular_volumen(None)
+        }
+
+        /// Obtiene lo facturado por un vendedor, con la misma aproximación que `volumen_total`.
+        /// El marketplace expone el valor exacto, con el precio de cada orden, en `ingresos_reales_vendedor`.
+        #[ink(message)]
+        pub fn revenue_por_vendedor(&self, vendedor: AccountId) -> Result<u128, ReportesError> {
+            self._calcular_volumen(Some(vendedor))
+        }
+
         /// Obtiene estadísticas por categoría.
         /// Retorna un vector de tuplas (categoria, total_ventas, calificacion_promedio).
         #[ink(message)]
-        pub fn estadisticas_por_categoria(&self) -> Vec<(String, u32, Option<u128>)> {
+        pub fn estadisticas_por_categoria(&self) -> Result<Vec<(String, u32, Option<u128>)>, ReportesError> {
             self._obtener_estadisticas_categorias()
         }
 
+        /// Obtiene la categoría con mejor calificación promedio y ese promedio.
+        /// Ante empate gana la categoría con nombre menor. `None` si no hay calificaciones.
+        #[ink(message)]
+        pub fn mejor_categoria_por_calificacion(&self) -> Result<Option<(String, u128)>, ReportesError> {
+            let estadisticas = self._obtener_estadisticas_categorias()?;
+            Ok(Self::_mejor_categoria(estadisticas))
+        }
+
+        /// Obtiene el tiempo promedio de envío de un vendedor, en milisegundos.
+        /// `None` si el vendedor todavía no envió ninguna orden.
+        #[ink(message)]
+        pub fn tiempo_envio_promedio(&self, vendedor: AccountId) -> Result<Option<u64>, ReportesError> {
+            build_call::<ink::env::DefaultEnvironment>()
+                .call(self._marketplace_configurado()?)
+                .exec_input(
+                    ExecutionInput::new(Selector::new(ink::selector_bytes!(
+                        "tiempo_envio_promedio"
+                    )))
+                    .push_arg(vendedor),
+                )
+                .returns::<Option<u64>>()
+                .try_invoke()
+                .map_err(|_| ReportesError::LlamadaFallida)?
+                .map_err(|_| ReportesError::LlamadaFallida)
+        }
+
         /// Obtiene la cantidad de órdenes de un usuario específico.
         #[ink(message)]
-        pub fn cantidad_ordenes_usuario(&self, usuario: AccountId) -> u32 {
+        pub fn cantidad_ordenes_usuario(&self, usuario: AccountId) -> Result<u32, ReportesError> {
             self._llamar_marketplace_cantidad_ordenes(usuario)
         }
 
         // ===== Funciones privadas =====
 
+        /// Devuelve la dirección del marketplace, o un error si no fue configurada
+        /// (dirección nula).
+        fn _marketplace_configurado(&self) -> Result<AccountId, ReportesError> {
+            if self.marketplace == AccountId::from([0u8; 32]) {
+                return Err(ReportesError::MarketplaceNoConfigurado);
+            }
+            Ok(self.marketplace)
+        }
+
         /// Hace una llamada cross-contract al marketplace para obtener cantidad de órdenes.
-        fn _llamar_marketplace_cantidad_ordenes(&self, usuario: AccountId) -> u32 {
+        fn _llamar_marketplace_cantidad_ordenes(&self, usuario: AccountId) -> Result<u32, ReportesError> {
             build_call::<ink::env::DefaultEnvironment>()
-                .call(self.marketplace)
+                .call(self._marketplace_configurado()?)
                 .exec_input(
                     ExecutionInput::new(Selector::new(ink::selector_bytes!(
                         "cantidad_ordenes_usuario"
@@ -134,95 +237,166 @@ mod reportes_view {
                     .push_arg(usuario),
                 )
                 .returns::<u32>()
-                .invoke()
-                .unwrap_or(0) // En caso de error, retornar 0
+                .try_invoke()
+                .map_err(|_| ReportesError::LlamadaFallida)?
+                .map_err(|_| ReportesError::LlamadaFallida)
         }
 
         /// Obtiene el top N de vendedores ordenados por reputación.
-        fn _obtener_top_vendedores(&self, cantidad: usize) -> Vec<(AccountId, u128)> {
+        fn _obtener_top_vendedores(&self, cantidad: usize) -> Result<Vec<(AccountId, u128)>, ReportesError> {
             // Obtener todos los usuarios con reputación del marketplace
-            let usuarios = self._llamar_marketplace_usuarios_con_reputacion();
+            let usuarios = self._llamar_marketplace_usuarios_con_reputacion()?;
             
-            let mut vendedores: Vec<(AccountId, u128)> = usuarios
+            let mut vendedores: Vec<(AccountId, u128, u128)> = usuarios
                 .into_iter()
                 .filter_map(|(usuario, reputacion)| {
-                    reputacion
-                        .promedio_vendedor()
-                        .map(|promedio| (usuario, promedio))
+                    Some((usuario, reputacion.promedio_vendedor()?, reputacion.promedio_vendedor_escalado()?))
                 })
                 .collect();
             
-            // Ordenar por reputación descendente
-            vendedores.sort_by(|a, b| b.1.cmp(&a.1));
+            // Ordenar por reputación descendente, usando el promedio escalado para desempatar
+            // promedios que truncados serían iguales
+            vendedores.sort_by(|a, b| b.2.cmp(&a.2));
+            vendedores.truncate(cantidad);
+            Ok(vendedores.into_iter().map(|(usuario, promedio, _)| (usuario, promedio)).collect())
+        }
+
+        /// Obtiene los N vendedores con peor reputación, en orden ascendente.
+        fn _obtener_peores_vendedores(&self, cantidad: usize) -> Result<Vec<(AccountId, u128)>, ReportesError> {
+            let usuarios = self._llamar_marketplace_usuarios_con_reputacion()?;
+
+            let mut vendedores: Vec<(AccountId, u128, u128)> = usuarios
+                .into_iter()
+                .filter_map(|(usuario, reputacion)| {
+                    Some((usuario, reputacion.promedio_vendedor()?, reputacion.promedio_vendedor_escalado()?))
+                })
+                .collect();
+
+            // Ordenar por reputación ascendente (promedio escalado)
+            vendedores.sort_by_key(|v| v.2);
             vendedores.truncate(cantidad);
-            vendedores
+            Ok(vendedores.into_iter().map(|(usuario, promedio, _)| (usuario, promedio)).collect())
         }
 
         /// Obtiene el top N de compradores ordenados por reputación.
-        fn _obtener_top_compradores(&self, cantidad: usize) -> Vec<(AccountId, u128)> {
+        fn _obtener_top_compradores(&self, cantidad: usize) -> Result<Vec<(AccountId, u128)>, ReportesError> {
             // Obtener todos los usuarios con reputación del marketplace
-            let usuarios = self._llamar_marketplace_usuarios_con_reputacion();
+            let usuarios = self._llamar_marketplace_usuarios_con_reputacion()?;
             
-            let mut compradores: Vec<(AccountId, u128)> = usuarios
+            let mut compradores: Vec<(AccountId, u128, u128)> = usuarios
                 .into_iter()
                 .filter_map(|(usuario, reputacion)| {
-                    reputacion
-                        .promedio_comprador()
-                        .map(|promedio| (usuario, promedio))
+                    Some((usuario, reputacion.promedio_comprador()?, reputacion.promedio_comprador_escalado()?))
                 })
                 .collect();
             
-            // Ordenar por reputación descendente
-            compradores.sort_by(|a, b| b.1.cmp(&a.1));
+            // Ordenar por reputación descendente (promedio escalado)
+            compradores.sort_by(|a, b| b.2.cmp(&a.2));
             compradores.truncate(cantidad);
-            compradores
+            Ok(compradores.into_iter().map(|(usuario, promedio, _)| (usuario, promedio)).collect())
         }
 
-        /// Hace una llamada cross-contract al marketplace para obtener usuarios con reputación.
-        fn _llamar_marketplace_usuarios_con_reputacion(&self) -> Vec<(AccountId, ReputacionData)> {
+        /// Obtiene todos los usuarios con reputación pidiéndolos al marketplace de a páginas
+        /// de `TAMANO_PAGINA_USUARIOS`, para que ninguna llamada recorra todo el directorio.
+        /// Si la reputación no es pública, el marketplace no devuelve ninguna a este contrato.
+        fn _llamar_marketplace_usuarios_con_reputacion(
+            &self,
+        ) -> Result<Vec<(AccountId, ReputacionData)>, ReportesError> {
+            let total = self._llamar_marketplace_total_usuarios()?;
+            let mut usuarios = Vec::new();
+            let mut inicio: u32 = 0;
+            while inicio < total {
+                usuarios.extend(self._llamar_marketplace_usuarios_con_reputacion_paginado(
+                    inicio,
+                    TAMANO_PAGINA_USUARIOS,
+                )?);
+                inicio = inicio.saturating_add(TAMANO_PAGINA_USUARIOS);
+            }
+            Ok(usuarios)
+        }
+
+        /// Hace una llamada cross-contract al marketplace para obtener la cantidad de usuarios.
+        fn _llamar_marketplace_total_usuarios(&self) -> Result<u32, ReportesError> {
             build_call::<ink::env::DefaultEnvironment>()
-                .call(self.marketplace)
+                .call(self._marketplace_configurado()?)
                 .exec_input(ExecutionInput::new(Selector::new(ink::selector_bytes!(
-                    "obtener_usuarios_con_reputacion"
+                    "total_usuarios"
                 ))))
+                .returns::<u32>()
+                .try_invoke()
+                .map_err(|_| ReportesError::LlamadaFallida)?
+                .map_err(|_| ReportesError::LlamadaFallida)
+        }
+
+        /// Hace una llamada cross-contract al marketplace para obtener una página de usuarios con reputación.
+        fn _llamar_marketplace_usuarios_con_reputacion_paginado(
+            &self,
+            inicio: u32,
+            limite: u32,
+        ) -> Result<Vec<(AccountId, ReputacionData)>, ReportesError> {
+            build_call::<ink::env::DefaultEnvironment>()
+                .call(self._marketplace_configurado()?)
+                .exec_input(
+                    ExecutionInput::new(Selector::new(ink::selector_bytes!(
+                        "usuarios_con_reputacion_paginado"
+                    )))
+                    .push_arg(inicio)
+                    .push_arg(limite),
+                )
                 .returns::<Vec<(AccountId, ReputacionData)>>()
-                .invoke()
-                .unwrap_or_default() // En caso de error, retornar vector vacío
+                .try_invoke()
+                .map_err(|_| ReportesError::LlamadaFallida)?
+                .map_err(|_| ReportesError::LlamadaFallida)
         }
 
         /// Obtiene los productos más vendidos ordenados por cantidad de ventas.
-        fn _obtener_productos_mas_vendidos(&self) -> Vec<(u128, u32)> {
-            let todos_productos = self._llamar_marketplace_ver_todos_productos();
+        fn _obtener_productos_mas_vendidos(&self) -> Result<Vec<(u128, u32)>, ReportesError> {
+            let todos_productos = self._llamar_marketplace_ver_todos_productos()?;
             
             let mut productos_ventas: Vec<(u128, u32)> = todos_productos
                 .into_iter()
                 .map(|(id, _)| {
-                    let ventas = self._llamar_marketplace_ventas_producto(id);
-                    (id, ventas)
+                    let ventas = self._llamar_marketplace_ventas_producto(id)?;
+                    Ok((id, ventas))
                 })
-                .collect();
+                .collect::<Result<_, ReportesError>>()?;
             
             // Ordenar por ventas (descendente)
             productos_ventas.sort_by(|a, b| b.1.cmp(&a.1));
-            productos_ventas
+            Ok(productos_ventas)
+        }
+
+        /// Suma `precio * ventas` de los productos, opcionalmente solo los de un vendedor.
+        /// Satura en lugar de desbordar.
+        fn _calcular_volumen(&self, vendedor: Option<AccountId>) -> Result<u128, ReportesError> {
+            let mut total: u128 = 0;
+            for (id, producto) in self._llamar_marketplace_ver_todos_productos()? {
+                if vendedor.is_some_and(|v| v != producto.vendedor) {
+                    continue;
+                }
+                let ventas = self._llamar_marketplace_ventas_producto(id)?;
+                total = total.saturating_add(producto.precio.saturating_mul(ventas as u128));
+            }
+            Ok(total)
         }
 
         /// Hace una llamada cross-contract al marketplace para obtener todos los productos.
-        fn _llamar_marketplace_ver_todos_productos(&self) -> Vec<(u128, Producto)> {
+        fn _llamar_marketplace_ver_todos_productos(&self) -> Result<Vec<(u128, Producto)>, ReportesError> {
             build_call::<ink::env::DefaultEnvironment>()
-                .call(self.marketplace)
+                .call(self._marketplace_configurado()?)
                 .exec_input(ExecutionInput::new(Selector::new(ink::selector_bytes!(
                     "ver_todos_los_productos"
                 ))))
                 .returns::<Vec<(u128, Producto)>>()
-                .invoke()
-                .unwrap_or_default() // En caso de error, retornar vector vacío
+                .try_invoke()
+                .map_err(|_| ReportesError::LlamadaFallida)?
+                .map_err(|_| ReportesError::LlamadaFallida)
         }
 
         /// Hace una llamada cross-contract al marketplace para obtener ventas de un producto.
-        fn _llamar_marketplace_ventas_producto(&self, producto_id: u128) -> u32 {
+        fn _llamar_marketplace_ventas_producto(&self, producto_id: u128) -> Result<u32, ReportesError> {
             build_call::<ink::env::DefaultEnvironment>()
-                .call(self.marketplace)
+                .call(self._marketplace_configurado()?)
                 .exec_input(
                     ExecutionInput::new(Selector::new(ink::selector_bytes!(
                         "obtener_ventas_producto"
@@ -230,19 +404,20 @@ mod reportes_view {
                     .push_arg(producto_id),
                 )
                 .returns::<u32>()
-                .invoke()
-                .unwrap_or(0) // En caso de error, retornar 0
+                .try_invoke()
+                .map_err(|_| ReportesError::LlamadaFallida)?
+                .map_err(|_| ReportesError::LlamadaFallida)
         }
 
         /// Obtiene las estadísticas agrupadas por categoría.
-        fn _obtener_estadisticas_categorias(&self) -> Vec<(String, u32, Option<u128>)> {
-            let todos_productos = self._llamar_marketplace_ver_todos_productos();
+        fn _obtener_estadisticas_categorias(&self) -> Result<Vec<(String, u32, Option<u128>)>, ReportesError> {
+            let todos_productos = self._llamar_marketplace_ver_todos_productos()?;
             let mut stats_map: BTreeMap<String, (u32, u128, u32)> = BTreeMap::new();
             
             for (_, producto) in todos_productos {
                 if let Some(stats) = self._llamar_marketplace_estadisticas_categoria(
                     producto.categoria.clone()
-                ) {
+                )? {
                     let entry = stats_map.entry(producto.categoria).or_insert((0, 0, 0));
                     // Usar unwrap_or para mantener el valor anterior en caso de overflow
                     entry.0 = entry.0.checked_add(stats.0).unwrap_or(entry.0);
@@ -251,7 +426,7 @@ mod reportes_view {
                 }
             }
             
-            stats_map
+            Ok(stats_map
                 .into_iter()
                 .map(|(cat, (total_ventas, suma_calif, num_calif))| {
                     let promedio = if num_calif > 0 {
@@ -261,16 +436,30 @@ mod reportes_view {
                     };
                     (cat, total_ventas, promedio)
                 })
-                .collect()
+                .collect())
+        }
+
+        /// Elige la categoría con mayor promedio. Las estadísticas vienen ordenadas por
+        /// nombre, así que solo se reemplaza ante un promedio estrictamente mayor.
+        fn _mejor_categoria(estadisticas: Vec<(String, u32, Option<u128>)>) -> Option<(String, u128)> {
+            let mut mejor: Option<(String, u128)> = None;
+            for (categoria, _, promedio) in estadisticas {
+                let Some(promedio) = promedio else { continue };
+                match mejor {
+                    Some((_, actual)) if promedio <= actual => {}
+                    _ => mejor = Some((categoria, promedio)),
+                }
+            }
+            mejor
         }
 
         /// Hace una llamada cross-contract al marketplace para obtener estadísticas de categoría.
         fn _llamar_marketplace_estadisticas_categoria(
             &self,
             categoria: String,
-        ) -> Option<(u32, u128, u32)> {
+        ) -> Result<Option<(u32, u128, u32)>, ReportesError> {
             build_call::<ink::env::DefaultEnvironment>()
-                .call(self.marketplace)
+                .call(self._marketplace_configurado()?)
                 .exec_input(
                     ExecutionInput::new(Selector::new(ink::selector_bytes!(
                         "obtener_estadisticas_categoria"
@@ -278,8 +467,9 @@ mod reportes_view {
                     .push_arg(categoria),
                 )
                 .returns::<Option<(u32, u128, u32)>>()
-                .invoke()
-                .unwrap_or(None) // En caso de error, retornar None
+                .try_invoke()
+                .map_err(|_| ReportesError::LlamadaFallida)?
+                .map_err(|_| ReportesError::LlamadaFallida)
         }
     }
 
@@ -313,50 +503,100 @@ mod reportes_view {
             assert_eq!(reportes.obtener_marketplace(), accounts.bob);
         }
 
+        // Nota: el entorno off-chain no soporta llamadas cross-contract, por lo que
+        // solo se prueban los casos que fallan antes de invocar al marketplace.
+
+        fn marketplace_nulo() -> AccountId {
+            AccountId::from([0u8; 32])
+        }
+
         #[ink::test]
-        fn cantidad_ordenes_usuario_retorna_cero_si_no_hay_marketplace() {
+        fn cantidad_ordenes_usuario_falla_si_no_hay_marketplace() {
             let accounts = default_accounts();
-            // Usar una cuenta que no es un contrato desplegado
-            let reportes = init_reportes_view(accounts.charlie);
-            // Debería retornar 0 si la llamada falla
-            let cantidad = reportes.cantidad_ordenes_usuario(accounts.alice);
-            assert_eq!(cantidad, 0);
+            let reportes = init_reportes_view(marketplace_nulo());
+            assert_eq!(
+                reportes.cantidad_ordenes_usuario(accounts.alice),
+                Err(ReportesError::MarketplaceNoConfigurado)
+            );
         }
 
         #[ink::test]
-        fn top_vendedores_retorna_vacio_si_no_hay_marketplace() {
-            let accounts = default_accounts();
-            let reportes = init_reportes_view(accounts.charlie);
-            // Debería retornar vacío si la llamada falla
-            let top = reportes.top_5_vendedores();
-            assert_eq!(top.len(), 0);
+        fn top_vendedores_falla_si_no_hay_marketplace() {
+            let reportes = init_reportes_view(marketplace_nulo());
+            assert_eq!(reportes.top_5_vendedores(), Err(ReportesError::MarketplaceNoConfigurado));
         }
 
         #[ink::test]
-        fn top_compradores_retorna_vacio_si_no_hay_marketplace() {
-            let accounts = default_accounts();
-            let reportes = init_reportes_view(accounts.charlie);
-            // Debería retornar vacío si la llamada falla
-            let top = reportes.top_5_compradores();
-            assert_eq!(top.len(), 0);
+        fn top_compradores_falla_si_no_hay_marketplace() {
+            let reportes = init_reportes_view(marketplace_nulo());
+            assert_eq!(reportes.top_5_compradores(), Err(ReportesError::MarketplaceNoConfigurado));
+        }
+
+        #[ink::test]
+        fn productos_mas_vendidos_falla_si_no_hay_marketplace() {
+            let reportes = init_reportes_view(marketplace_nulo());
+            assert_eq!(reportes.productos_mas_vendidos(), Err(ReportesError::MarketplaceNoConfigurado));
         }
 
         #[ink::test]
-        fn productos_mas_vendidos_retorna_vacio_si_no_hay_marketplace() {
+        fn estadisticas_por_categoria_falla_si_no_hay_marketplace() {
+            let reportes = init_reportes_view(marketplace_nulo());
+            assert_eq!(
+                reportes.estadisticas_por_categoria(),
+                Err(ReportesError::MarketplaceNoConfigurado)
+            );
+        }
+
+        #[ink::test]
+        fn top_n_falla_si_no_hay_marketplace() {
+            let reportes = init_reportes_view(marketplace_nulo());
+            assert_eq!(reportes.top_vendedores(10), Err(ReportesError::MarketplaceNoConfigurado));
+            assert_eq!(reportes.top_compradores(u32::MAX), Err(ReportesError::MarketplaceNoConfigurado));
+        }
+
+        #[ink::test]
+        fn volumen_falla_si_no_hay_marketplace() {
             let accounts = default_accounts();
-            let reportes = init_reportes_view(accounts.charlie);
-            // Debería retornar vacío si la llamada falla
-            let productos = reportes.productos_mas_vendidos();
-            assert_eq!(productos.len(), 0);
+            let reportes = init_reportes_view(marketplace_nulo());
+            assert_eq!(reportes.volumen_total(), Err(ReportesError::MarketplaceNoConfigurado));
+            assert_eq!(
+                reportes.revenue_por_vendedor(accounts.bob),
+                Err(ReportesError::MarketplaceNoConfigurado)
+            );
         }
 
         #[ink::test]
-        fn estadisticas_por_categoria_retorna_vacio_si_no_hay_marketplace() {
+        fn peores_vendedores_falla_si_no_hay_marketplace() {
+            let reportes = init_reportes_view(marketplace_nulo());
+            assert_eq!(reportes.peores_vendedores(5), Err(ReportesError::MarketplaceNoConfigurado));
+        }
+
+        #[ink::test]
+        fn tiempo_envio_promedio_falla_si_no_hay_marketplace() {
             let accounts = default_accounts();
-            let reportes = init_reportes_view(accounts.charlie);
-            // Debería retornar vacío si la llamada falla
-            let stats = reportes.estadisticas_por_categoria();
-            assert_eq!(stats.len(), 0);
+            let reportes = init_reportes_view(marketplace_nulo());
+            assert_eq!(
+                reportes.tiempo_envio_promedio(accounts.bob),
+                Err(ReportesError::MarketplaceNoConfigurado)
+            );
+        }
+
+        #[ink::test]
+        fn mejor_categoria_desempata_por_nombre_e_ignora_sin_calificaciones() {
+            let reportes = init_reportes_view(marketplace_nulo());
+            assert_eq!(
+                reportes.mejor_categoria_por_calificacion(),
+                Err(ReportesError::MarketplaceNoConfigurado)
+            );
+
+            let estadisticas = vec![
+                ("Hogar".into(), 3, Some(4)),
+                ("Libros".into(), 9, None),
+                ("Ropa".into(), 1, Some(4)),
+                ("Autos".into(), 2, Some(2)),
+            ];
+            assert_eq!(ReportesView::_mejor_categoria(estadisticas), Some(("Hogar".into(), 4)));
+            assert_eq!(ReportesView::_mejor_categoria(vec![("Libros".into(), 9, None)]), None);
         }
     }
 
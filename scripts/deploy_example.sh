@@ -6,6 +6,11 @@
 # Configuración
 TESTNET_URL="wss://rpc.shibuya.astar.network"
 DEPLOYER_SURI="//Alice"  # Cambia esto por tu seed phrase o account
+COMISION_BPS=250  # Comisión del marketplace en puntos básicos (250 = 2,5%)
+TIMEOUT_ENVIO=604800000  # Plazo de envío en milisegundos (7 días)
+CATEGORIAS_RESTRINGIDAS=false  # true para aceptar solo las categorías que habilite el owner
+TOKEN="None"  # O "Some(<AccountId>)" para cobrar con un token PSP22 en lugar del balance nativo
+DECIMALES=0  # Decimales con que se muestran los precios (solo informativo)
 MARKETPLACE_CODE_HASH=""  # Se llenará después de subir el contrato
 
 echo "=== Paso 1: Subiendo contrato Marketplace ==="
@@ -21,6 +26,7 @@ MARKETPLACE_ADDRESS=$(cargo contract instantiate \
   --suri "$DEPLOYER_SURI" \
   --url "$TESTNET_URL" \
   --constructor new \
+  --args "$COMISION_BPS" "$TIMEOUT_ENVIO" "$CATEGORIAS_RESTRINGIDAS" "$TOKEN" "$DECIMALES" \
   --execute \
   --output-json | jq -r '.contract')
 